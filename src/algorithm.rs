@@ -0,0 +1,140 @@
+use super::codec;
+use super::error::CgnCliError;
+use bit_vec::BitVec;
+use cgn::pgn_data::PgnData;
+use clap::ValueEnum;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A library function that compresses a PgnData struct
+pub type CompressFn = fn(&PgnData) -> anyhow::Result<BitVec>;
+
+/// A library function that decompresses a bit vector into a PgnData struct
+pub type DecompressFn = fn(&BitVec) -> anyhow::Result<PgnData>;
+
+/// The compression algorithms available in the cgn library
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    Bincode,
+    Huffman,
+    DynamicHuffman,
+    OpeningHuffman,
+}
+
+impl Algorithm {
+    /// Get the algorithm for a numeric optimization level (0-3)
+    pub fn from_level(level: u8) -> Result<Self, CgnCliError> {
+        match level {
+            0 => Ok(Algorithm::Bincode),
+            1 => Ok(Algorithm::Huffman),
+            2 => Ok(Algorithm::DynamicHuffman),
+            3 => Ok(Algorithm::OpeningHuffman),
+            n => Err(CgnCliError::InvalidAlgorithm(n)),
+        }
+    }
+
+    /// Get the numeric optimization level (0-3) of the algorithm, also used as its ID in file headers
+    pub fn level(self) -> u8 {
+        match self {
+            Algorithm::Bincode => 0,
+            Algorithm::Huffman => 1,
+            Algorithm::DynamicHuffman => 2,
+            Algorithm::OpeningHuffman => 3,
+        }
+    }
+
+    /// Check that compressed data decompresses back to the PGN string it was compressed from.
+    /// The cgn library stores games in PGN reduced export format, so the comparison is made in that format.
+    pub fn round_trips(self, pgn_str: &str, compressed_data: &[u8]) -> bool {
+        codec::codec_for(self).round_trips(pgn_str, compressed_data)
+    }
+}
+
+/// Custom parameters for the Gaussian function that dynamic huffman uses to adjust the weights of its Huffman tree,
+/// as found by the genetic algorithm
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    pub height: f64,
+    pub dev: f64,
+}
+
+/// The algorithm to decompress with, either read from the file header or given explicitly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlgorithmChoice {
+    Auto,
+    Explicit(Algorithm),
+}
+
+impl FromStr for AlgorithmChoice {
+    type Err = String;
+
+    /// Parse an optimization level (0-3) or `auto`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(AlgorithmChoice::Auto),
+            _ => match s.parse::<u8>().map(Algorithm::from_level) {
+                Ok(Ok(algorithm)) => Ok(AlgorithmChoice::Explicit(algorithm)),
+                _ => Err(String::from(
+                    "Optimization level must be between 0 and 3, or 'auto'",
+                )),
+            },
+        }
+    }
+}
+
+/// The algorithm to compress with, either given explicitly or chosen by trying every algorithm
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlgorithmSelection {
+    Best,
+    Explicit(Algorithm),
+}
+
+impl AlgorithmSelection {
+    /// Get the algorithms to try, keeping whichever produces the smallest output
+    pub fn candidates(self) -> Vec<Algorithm> {
+        match self {
+            AlgorithmSelection::Best => Algorithm::value_variants().to_vec(),
+            AlgorithmSelection::Explicit(algorithm) => vec![algorithm],
+        }
+    }
+}
+
+impl FromStr for AlgorithmSelection {
+    type Err = String;
+
+    /// Parse an algorithm name or `best`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "best" => Ok(AlgorithmSelection::Best),
+            _ => <Algorithm as ValueEnum>::from_str(s, false)
+                .map(AlgorithmSelection::Explicit)
+                .map_err(|_| {
+                    String::from(
+                        "Algorithm must be bincode, huffman, dynamic-huffman, opening-huffman, or 'best'",
+                    )
+                }),
+        }
+    }
+}
+
+impl Display for AlgorithmSelection {
+    /// Display the selection using the same name accepted by `--algorithm`
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AlgorithmSelection::Best => write!(f, "best"),
+            AlgorithmSelection::Explicit(algorithm) => write!(f, "{}", algorithm),
+        }
+    }
+}
+
+impl Display for Algorithm {
+    /// Display the algorithm using the same name accepted by `--algorithm`
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::Bincode => write!(f, "bincode"),
+            Algorithm::Huffman => write!(f, "huffman"),
+            Algorithm::DynamicHuffman => write!(f, "dynamic-huffman"),
+            Algorithm::OpeningHuffman => write!(f, "opening-huffman"),
+        }
+    }
+}
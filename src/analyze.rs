@@ -0,0 +1,136 @@
+use super::algorithm::Algorithm;
+use super::codec::{codec_for, Codec};
+use super::error::CgnCliError;
+use super::io_util::open_reader;
+use super::output;
+use super::pgn_db::PgnDbIter;
+use cgn::pgn_data::PgnData;
+use std::io::{BufReader, Write};
+use std::str::FromStr;
+
+/// The last move number counted as the opening
+const OPENING_END_MOVE: usize = 10;
+
+/// The last move number counted as the middlegame, with every later move counted as the endgame
+const MIDDLEGAME_END_MOVE: usize = 40;
+
+/// The number of moves and compressed bits in a phase of the game
+#[derive(Clone, Copy, Debug, Default)]
+struct PhaseStats {
+    moves: usize,
+    bits: usize,
+}
+
+impl PhaseStats {
+    /// Get the average number of compressed bits per move, or 0 if the phase has no moves
+    fn bits_per_move(&self) -> f64 {
+        if self.moves == 0 {
+            return 0.0;
+        }
+        self.bits as f64 / self.moves as f64
+    }
+}
+
+/// Get the ply at which each phase ends, so the phases are the plies up to the first end,
+/// between the first and second end, and after the second end
+fn phase_ends() -> [usize; 2] {
+    [OPENING_END_MOVE * 2, MIDDLEGAME_END_MOVE * 2]
+}
+
+/// Compress the moves of a game, without its headers, to get the size of each phase.
+/// The size of a phase is the difference in compressed size between the game cut off before and after the phase,
+/// so the moves are compressed with the context of the moves played before them.
+fn analyze_game(pgn_str: &str, codec: &dyn Codec) -> anyhow::Result<[PhaseStats; 3]> {
+    let mut pgn_data = PgnData::from_str(pgn_str)?;
+    pgn_data.clear_headers();
+    let total_moves = pgn_data.moves.len();
+
+    // compress the game cut off at the end of each phase, working backwards from the full game
+    let [opening_end, middlegame_end] = phase_ends();
+    let mut bits = [0; 4];
+    for (i, ply) in [total_moves, middlegame_end, opening_end, 0]
+        .into_iter()
+        .enumerate()
+    {
+        pgn_data.moves.truncate(ply);
+        bits[3 - i] = codec.compress_pgn_data(&pgn_data)?.len();
+    }
+
+    let moves = [
+        total_moves.min(opening_end),
+        total_moves.clamp(opening_end, middlegame_end) - opening_end,
+        total_moves.max(middlegame_end) - middlegame_end,
+    ];
+    Ok([0, 1, 2].map(|phase| PhaseStats {
+        moves: moves[phase],
+        bits: bits[phase + 1].saturating_sub(bits[phase]),
+    }))
+}
+
+/// Compress every game in a PGN file with an algorithm and print the average compressed bits per move
+/// in the opening, middlegame, and endgame
+pub fn analyze(input_path: &str, algorithm: Algorithm) -> Result<(), CgnCliError> {
+    let codec = codec_for(algorithm);
+    let reader = BufReader::new(open_reader(input_path)?);
+
+    let mut phases = [PhaseStats::default(); 3];
+    let mut games = 0;
+    let mut failed = 0;
+    for pgn_str in PgnDbIter::new(reader) {
+        match analyze_game(&pgn_str, codec.as_ref()) {
+            Ok(game) => {
+                for (phase, stats) in phases.iter_mut().zip(game) {
+                    phase.moves += stats.moves;
+                    phase.bits += stats.bits;
+                }
+                games += 1;
+            }
+            Err(e) => {
+                log::warn!("Skipping game {}: {}", games + failed, e);
+                failed += 1;
+            }
+        }
+    }
+
+    // print a row for each phase, then the whole game
+    output::report(|out| {
+        writeln!(
+            out,
+            "Analyzed {} game(s) from {} with {}",
+            games, input_path, algorithm
+        )?;
+        if failed > 0 {
+            writeln!(out, "Skipped {} game(s) that failed to compress", failed)?;
+        }
+        let total = PhaseStats {
+            moves: phases.iter().map(|phase| phase.moves).sum(),
+            bits: phases.iter().map(|phase| phase.bits).sum(),
+        };
+        let labels = [
+            format!("Opening (1-{})", OPENING_END_MOVE),
+            format!(
+                "Middlegame ({}-{})",
+                OPENING_END_MOVE + 1,
+                MIDDLEGAME_END_MOVE
+            ),
+            format!("Endgame ({}+)", MIDDLEGAME_END_MOVE + 1),
+            String::from("Total"),
+        ];
+        writeln!(
+            out,
+            "{:<20} {:>10} {:>12} {:>14}",
+            "Phase", "Moves", "Bits", "Bits per move"
+        )?;
+        for (label, stats) in labels.iter().zip(phases.iter().chain([&total])) {
+            writeln!(
+                out,
+                "{:<20} {:>10} {:>12} {:>14.2}",
+                label,
+                stats.moves,
+                stats.bits,
+                stats.bits_per_move()
+            )?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,54 @@
+use super::algorithm::Algorithm;
+use super::archive::{append_games, game_count, Appended};
+use super::error::CgnCliError;
+use super::header::{read_header, FLAG_NORMALIZED};
+use super::io_util::{open_reader, write_atomically};
+use super::normalize::NormalizingReader;
+use super::output;
+use std::fs::{self, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+/// Compress the games in a PGN file and add them as new records to a streamed or indexed CGN file.
+/// Streamed files are extended in place, while indexed files are rewritten with the new records added to the index.
+/// If an algorithm is given, it must match the one the file was compressed with, as records cannot be mixed.
+/// New games are normalized if the file's games were, so the flag in its header stays true.
+pub fn append(
+    input_path: &str,
+    archive_path: &str,
+    algorithm: Option<Algorithm>,
+    verify: bool,
+) -> Result<(), CgnCliError> {
+    let compressed_data = fs::read(archive_path)?;
+    let (header, payload) = read_header(&compressed_data)?;
+    if let Some(requested) = algorithm.filter(|&requested| requested != header.algorithm) {
+        return Err(CgnCliError::AlgorithmMismatch {
+            requested,
+            found: header.algorithm,
+        });
+    }
+
+    let reader: Box<dyn Read> = if header.has_flag(FLAG_NORMALIZED) {
+        Box::new(NormalizingReader::new(open_reader(input_path)?))
+    } else {
+        Box::new(open_reader(input_path)?)
+    };
+    let reader = BufReader::new(reader);
+    let (appended, added) = append_games(&header, payload, reader, verify)?;
+    match appended {
+        Appended::Tail(records) => {
+            let mut archive = OpenOptions::new().append(true).open(archive_path)?;
+            archive.write_all(&records)?;
+            archive.flush()?;
+        }
+        Appended::Rewritten(output) => write_atomically(Path::new(archive_path), &output)?,
+    }
+
+    output::status(format!(
+        "Appended {} game(s) to {}, which now holds {} game(s)",
+        added,
+        archive_path,
+        game_count(&header, payload)? + added
+    ));
+    Ok(())
+}
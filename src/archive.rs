@@ -0,0 +1,677 @@
+use super::algorithm::Algorithm;
+use super::codec;
+use super::error::CgnCliError;
+use super::header::{
+    read_header_from, write_extended_file, write_extended_header, Extensions, Header, Metadata,
+    FLAG_INDEXED, FLAG_NORMALIZED, FLAG_STREAM,
+};
+use super::pgn_db::PgnDbIter;
+use super::profile;
+use rayon::prelude::*;
+use std::io::{self, BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+
+/// The number of bytes used for the length prefix of each record in a stream
+const RECORD_LEN_BYTES: usize = 4;
+
+/// The number of bytes used for the game count and each offset, length, and checksum in an index
+const INDEX_FIELD_BYTES: usize = 4;
+
+/// The number of bytes used for the CRC32 checksum of each record
+const RECORD_CHECKSUM_BYTES: usize = 4;
+
+/// The separator written between games when decompressing a stream
+pub const GAME_SEPARATOR: &str = "\n\n";
+
+/// Write a length-prefixed record to a stream, followed by its checksum
+fn write_record<W: Write>(writer: &mut W, record: &[u8]) -> Result<(), CgnCliError> {
+    let len = u32::try_from(record.len()).map_err(|_| CgnCliError::RecordTooLarge(record.len()))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(record)?;
+    writer.write_all(&crc32fast::hash(record).to_le_bytes())?;
+    Ok(())
+}
+
+/// Check a record against its checksum, if it has one
+fn check_record(record: &[u8], index: usize, checksum: Option<u32>) -> Result<&[u8], CgnCliError> {
+    match checksum {
+        Some(checksum) if checksum != crc32fast::hash(record) => {
+            Err(CgnCliError::RecordChecksumMismatch(index))
+        }
+        _ => Ok(record),
+    }
+}
+
+/// An iterator over the length-prefixed records in a stream payload.
+/// A record that does not match its checksum is an error, but the records after it can still be read.
+struct Records<'a> {
+    payload: &'a [u8],
+    checksums: bool,
+    index: usize,
+}
+
+impl<'a> Records<'a> {
+    /// Iterate over the records of a stream payload, checking their checksums if the file has them
+    fn new(header: &Header, payload: &'a [u8]) -> Self {
+        Records {
+            payload,
+            checksums: header.has_record_checksums(),
+            index: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<&'a [u8], CgnCliError>;
+
+    /// Get the next record in the stream
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.payload.is_empty() {
+            return None;
+        }
+
+        // read the length prefix and any checksum, failing if the stream is truncated
+        let checksum_len = if self.checksums {
+            RECORD_CHECKSUM_BYTES
+        } else {
+            0
+        };
+        let record = self
+            .payload
+            .split_first_chunk::<RECORD_LEN_BYTES>()
+            .map(|(len, rest)| (u32::from_le_bytes(*len) as usize, rest))
+            .filter(|(len, rest)| len.saturating_add(checksum_len) <= rest.len())
+            .map(|(len, rest)| rest.split_at(len));
+        let index = self.index;
+        self.index += 1;
+        match record {
+            Some((record, rest)) => {
+                let (checksum, rest) = match rest.split_first_chunk::<RECORD_CHECKSUM_BYTES>() {
+                    Some((checksum, rest)) if self.checksums => {
+                        (Some(u32::from_le_bytes(*checksum)), rest)
+                    }
+                    _ => (None, rest),
+                };
+                self.payload = rest;
+                Some(check_record(record, index, checksum))
+            }
+            None => {
+                self.payload = &[];
+                Some(Err(CgnCliError::TruncatedRecord))
+            }
+        }
+    }
+}
+
+/// Read a little-endian index field from the start of a slice, returning it with the rest of the slice
+fn read_index_field(data: &[u8]) -> Result<(usize, &[u8]), CgnCliError> {
+    data.split_first_chunk::<INDEX_FIELD_BYTES>()
+        .map(|(field, rest)| (u32::from_le_bytes(*field) as usize, rest))
+        .ok_or(CgnCliError::TruncatedRecord)
+}
+
+/// The index at the start of an indexed payload, giving the offset, length, and any checksum of each game's record
+struct GameIndex<'a> {
+    entries: Vec<(usize, usize, Option<u32>)>,
+    records: &'a [u8],
+}
+
+impl<'a> GameIndex<'a> {
+    /// Parse the index from the start of an indexed payload: a game count, then an offset and length
+    /// for each game relative to the end of the index, followed by the checksum of its record if the file has them,
+    /// then the records themselves
+    fn parse(header: &Header, payload: &'a [u8]) -> Result<Self, CgnCliError> {
+        let checksums = header.has_record_checksums();
+        let entry_fields = 2 + usize::from(checksums);
+        let (count, mut rest) = read_index_field(payload)?;
+        let mut entries =
+            Vec::with_capacity(count.min(rest.len() / (entry_fields * INDEX_FIELD_BYTES)));
+        for _ in 0..count {
+            let (offset, after_offset) = read_index_field(rest)?;
+            let (len, after_len) = read_index_field(after_offset)?;
+            rest = after_len;
+            let checksum = if checksums {
+                let (checksum, after_checksum) = read_index_field(rest)?;
+                rest = after_checksum;
+                Some(checksum as u32)
+            } else {
+                None
+            };
+            entries.push((offset, len, checksum));
+        }
+        Ok(GameIndex {
+            entries,
+            records: rest,
+        })
+    }
+
+    /// Get the number of games in the index
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Get the compressed record of a game, failing if the record lies outside the payload or does not match its
+    /// checksum
+    fn record(&self, index: usize) -> Result<&'a [u8], CgnCliError> {
+        let (offset, len, checksum) = self.entries[index];
+        let record = offset
+            .checked_add(len)
+            .and_then(|end| self.records.get(offset..end))
+            .ok_or(CgnCliError::TruncatedRecord)?;
+        check_record(record, index, checksum)
+    }
+}
+
+/// Build an indexed payload from the compressed record of each game, with the checksum of each record in the index
+fn indexed_payload(records: &[Vec<u8>]) -> Result<Vec<u8>, CgnCliError> {
+    let to_field = |n: usize| u32::try_from(n).map_err(|_| CgnCliError::RecordTooLarge(n));
+    let index_len = INDEX_FIELD_BYTES * (1 + 3 * records.len());
+    let records_len = records.iter().map(Vec::len).sum::<usize>();
+    let mut payload = Vec::with_capacity(index_len + records_len);
+
+    // write the index, then the records in the same order
+    payload.extend(to_field(records.len())?.to_le_bytes());
+    let mut offset = 0;
+    for record in records {
+        payload.extend(to_field(offset)?.to_le_bytes());
+        payload.extend(to_field(record.len())?.to_le_bytes());
+        payload.extend(crc32fast::hash(record).to_le_bytes());
+        offset += record.len();
+    }
+    for record in records {
+        payload.extend(record);
+    }
+    Ok(payload)
+}
+
+/// A writer of a streamed file, compressing each game given to it into a length-prefixed record
+pub struct StreamWriter<W: Write> {
+    writer: W,
+    algorithm: Algorithm,
+    verify: bool,
+    /// The number of games written so far
+    pub games: usize,
+}
+
+impl<W: Write> StreamWriter<W> {
+    /// Start a streamed file compressed with the algorithm by writing its header.
+    /// If `verify` is set, each game is checked to decompress back to itself before it is written.
+    pub fn new(
+        mut writer: W,
+        algorithm: Algorithm,
+        extensions: &Extensions,
+        verify: bool,
+    ) -> Result<Self, CgnCliError> {
+        write_extended_header(&mut writer, algorithm, FLAG_STREAM, None, extensions)?;
+        Ok(Self {
+            writer,
+            algorithm,
+            verify,
+            games: 0,
+        })
+    }
+
+    /// Compress a game and write it as the next record
+    pub fn write_game(&mut self, pgn_str: &str) -> Result<(), CgnCliError> {
+        let compressed_pgn_data = codec::compress(self.algorithm, pgn_str)
+            .map_err(|e| CgnCliError::GameCompressionFailed(self.games, e))?;
+        if self.verify && !self.algorithm.round_trips(pgn_str, &compressed_pgn_data) {
+            return Err(CgnCliError::RoundTripFailed(self.algorithm));
+        }
+        profile::time("write", || {
+            write_record(&mut self.writer, &compressed_pgn_data)
+        })?;
+        self.games += 1;
+        Ok(())
+    }
+
+    /// Get the underlying writer back once every game is written
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Compress the games read from a PGN database one at a time, writing each as a length-prefixed record.
+/// Only a single game is held in memory at once. Returns the number of games written.
+pub fn compress_stream<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    algorithm: Algorithm,
+    extensions: &Extensions,
+    verify: bool,
+) -> Result<usize, CgnCliError> {
+    let mut stream = StreamWriter::new(writer, algorithm, extensions, verify)?;
+    let mut games = PgnDbIter::new(reader);
+    while let Some(pgn_str) = profile::time("read", || games.next()) {
+        stream.write_game(&pgn_str)?;
+    }
+    Ok(stream.games)
+}
+
+/// Compress each game in a PGN database into its own record
+fn compress_records<R: BufRead>(
+    reader: R,
+    algorithm: Algorithm,
+    verify: bool,
+) -> Result<Vec<Vec<u8>>, CgnCliError> {
+    let mut records = Vec::new();
+    for (i, pgn_str) in PgnDbIter::new(reader).enumerate() {
+        let compressed_pgn_data = codec::compress(algorithm, &pgn_str)
+            .map_err(|e| CgnCliError::GameCompressionFailed(i, e))?;
+        if verify && !algorithm.round_trips(&pgn_str, &compressed_pgn_data) {
+            return Err(CgnCliError::RoundTripFailed(algorithm));
+        }
+        records.push(compressed_pgn_data);
+    }
+    Ok(records)
+}
+
+/// Compress each game in a PGN database separately, writing an index of the games followed by their records
+/// so any game can be decompressed on its own. Returns the number of games written.
+pub fn compress_indexed<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    algorithm: Algorithm,
+    extensions: &Extensions,
+    verify: bool,
+) -> Result<usize, CgnCliError> {
+    let records = compress_records(reader, algorithm, verify)?;
+    let payload = indexed_payload(&records)?;
+    write_extended_file(writer, algorithm, FLAG_INDEXED, extensions, &payload)?;
+    Ok(records.len())
+}
+
+/// Get the compressed record of every game in a streamed or indexed payload
+fn game_records<'a>(header: &Header, payload: &'a [u8]) -> Result<Vec<&'a [u8]>, CgnCliError> {
+    scan_records(header, payload)?.into_iter().collect()
+}
+
+/// Get every record of a streamed or indexed payload, or why it cannot be read, checking each against its checksum
+/// without stopping at the first bad record. A truncated stream ends with the error of the record it cuts off, as
+/// the records after it cannot be found. Fails only if the index of an indexed payload cannot be read.
+pub fn scan_records<'a>(
+    header: &Header,
+    payload: &'a [u8],
+) -> Result<Vec<Result<&'a [u8], CgnCliError>>, CgnCliError> {
+    if header.has_flag(FLAG_INDEXED) {
+        let index = GameIndex::parse(header, payload)?;
+        Ok((0..index.len()).map(|i| index.record(i)).collect())
+    } else {
+        Ok(Records::new(header, payload).collect())
+    }
+}
+
+/// Check if a payload holds a single compressed game rather than one record per game
+pub fn is_single_game(header: &Header) -> bool {
+    !header.has_flag(FLAG_STREAM) && !header.has_flag(FLAG_INDEXED)
+}
+
+/// Check if a single-game payload is empty, which is how an input with no games is compressed
+fn is_empty_archive(header: &Header, payload: &[u8]) -> bool {
+    is_single_game(header) && payload.is_empty()
+}
+
+/// Decompress the payload of a CGN file game by game, passing each game and its index to a function as soon as it is
+/// decompressed
+pub fn decompress_each(
+    header: &Header,
+    payload: &[u8],
+    mut f: impl FnMut(usize, String) -> Result<(), CgnCliError>,
+) -> Result<(), CgnCliError> {
+    header.verify_checksum(payload)?;
+
+    if is_single_game(header) {
+        let pgn_str = header
+            .decompress(payload)
+            .map_err(CgnCliError::DecompressionFailed)?;
+        return f(0, pgn_str);
+    }
+
+    for (i, record) in game_records(header, payload)?.into_iter().enumerate() {
+        let pgn_str = header
+            .decompress(record)
+            .map_err(|e| CgnCliError::GameDecompressionFailed(i, e))?;
+        f(i, pgn_str)?;
+    }
+    Ok(())
+}
+
+/// Decompress the payload of a CGN file, writing the PGN data to the writer as each game is decompressed
+pub fn decompress_to<W: Write>(
+    header: &Header,
+    payload: &[u8],
+    writer: &mut W,
+) -> Result<(), CgnCliError> {
+    decompress_each(header, payload, |i, pgn_str| {
+        if i > 0 {
+            writer.write_all(GAME_SEPARATOR.as_bytes())?;
+        }
+        writer.write_all(pgn_str.as_bytes())?;
+        Ok(())
+    })
+}
+
+/// Count the games in the payload of a CGN file without decompressing them
+pub fn game_count(header: &Header, payload: &[u8]) -> Result<usize, CgnCliError> {
+    if is_empty_archive(header, payload) {
+        return Ok(0);
+    }
+    if is_single_game(header) {
+        return Ok(1);
+    }
+    if header.has_flag(FLAG_INDEXED) {
+        return Ok(GameIndex::parse(header, payload)?.len());
+    }
+    Ok(game_records(header, payload)?.len())
+}
+
+/// Decompress a single game from the payload of a CGN file, using the index to find it in indexed files
+pub fn extract_game(header: &Header, payload: &[u8], index: usize) -> Result<String, CgnCliError> {
+    header.verify_checksum(payload)?;
+
+    // find the record of the game, or the whole payload if it holds a single game
+    let count = game_count(header, payload)?;
+    if index >= count {
+        return Err(CgnCliError::GameOutOfRange { index, count });
+    }
+    let record = if is_single_game(header) {
+        payload
+    } else if header.has_flag(FLAG_INDEXED) {
+        GameIndex::parse(header, payload)?.record(index)?
+    } else {
+        game_records(header, payload)?[index]
+    };
+
+    header
+        .decompress(record)
+        .map_err(|e| CgnCliError::GameDecompressionFailed(index, e))
+}
+
+/// Read a little-endian length prefix or index field from a reader, failing if the reader ends first
+fn read_field_from<R: Read>(reader: &mut R) -> Result<usize, CgnCliError> {
+    let mut field = [0; INDEX_FIELD_BYTES];
+    reader.read_exact(&mut field).map_err(truncated)?;
+    Ok(u32::from_le_bytes(field) as usize)
+}
+
+/// Read a record of a known length from a reader, failing if the reader ends first. The record is read as it comes
+/// rather than allocated up front, as its length is not yet known to be valid.
+fn read_record_from<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, CgnCliError> {
+    let mut record = Vec::new();
+    reader.take(len as u64).read_to_end(&mut record)?;
+    if record.len() < len {
+        return Err(CgnCliError::TruncatedRecord);
+    }
+    Ok(record)
+}
+
+/// Report a reader ending part way through a field as a truncated record
+fn truncated(e: io::Error) -> CgnCliError {
+    match e.kind() {
+        ErrorKind::UnexpectedEof => CgnCliError::TruncatedRecord,
+        _ => CgnCliError::Io(e),
+    }
+}
+
+/// Decompress a single game from a CGN file read from a seekable reader, such as a file. Only the header, the record
+/// of the game, and either its entry in the index or the length prefixes of the records before it are read from a
+/// streamed or indexed file, and only the record of the game is checked against its checksum, so a corrupt record
+/// does not stop the other games from being extracted. A single payload is read and checked as a whole.
+pub fn extract_game_from<R: Read + Seek>(
+    reader: &mut R,
+    index: usize,
+) -> Result<String, CgnCliError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let (header, header_len) = read_header_from(reader)?;
+    if is_single_game(&header) {
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload)?;
+        return extract_game(&header, &payload, index);
+    }
+
+    let checksums = header.has_record_checksums();
+    let record = if header.has_flag(FLAG_INDEXED) {
+        // find the entry of the game in the index, then its record after the end of the index
+        let count = read_field_from(reader)?;
+        if index >= count {
+            return Err(CgnCliError::GameOutOfRange { index, count });
+        }
+        let entry_len = (INDEX_FIELD_BYTES * (2 + usize::from(checksums))) as u64;
+        let records_start = header_len + INDEX_FIELD_BYTES as u64 + count as u64 * entry_len;
+        reader.seek(SeekFrom::Current((index as u64 * entry_len) as i64))?;
+        let offset = read_field_from(reader)?;
+        let len = read_field_from(reader)?;
+        let checksum = checksums
+            .then(|| read_field_from(reader))
+            .transpose()?
+            .map(|checksum| checksum as u32);
+        reader.seek(SeekFrom::Start(records_start + offset as u64))?;
+        check_record(&read_record_from(reader, len)?, index, checksum)?.to_vec()
+    } else {
+        // skip over the records before the game by their length prefixes, without reading them
+        let checksum_len = if checksums { RECORD_CHECKSUM_BYTES } else { 0 };
+        let mut position = header_len;
+        let mut i = 0;
+        loop {
+            if position == file_len {
+                return Err(CgnCliError::GameOutOfRange { index, count: i });
+            }
+            let len = read_field_from(reader)?;
+            position += (RECORD_LEN_BYTES + len + checksum_len) as u64;
+            if position > file_len {
+                return Err(CgnCliError::TruncatedRecord);
+            }
+            if i == index {
+                let record = read_record_from(reader, len)?;
+                let checksum = checksums
+                    .then(|| read_field_from(reader))
+                    .transpose()?
+                    .map(|checksum| checksum as u32);
+                break check_record(&record, index, checksum)?.to_vec();
+            }
+            reader.seek(SeekFrom::Start(position))?;
+            i += 1;
+        }
+    };
+
+    header
+        .decompress(&record)
+        .map_err(|e| CgnCliError::GameDecompressionFailed(index, e))
+}
+
+/// Decompress the records of a streamed or indexed payload one at a time, so only a single game is held in memory
+pub fn decompress_records<'a>(
+    header: &'a Header,
+    payload: &'a [u8],
+) -> Result<impl Iterator<Item = Result<String, CgnCliError>> + 'a, CgnCliError> {
+    header.verify_checksum(payload)?;
+    let records = game_records(header, payload)?;
+    Ok(records.into_iter().enumerate().map(move |(i, record)| {
+        header
+            .decompress(record)
+            .map_err(|e| CgnCliError::GameDecompressionFailed(i, e))
+    }))
+}
+
+/// Decompress the payload of a CGN file into a PGN string
+pub fn decompress(header: &Header, payload: &[u8]) -> Result<String, CgnCliError> {
+    let mut pgn_data = Vec::new();
+    decompress_to(header, payload, &mut pgn_data)?;
+    String::from_utf8(pgn_data)
+        .map_err(|e| CgnCliError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+/// Join the records of streamed or indexed files compressed with the same algorithm into the contents of one indexed
+/// file, in order and without re-compressing them, returning it along with the number of games it holds.
+/// The result is only marked as normalized if every file is.
+pub fn merge_records(files: &[(Header, &[u8])]) -> Result<(Vec<u8>, usize), CgnCliError> {
+    let mut records = Vec::new();
+    for (header, payload) in files {
+        header.verify_checksum(payload)?;
+        records.extend(
+            game_records(header, payload)?
+                .into_iter()
+                .map(<[u8]>::to_vec),
+        );
+    }
+    let extensions = Extensions {
+        normalized: files
+            .iter()
+            .all(|(header, _)| header.has_flag(FLAG_NORMALIZED)),
+        metadata: Some(Metadata::current()),
+        ..Extensions::default()
+    };
+    let algorithm = files
+        .first()
+        .map_or(Algorithm::Bincode, |(header, _)| header.algorithm);
+    let mut output = Vec::new();
+    write_extended_file(
+        &mut output,
+        algorithm,
+        FLAG_INDEXED,
+        &extensions,
+        &indexed_payload(&records)?,
+    )?;
+    Ok((output, records.len()))
+}
+
+/// Write the given records of a streamed or indexed file as the contents of a new indexed file, keeping the
+/// algorithm and extensions of the file they came from
+pub fn indexed_file(header: &Header, records: &[&[u8]]) -> Result<Vec<u8>, CgnCliError> {
+    let records = records
+        .iter()
+        .map(|record| record.to_vec())
+        .collect::<Vec<_>>();
+    let mut output = Vec::new();
+    write_extended_file(
+        &mut output,
+        header.algorithm,
+        FLAG_INDEXED,
+        &header.kept_extensions(),
+        &indexed_payload(&records)?,
+    )?;
+    Ok(output)
+}
+
+/// Decompress the payload of a CGN file into a PGN string like `decompress`, decompressing the records of a streamed
+/// or indexed file in parallel on the current rayon thread pool. The games are joined in their order in the file,
+/// however the threads finish. A payload holding a single game can only be decompressed on one thread.
+pub fn decompress_parallel(header: &Header, payload: &[u8]) -> Result<String, CgnCliError> {
+    if is_single_game(header) {
+        return decompress(header, payload);
+    }
+    header.verify_checksum(payload)?;
+    let records = game_records(header, payload)?;
+    let games = records
+        .par_iter()
+        .enumerate()
+        .map(|(i, record)| {
+            header
+                .decompress(record)
+                .map_err(|e| CgnCliError::GameDecompressionFailed(i, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(games.join(GAME_SEPARATOR))
+}
+
+/// Re-compress the payload of a CGN file with a different algorithm, returning the contents of the new file.
+/// Streamed and indexed files keep their layout, with each record transcoded individually.
+pub fn transcode(header: &Header, payload: &[u8], to: Algorithm) -> Result<Vec<u8>, CgnCliError> {
+    header.verify_checksum(payload)?;
+    let extensions = header.kept_extensions();
+    let mut output = Vec::new();
+
+    if is_single_game(header) {
+        let compressed_pgn_data = codec::compress(to, &decompress(header, payload)?)
+            .map_err(CgnCliError::CompressionFailed)?;
+        write_extended_file(&mut output, to, 0, &extensions, &compressed_pgn_data)?;
+        return Ok(output);
+    }
+
+    // transcode each record, keeping them in order
+    let mut records = Vec::new();
+    for (i, record) in game_records(header, payload)?.into_iter().enumerate() {
+        let pgn_str = header
+            .decompress(record)
+            .map_err(|e| CgnCliError::GameDecompressionFailed(i, e))?;
+        let compressed_pgn_data =
+            codec::compress(to, &pgn_str).map_err(|e| CgnCliError::GameCompressionFailed(i, e))?;
+        records.push(compressed_pgn_data);
+    }
+
+    if header.has_flag(FLAG_INDEXED) {
+        let payload = indexed_payload(&records)?;
+        write_extended_file(&mut output, to, FLAG_INDEXED, &extensions, &payload)?;
+    } else {
+        write_extended_header(&mut output, to, FLAG_STREAM, None, &extensions)?;
+        for record in &records {
+            write_record(&mut output, record)?;
+        }
+    }
+    Ok(output)
+}
+
+/// The bytes to write to add games to a streamed or indexed file
+pub enum Appended {
+    /// Records to write at the end of a streamed file, leaving the rest of the file unchanged
+    Tail(Vec<u8>),
+    /// The whole file rewritten with the new records, as its index or checksum covers every record
+    Rewritten(Vec<u8>),
+}
+
+/// Compress the games read from a PGN database with the algorithm of a streamed or indexed file,
+/// returning the bytes to write to add them as new records along with the number of games added.
+/// New records must match the algorithm of the existing ones, so other files are rejected. Files of a format version
+/// without record checksums are rewritten in the current version, as their records are laid out differently.
+pub fn append_games<R: BufRead>(
+    header: &Header,
+    payload: &[u8],
+    reader: R,
+    verify: bool,
+) -> Result<(Appended, usize), CgnCliError> {
+    if is_single_game(header) || header.tuning.is_some() {
+        return Err(CgnCliError::NotAppendable);
+    }
+    header.verify_checksum(payload)?;
+    let new_records = compress_records(reader, header.algorithm, verify)?;
+    let added = new_records.len();
+
+    // streamed files with checksummed records and no checksum of the whole payload can be extended in place
+    if header.has_flag(FLAG_STREAM) && header.checksum.is_none() && header.has_record_checksums() {
+        let mut tail = Vec::new();
+        for record in &new_records {
+            write_record(&mut tail, record)?;
+        }
+        return Ok((Appended::Tail(tail), added));
+    }
+
+    let extensions = header.kept_extensions();
+    let mut output = Vec::new();
+    let mut records = game_records(header, payload)?
+        .into_iter()
+        .map(<[u8]>::to_vec)
+        .collect::<Vec<_>>();
+    records.extend(new_records);
+    if header.has_flag(FLAG_INDEXED) {
+        write_extended_file(
+            &mut output,
+            header.algorithm,
+            FLAG_INDEXED,
+            &extensions,
+            &indexed_payload(&records)?,
+        )?;
+    } else {
+        let mut stream = Vec::new();
+        for record in &records {
+            write_record(&mut stream, record)?;
+        }
+        write_extended_file(
+            &mut output,
+            header.algorithm,
+            FLAG_STREAM,
+            &extensions,
+            &stream,
+        )?;
+    }
+    Ok((Appended::Rewritten(output), added))
+}
@@ -0,0 +1,214 @@
+use super::error::CgnCliError;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+
+/// The results of an algorithm that are compared between benchmark runs, as written to a JSON benchmark report.
+/// Lower is better for every one of them.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BaselineResult {
+    pub algorithm: String,
+    pub compression_ratio: f64,
+    pub avg_time_to_compress: f64,
+    pub avg_time_to_decompress: f64,
+}
+
+/// The parts of a JSON benchmark report read back as a baseline, ignoring everything else in it
+#[derive(Deserialize)]
+struct BaselineReport {
+    algorithms: Vec<BaselineResult>,
+}
+
+/// Read the results of every algorithm from a JSON benchmark report written by `bench --format json` or to a .json file
+pub fn load_baseline(path: &str) -> Result<Vec<BaselineResult>, CgnCliError> {
+    let reader = BufReader::new(File::open(path)?);
+    let report: BaselineReport = serde_json::from_reader(reader)
+        .map_err(|e| CgnCliError::InvalidBaseline(format!("{}: {}", path, e)))?;
+    Ok(report.algorithms)
+}
+
+/// The smallest change in an average time, in seconds, that can count as a regression, as smaller changes are within
+/// the noise of timing the same build twice
+const MIN_TIME_CHANGE_SECS: f64 = 0.000_1;
+
+/// How a metric of an algorithm changed from the baseline
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricChange {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    /// The change as a percentage of the baseline, positive when the metric got worse
+    pub change_percent: f64,
+    /// Whether the metric got worse by more than its tolerance
+    pub regressed: bool,
+}
+
+/// How the metrics of an algorithm changed from the baseline
+#[derive(Clone, Debug, PartialEq)]
+pub struct BaselineComparison {
+    pub algorithm: String,
+    pub changes: Vec<MetricChange>,
+}
+
+impl BaselineComparison {
+    /// Check if any metric of the algorithm regressed
+    pub fn regressed(&self) -> bool {
+        self.changes.iter().any(|change| change.regressed)
+    }
+}
+
+/// Get the change in a metric as a percentage of its baseline. A metric rising from zero is an infinite change.
+fn change_percent(baseline: f64, current: f64) -> f64 {
+    if baseline == 0.0 {
+        if current > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        }
+    } else {
+        (current - baseline) / baseline * 100.0
+    }
+}
+
+/// Compare the results of each algorithm against its results in the baseline, flagging the compression ratio if it got
+/// worse by more than `tolerance_percent` of its baseline value, and a timing if it got worse by more than
+/// `time_tolerance_percent` and by more than the noise of timing a run. Algorithms missing from the baseline are left
+/// out.
+pub fn compare_to_baseline(
+    current: &[BaselineResult],
+    baseline: &[BaselineResult],
+    tolerance_percent: f64,
+    time_tolerance_percent: f64,
+) -> Vec<BaselineComparison> {
+    current
+        .iter()
+        .filter_map(|result| {
+            let base = baseline
+                .iter()
+                .find(|base| base.algorithm == result.algorithm)?;
+            let changes = [
+                (
+                    "compression ratio",
+                    base.compression_ratio,
+                    result.compression_ratio,
+                    tolerance_percent,
+                    0.0,
+                ),
+                (
+                    "time to compress",
+                    base.avg_time_to_compress,
+                    result.avg_time_to_compress,
+                    time_tolerance_percent,
+                    MIN_TIME_CHANGE_SECS,
+                ),
+                (
+                    "time to decompress",
+                    base.avg_time_to_decompress,
+                    result.avg_time_to_decompress,
+                    time_tolerance_percent,
+                    MIN_TIME_CHANGE_SECS,
+                ),
+            ]
+            .into_iter()
+            .map(
+                |(metric, baseline, current, tolerance_percent, min_change)| {
+                    let change_percent = change_percent(baseline, current);
+                    MetricChange {
+                        metric,
+                        baseline,
+                        current,
+                        change_percent,
+                        regressed: change_percent > tolerance_percent
+                            && current - baseline > min_change,
+                    }
+                },
+            )
+            .collect();
+            Some(BaselineComparison {
+                algorithm: result.algorithm.clone(),
+                changes,
+            })
+        })
+        .collect()
+}
+
+/// Format the comparison of each algorithm against the baseline as a table, marking every metric that regressed
+pub fn comparison_table(comparisons: &[BaselineComparison]) -> String {
+    let mut table = String::new();
+    for comparison in comparisons {
+        table.push_str(&format!("{}:\n", comparison.algorithm));
+        for change in &comparison.changes {
+            table.push_str(&format!(
+                "  {:<20} {:>12.6} -> {:>12.6} {:>+9.2}%{}\n",
+                change.metric,
+                change.baseline,
+                change.current,
+                change.change_percent,
+                if change.regressed { "  regressed" } else { "" }
+            ));
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the results of bincode with a compression ratio and the same time to compress and decompress, in seconds
+    fn result(compression_ratio: f64, time: f64) -> BaselineResult {
+        BaselineResult {
+            algorithm: String::from("bincode"),
+            compression_ratio,
+            avg_time_to_compress: time,
+            avg_time_to_decompress: time,
+        }
+    }
+
+    /// Get the metrics of an algorithm that regressed from its baseline, with a 5% tolerance and 25% for timings
+    fn regressed(current: BaselineResult, baseline: BaselineResult) -> Vec<&'static str> {
+        let comparisons = compare_to_baseline(&[current], &[baseline], 5.0, 25.0);
+        assert_eq!(comparisons.len(), 1);
+        comparisons[0]
+            .changes
+            .iter()
+            .filter(|change| change.regressed)
+            .map(|change| change.metric)
+            .collect()
+    }
+
+    #[test]
+    fn flags_regressions() {
+        assert_eq!(
+            regressed(result(0.5, 0.01), result(0.4, 0.01)),
+            ["compression ratio"]
+        );
+        assert_eq!(
+            regressed(result(0.4, 0.02), result(0.4, 0.01)),
+            ["time to compress", "time to decompress"]
+        );
+    }
+
+    #[test]
+    fn improvements_do_not_regress() {
+        assert!(regressed(result(0.3, 0.005), result(0.4, 0.01)).is_empty());
+    }
+
+    #[test]
+    fn changes_within_the_tolerance_do_not_regress() {
+        assert!(regressed(result(0.41, 0.012), result(0.4, 0.01)).is_empty());
+    }
+
+    #[test]
+    fn timing_noise_does_not_regress() {
+        // doubling a time of 20 microseconds is beyond the tolerance but within the noise of timing a run
+        assert!(regressed(result(0.4, 0.000_04), result(0.4, 0.000_02)).is_empty());
+    }
+
+    #[test]
+    fn algorithms_missing_from_the_baseline_are_left_out() {
+        let mut baseline = result(0.4, 0.01);
+        baseline.algorithm = String::from("huffman");
+        assert!(compare_to_baseline(&[result(0.4, 0.01)], &[baseline], 5.0, 25.0).is_empty());
+    }
+}
@@ -0,0 +1,505 @@
+use super::algorithm::Algorithm;
+use super::benchmark::compression_ratio;
+use super::codec;
+use super::compress::{warn_if_exists, CompressSummary};
+use super::crypto::encrypt_file;
+use super::encoding::{DecodingReader, Encoding};
+use super::error::CgnCliError;
+use super::header::{write_extended_file, Extensions, Metadata};
+use super::interrupt::{catch_interrupts, is_interrupted};
+use super::io_util::{
+    check_overwrite, is_url, open_file_reader, strip_compression_extension, write_atomically,
+};
+use super::normalize::NormalizingReader;
+use super::output;
+use super::progress::progress_bar;
+use filetime::FileTime;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// The extension of the PGN files compressed in batch mode
+const PGN_EXTENSION: &str = "pgn";
+
+/// The template used to name compressed files in batch mode, unless another is given
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{stem}.cgn";
+
+/// A piece of an output template, either literal text or a placeholder expanded per input file
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Stem,
+    Ext,
+    Algo,
+    Parent,
+}
+
+/// A template for the file name of each compressed file in batch mode, such as `{stem}.{algo}.cgn`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl OutputTemplate {
+    /// Check if the template names each file after its input, so the files of a batch get different names
+    fn has_stem(&self) -> bool {
+        self.parts.contains(&TemplatePart::Stem)
+    }
+
+    /// Expand the placeholders for an input file compressed with an algorithm, naming a compressed input such as
+    /// `games.pgn.zst` after the file it decompresses to
+    fn expand(&self, input_path: &Path, algorithm: Algorithm) -> PathBuf {
+        let input_path = &strip_compression_extension(input_path);
+        let name = |s: Option<&OsStr>| s.unwrap_or_default().to_string_lossy().into_owned();
+        let mut file_name = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => file_name.push_str(text),
+                TemplatePart::Stem => file_name.push_str(&name(input_path.file_stem())),
+                TemplatePart::Ext => file_name.push_str(&name(input_path.extension())),
+                TemplatePart::Algo => file_name.push_str(&algorithm.to_string()),
+                TemplatePart::Parent => {
+                    file_name.push_str(&name(input_path.parent().and_then(Path::file_name)))
+                }
+            }
+        }
+        PathBuf::from(file_name)
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    /// Parse a template made up of text and the `{stem}`, `{ext}`, `{algo}`, and `{parent}` placeholders
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in output template '{}'", s))?;
+            parts.push(match &rest[start + 1..start + end] {
+                "stem" => TemplatePart::Stem,
+                "ext" => TemplatePart::Ext,
+                "algo" => TemplatePart::Algo,
+                "parent" => TemplatePart::Parent,
+                placeholder => {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' in output template, expected {{stem}}, {{ext}}, {{algo}}, or {{parent}}",
+                        placeholder
+                    ))
+                }
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+        if parts.is_empty() {
+            return Err(String::from("Output template must not be empty"));
+        }
+        Ok(OutputTemplate { parts })
+    }
+}
+
+/// Recursively find every PGN file in a directory, including those compressed with zstd, gzip, or bzip2, sorted by
+/// path
+fn find_pgn_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_pgn_files(&path)?);
+        } else if strip_compression_extension(&path)
+            .extension()
+            .is_some_and(|ext| ext == PGN_EXTENSION)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Compress a single PGN file and write it, with a header, to the output path, decompressing a `.zst`, `.gz`, or
+/// `.bz2` input first.
+/// If `preserve_times` is set, the modification time of the input is stored in the header and given to the output.
+/// Inputs larger than the maximum input size are rejected, and with `verify` the output is checked to round-trip.
+/// The output is encrypted with the passphrase if one is given. On a dry run, the file is only compressed in memory.
+fn compress_file(
+    input_path: &Path,
+    output_path: &Path,
+    options: &BatchOptions,
+    dry_run: bool,
+) -> Result<CompressSummary, CgnCliError> {
+    let algorithm = options.algorithm;
+    // read at most one byte past the limit to detect oversized inputs
+    let limit = options.max_input_size.saturating_add(1);
+    let reader = open_file_reader(&input_path.to_string_lossy())?;
+    let reader = DecodingReader::new(reader, options.encoding).take(limit);
+    let mut pgn_str = String::new();
+    if options.normalize {
+        NormalizingReader::new(reader).read_to_string(&mut pgn_str)?;
+    } else {
+        let mut reader = reader;
+        reader.read_to_string(&mut pgn_str)?;
+    }
+    if pgn_str.len() as u64 > options.max_input_size {
+        return Err(CgnCliError::InputTooLarge(options.max_input_size));
+    }
+    let compressed_pgn_data =
+        codec::compress(algorithm, &pgn_str).map_err(CgnCliError::CompressionFailed)?;
+    if options.verify && !algorithm.round_trips(&pgn_str, &compressed_pgn_data) {
+        return Err(CgnCliError::RoundTripFailed(algorithm));
+    }
+    let extensions = Extensions {
+        mtime: options
+            .preserve_times
+            .then(|| fs::metadata(input_path))
+            .transpose()?
+            .map(|metadata| FileTime::from_last_modification_time(&metadata)),
+        name: strip_compression_extension(input_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(String::from),
+        normalized: options.normalize,
+        metadata: options.metadata.then(Metadata::current),
+        ..Extensions::default()
+    };
+    let mut output = Vec::with_capacity(compressed_pgn_data.len() + 24);
+    write_extended_file(&mut output, algorithm, 0, &extensions, &compressed_pgn_data)?;
+    if let Some(passphrase) = &options.passphrase {
+        output = encrypt_file(&output, passphrase)?;
+    }
+    let summary = CompressSummary {
+        original_bytes: pgn_str.len() as u64,
+        stripped_bytes: None,
+        compressed_bytes: output.len() as u64,
+    };
+    if dry_run {
+        return Ok(summary);
+    }
+
+    // create any missing parent directories before writing the output
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomically(output_path, &output)?;
+    if let Some(mtime) = extensions.mtime {
+        filetime::set_file_mtime(output_path, mtime)?;
+    }
+    Ok(summary)
+}
+
+/// Options shared by the batch compression commands
+pub struct BatchOptions {
+    pub algorithm: Algorithm,
+    pub dry_run: bool,
+    pub force: bool,
+    pub keep_going: bool,
+    pub preserve_times: bool,
+    pub output_template: OutputTemplate,
+    pub passphrase: Option<String>,
+    pub encoding: Encoding,
+    pub normalize: bool,
+    /// Check each compressed file decompresses back to its input
+    pub verify: bool,
+    /// The largest input to read into memory, in bytes
+    pub max_input_size: u64,
+    /// Store the time of creation and the version of the tool in the header of each file
+    pub metadata: bool,
+}
+
+/// Compress each input file to its output path in parallel, stopping at the first failure unless `keep_going` is set,
+/// in which case every failure is listed at the end and an error is returned if any file failed.
+/// On a dry run, the total size the files would be compressed to is reported instead of writing them.
+/// Existing output files are only overwritten if `force` is set.
+/// Ctrl-C stops the batch once the files in progress are written, returning how many files were finished.
+/// Several files can only be compressed with an output template naming each after its input, and no two of them to
+/// the same output path.
+fn compress_files(jobs: &[(PathBuf, PathBuf)], options: &BatchOptions) -> Result<(), CgnCliError> {
+    if jobs.len() > 1 && !options.output_template.has_stem() {
+        return Err(CgnCliError::TemplateWithoutStem);
+    }
+    let mut inputs = HashMap::new();
+    for (input_path, output_path) in jobs {
+        if let Some(first) = inputs.insert(output_path, input_path) {
+            return Err(CgnCliError::DuplicateOutput {
+                output: output_path.clone(),
+                first: first.clone(),
+                second: input_path.clone(),
+            });
+        }
+    }
+    let progress = progress_bar(Some(jobs.len() as u64), "files");
+    let start = Instant::now();
+    catch_interrupts();
+
+    // compress the files across the global thread pool, skipping every file not yet started once one fails unless
+    // keeping going
+    let stop = AtomicBool::new(false);
+    let results = jobs
+        .par_iter()
+        .map(|(input_path, output_path)| {
+            if stop.load(Ordering::Relaxed) || is_interrupted() {
+                return None;
+            }
+            let result = compress_job(input_path, output_path, options, &progress);
+            if result.is_err() && !options.keep_going {
+                stop.store(true, Ordering::Relaxed);
+            }
+            progress.inc(1);
+            Some(result)
+        })
+        .collect::<Vec<_>>();
+    progress.finish_and_clear();
+
+    // total the results in the order of the files, so the first failure is reported whatever order they finished in
+    let finished = results.iter().flatten().count();
+    let mut failures = Vec::new();
+    let mut total = CompressSummary {
+        original_bytes: 0,
+        stripped_bytes: None,
+        compressed_bytes: 0,
+    };
+    for ((input_path, _), result) in jobs.iter().zip(results) {
+        match result {
+            Some(Ok(summary)) => {
+                log::debug!(
+                    "Compressed {} from {} to {} bytes",
+                    input_path.display(),
+                    summary.original_bytes,
+                    summary.compressed_bytes
+                );
+                total.original_bytes += summary.original_bytes;
+                total.compressed_bytes += summary.compressed_bytes;
+            }
+            Some(Err(e)) if options.keep_going => {
+                log::warn!("Failed to compress {}: {}", input_path.display(), e);
+                failures.push((input_path, e))
+            }
+            Some(Err(e)) => {
+                return Err(CgnCliError::FileFailed {
+                    path: input_path.clone(),
+                    source: Box::new(e),
+                });
+            }
+            None => {}
+        }
+    }
+    if is_interrupted() {
+        return Err(CgnCliError::Interrupted(finished));
+    }
+
+    let ratio = compression_ratio(
+        total.compressed_bytes as usize,
+        total.original_bytes as usize,
+    );
+    if options.dry_run {
+        output::status(format!(
+            "Dry run: {} files would be compressed from {} to {} bytes, a compression ratio of {}, {} failed",
+            jobs.len() - failures.len(),
+            total.original_bytes,
+            total.compressed_bytes,
+            ratio,
+            failures.len()
+        ));
+    } else {
+        output::status(format!(
+            "Compressed {} files from {} to {} bytes, a compression ratio of {}, in {:.3} seconds, {} failed",
+            jobs.len() - failures.len(),
+            total.original_bytes,
+            total.compressed_bytes,
+            ratio,
+            start.elapsed().as_secs_f64(),
+            failures.len()
+        ));
+    }
+
+    // list every failure at the end, so they are not lost among the other output
+    if !failures.is_empty() {
+        for (input_path, e) in &failures {
+            output::error(format!("{}: {}", input_path.display(), e));
+        }
+        return Err(CgnCliError::BatchFailed(failures.len()));
+    }
+    Ok(())
+}
+
+/// Compress a single file of a batch, only in memory on a dry run
+fn compress_job(
+    input_path: &Path,
+    output_path: &Path,
+    options: &BatchOptions,
+    progress: &ProgressBar,
+) -> Result<CompressSummary, CgnCliError> {
+    if options.dry_run {
+        progress.suspend(|| warn_if_exists(output_path));
+        compress_file(input_path, output_path, options, true)
+    } else {
+        check_overwrite(output_path, options.force)?;
+        compress_file(input_path, output_path, options, false)
+    }
+}
+
+/// Get the directory of an input file relative to the directory it was found under, keeping only named components
+/// so that mirroring it never leaves the output directory
+fn mirrored_dir(input_path: &Path, base_dir: &Path) -> PathBuf {
+    let relative_path = input_path.strip_prefix(base_dir).unwrap_or(input_path);
+    relative_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Pair each input file with its mirrored path under the output directory, relative to the directory it was found
+/// under and named with the output template
+fn mirrored_jobs(
+    input_paths: Vec<PathBuf>,
+    base_dir: &Path,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Vec<(PathBuf, PathBuf)> {
+    input_paths
+        .into_iter()
+        .map(|input_path| {
+            let output_path = output_dir.join(mirrored_dir(&input_path, base_dir)).join(
+                options
+                    .output_template
+                    .expand(&input_path, options.algorithm),
+            );
+            (input_path, output_path)
+        })
+        .collect()
+}
+
+/// Pair every PGN file under the input directory with its mirrored path under the output directory
+fn dir_jobs(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    Ok(mirrored_jobs(
+        find_pgn_files(input_dir)?,
+        input_dir,
+        output_dir,
+        options,
+    ))
+}
+
+/// Pair every file matching a glob pattern with its mirrored path under the output directory, relative to the
+/// directory the pattern starts from
+fn glob_jobs(
+    pattern: &str,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Result<Vec<(PathBuf, PathBuf)>, CgnCliError> {
+    Ok(mirrored_jobs(
+        glob_files(pattern)?,
+        &glob_base(pattern),
+        output_dir,
+        options,
+    ))
+}
+
+/// Compress every PGN file under the input directory into the mirrored path under the output directory
+pub fn compress_dir(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Result<(), CgnCliError> {
+    compress_files(&dir_jobs(input_dir, output_dir, options)?, options)
+}
+
+/// Check if an input path is a glob pattern rather than a plain file path or a URL, whose query can hold a `?`
+pub fn is_glob(path: &str) -> bool {
+    !is_url(path) && path.contains(['*', '?', '['])
+}
+
+/// Get the directory a glob pattern starts from, made up of its leading components without wildcards
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut components = Path::new(pattern).components().collect::<Vec<_>>();
+    // the last component names the files matched, even without wildcards
+    components.pop();
+    components
+        .into_iter()
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+/// Find every file matching a glob pattern, failing if there are none
+fn glob_files(pattern: &str) -> Result<Vec<PathBuf>, CgnCliError> {
+    let input_paths = glob::glob(pattern)
+        .map_err(|e| CgnCliError::InvalidGlob(e.to_string()))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| CgnCliError::Io(e.into()))?;
+    if input_paths.is_empty() {
+        return Err(CgnCliError::NoMatches(pattern.to_string()));
+    }
+    Ok(input_paths)
+}
+
+/// Compress every file matching a glob pattern. If more than one file matches, the output must be a directory,
+/// and each file is written to its mirrored path under it with a name expanded from the output template.
+pub fn compress_glob(
+    pattern: &str,
+    output_path: &Path,
+    options: &BatchOptions,
+) -> Result<(), CgnCliError> {
+    let input_paths = glob_files(pattern)?;
+
+    // a single match can be written to a plain file path
+    if !output_path.is_dir() {
+        if let [input_path] = &input_paths[..] {
+            let jobs = [(input_path.clone(), output_path.into())];
+            return compress_files(&jobs, options);
+        }
+        return Err(CgnCliError::OutputNotDirectory(output_path.into()));
+    }
+    compress_files(
+        &mirrored_jobs(input_paths, &glob_base(pattern), output_path, options),
+        options,
+    )
+}
+
+/// Compress every input into the output directory as a single batch, creating the directory if needed.
+/// Every input file is written to its mirrored path under the output directory with a name expanded from the output
+/// template, relative to the directory or the start of the glob pattern it was found under, or the working directory
+/// for a plain file.
+pub fn compress_into_dir(
+    inputs: &[String],
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Result<(), CgnCliError> {
+    let mut jobs = Vec::new();
+    for input in inputs {
+        if Path::new(input).is_dir() {
+            jobs.extend(dir_jobs(Path::new(input), output_dir, options)?);
+        } else if is_glob(input) {
+            jobs.extend(glob_jobs(input, output_dir, options)?);
+        } else {
+            jobs.extend(mirrored_jobs(
+                vec![PathBuf::from(input)],
+                Path::new(""),
+                output_dir,
+                options,
+            ));
+        }
+    }
+    if !options.dry_run {
+        fs::create_dir_all(output_dir)?;
+    }
+    compress_files(&jobs, options)
+}
@@ -1,78 +1,36 @@
+use super::algorithm::{Algorithm, Tuning};
+use super::baseline::{compare_to_baseline, comparison_table, load_baseline, BaselineResult};
+use super::codec::{codec_for, Codec};
+use super::diff::fnv1a;
+use super::error::CgnCliError;
+use super::filter::{header_value, GameFilter};
+use super::io_util::{write_atomically, Output};
+use super::latency::{first_game_latency, FirstGameLatency};
+use super::memory;
+use super::normalize::{strip_comments, StripComments};
+use super::output;
+use super::pgn_db::{count_db_games, pgn_db_into_iter};
+use super::progress::progress_bar;
 use anyhow::{anyhow, Result};
-use bit_vec::BitVec;
-use cgn::compression::{bincode, dynamic_huffman, huffman, opening_huffman};
 use cgn::pgn_data::PgnData;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{
     fmt::{self, Display, Formatter},
-    fs::File,
-    io::{BufRead, BufReader},
+    path::Path,
     str::FromStr,
+    thread,
     time::Instant,
 };
 
-/// An iterator over the games in a PGN database file.
-struct PgnDbIter<R: BufRead> {
-    reader: R,
-    buffer: String,
-}
-
-impl<R: BufRead> PgnDbIter<R> {
-    /// Creates a new PgnDBIter from a BufRead reader
-    fn new(reader: R) -> Self {
-        Self {
-            reader,
-            buffer: String::new(),
-        }
-    }
-}
-
-impl<R: BufRead> Iterator for PgnDbIter<R> {
-    // The type of the elements being iterated over.
-    type Item = String;
-
-    /// Get the next game in the database.
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut game = String::new();
-
-        // read until the next game
-        loop {
-            self.buffer.clear();
-            match self.reader.read_line(&mut self.buffer) {
-                Ok(0) => break, // EOF
-                Ok(_) => {
-                    // skip empty lines at the start of a game
-                    if game.is_empty() && self.buffer.trim().is_empty() {
-                        continue;
-                    // stop reading if we reach the start of the next game
-                    } else if self.buffer.starts_with("[Event") && !game.is_empty() {
-                        break;
-                    }
-                    // otherwise, add the line to the game
-                    game.push_str(&self.buffer);
-                }
-                Err(_) => return None,
-            }
-        }
-
-        // return the game if it's not empty
-        if game.trim().is_empty() {
-            None
-        } else {
-            Some(game)
-        }
-    }
-}
-
-/// Opens a PGN database file and returns an iterator over the games in the database.
-fn pgn_db_into_iter(path: &str) -> Result<PgnDbIter<BufReader<File>>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    Ok(PgnDbIter::new(reader))
-}
-
 ///  Metrics for a compression strategy.
 /// * Time to compress game (seconds)
 /// * Time to decompress game (seconds)
@@ -80,6 +38,9 @@ fn pgn_db_into_iter(path: &str) -> Result<PgnDbIter<BufReader<File>>> {
 /// * Size of compressed game (total bits including headers)
 /// * Bits per move (total bits / number of moves)
 /// * Bits per move excluding headers (total move bits / number of moves)
+/// * Number of plies in the game
+/// * Size of the game as read, and once annotations were stripped from its comments (bits)
+/// * ECO code of the opening, if the game has a valid one
 pub struct Metrics {
     time_to_compress: f64,
     time_to_decompress: f64,
@@ -87,6 +48,10 @@ pub struct Metrics {
     decompressed_size: usize,
     bits_per_move: f64,
     bits_per_move_excluding_headers: f64,
+    plies: usize,
+    text_size: usize,
+    stripped_text_size: usize,
+    eco: Option<String>,
 }
 
 impl Display for Metrics {
@@ -109,76 +74,61 @@ impl Display for Metrics {
     }
 }
 
-/// Collect a single metric for a compression strategy.
-fn collect_single_metric(
-    pgn_str: &str,
-    compress_fn: fn(&PgnData) -> Result<BitVec>,
-    decompress_fn: fn(&BitVec) -> Result<PgnData>,
-) -> Result<Metrics> {
-    let mut pgn_data = PgnData::from_str(pgn_str)?;
+/// Why a game has no metrics without the algorithm having failed on it
+#[derive(Debug)]
+enum Skipped {
+    /// The game has no moves to compress
+    NoMoves,
+    /// The benchmark was stopped by a failure in strict mode before reaching the game
+    Stopped,
+}
 
-    // if the game is empty, skip it
-    if pgn_data.moves.is_empty() {
-        return Err(anyhow!("collect_single_metric() - Game has no moves"));
+impl Display for Skipped {
+    /// Display the reason the game was skipped
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Skipped::NoMoves => write!(f, "Game has no moves"),
+            Skipped::Stopped => write!(f, "Benchmark stopped after a failure"),
+        }
     }
+}
 
-    // time to compress
-    let start = Instant::now();
-    let compressed_data = compress_fn(&pgn_data)?;
-    let end = Instant::now();
-    let time_to_compress = end.duration_since(start).as_secs_f64();
-
-    // compressed size
-    let compressed_size = compressed_data.len();
+impl std::error::Error for Skipped {}
 
-    // time to decompress
-    let start = Instant::now();
-    let decompressed_data = decompress_fn(&compressed_data)?;
-    let end = Instant::now();
-    let time_to_decompress = end.duration_since(start).as_secs_f64();
+/// A game that decompressed to different text than it was compressed from
+#[derive(Debug)]
+struct Mismatch {
+    /// The game as it should decompress, in PGN reduced export format
+    expected: String,
+    decompressed: String,
+}
 
-    // decompressed size
-    let decompressed_size = decompressed_data.to_string().len() * 8;
+impl Display for Mismatch {
+    /// Display the mismatch without the games, which are only printed for a strict round-trip
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "decompressed game does not match the original")
+    }
+}
 
-    // bits per move
-    let bits_per_move = compressed_size as f64 / pgn_data.moves.len() as f64;
+impl std::error::Error for Mismatch {}
 
-    // bits per move excluding headers
-    pgn_data.clear_headers();
-    let compressed_data_no_headers = compress_fn(&pgn_data)?;
-    let bits_per_move_excluding_headers =
-        (compressed_data_no_headers.len()) as f64 / pgn_data.moves.len() as f64;
-
-    Ok(Metrics {
-        time_to_compress,
-        time_to_decompress,
-        compressed_size,
-        decompressed_size,
-        bits_per_move,
-        bits_per_move_excluding_headers,
-    })
+/// Check if the metrics of a game are missing because the algorithm failed on it, rather than the game being skipped
+fn is_failure(metrics: &Result<Metrics>) -> bool {
+    matches!(metrics, Err(e) if !e.is::<Skipped>())
 }
 
 /// Collect a single metric for a compression strategy.
-fn collect_single_metric_custom(
-    pgn_str: &str,
-    compress_fn: fn(&PgnData, f64, f64) -> Result<BitVec>,
-    decompress_fn: fn(&BitVec, f64, f64) -> Result<PgnData>,
-    height: f64,
-    dev: f64,
-) -> Result<Metrics> {
+fn collect_single_metric(pgn_str: &str, codec: &dyn Codec) -> Result<Metrics> {
     let mut pgn_data = PgnData::from_str(pgn_str)?;
 
     // if the game is empty, skip it
     if pgn_data.moves.is_empty() {
-        return Err(anyhow!(
-            "collect_single_metric_custom() - Game has no moves"
-        ));
+        return Err(Skipped::NoMoves.into());
     }
 
     // time to compress
     let start = Instant::now();
-    let compressed_data = compress_fn(&pgn_data, height, dev)?;
+    let compressed_data = codec.compress_pgn_data(&pgn_data)?;
     let end = Instant::now();
     let time_to_compress = end.duration_since(start).as_secs_f64();
 
@@ -187,19 +137,30 @@ fn collect_single_metric_custom(
 
     // time to decompress
     let start = Instant::now();
-    let decompressed_data = decompress_fn(&compressed_data, height, dev)?;
+    let decompressed_data = codec.decompress_pgn_data(&compressed_data)?;
     let end = Instant::now();
     let time_to_decompress = end.duration_since(start).as_secs_f64();
 
+    // check the game round-trips, so a decoder bug is reported rather than skewing the averages
+    let decompressed_str = decompressed_data.to_string();
+    let expected = pgn_data.to_string();
+    if decompressed_str.as_bytes() != expected.as_bytes() {
+        return Err(Mismatch {
+            expected,
+            decompressed: decompressed_str,
+        }
+        .into());
+    }
+
     // decompressed size
-    let decompressed_size = decompressed_data.to_string().len() * 8;
+    let decompressed_size = decompressed_str.len() * 8;
 
     // bits per move
     let bits_per_move = compressed_size as f64 / pgn_data.moves.len() as f64;
 
     // bits per move excluding headers
     pgn_data.clear_headers();
-    let compressed_data_no_headers = compress_fn(&pgn_data, height, dev)?;
+    let compressed_data_no_headers = codec.compress_pgn_data(&pgn_data)?;
     let bits_per_move_excluding_headers =
         (compressed_data_no_headers.len()) as f64 / pgn_data.moves.len() as f64;
 
@@ -210,14 +171,49 @@ fn collect_single_metric_custom(
         decompressed_size,
         bits_per_move,
         bits_per_move_excluding_headers,
+        plies: pgn_data.moves.len(),
+        text_size: pgn_str.len() * 8,
+        stripped_text_size: pgn_str.len() * 8,
+        eco: eco_code(pgn_str),
     })
 }
 
 #[derive(Clone)]
 /// A number of games to take from a PGN database. If `all`, take all games.
+/// A percentage such as `10%` takes that fraction of the games, rounded up, and must be resolved into a number first.
 pub enum ToTake {
     All,
     N(usize),
+    Percent(f64),
+}
+
+impl ToTake {
+    /// Get the number of games to take, or None if all games should be taken.
+    /// An unresolved percentage is treated as taking all games.
+    pub fn count(&self) -> Option<usize> {
+        match self {
+            ToTake::All | ToTake::Percent(_) => None,
+            ToTake::N(n) => Some(*n),
+        }
+    }
+
+    /// Resolve a percentage into a number of games by counting the games in a database.
+    /// Counting reads the whole database, so it adds a full streaming pass before any game is benchmarked.
+    pub fn resolve(self, db_path: &str) -> io::Result<ToTake> {
+        let ToTake::Percent(percent) = self else {
+            return Ok(self);
+        };
+        let total = count_db_games(db_path)?;
+        let n = (total as f64 * percent / 100.0).ceil() as usize;
+        log::info!(
+            "Taking {}% of the {} game(s) in {}, which is {} game(s)",
+            percent,
+            total,
+            db_path,
+            n
+        );
+        Ok(ToTake::N(n))
+    }
 }
 
 impl FromStr for ToTake {
@@ -227,6 +223,13 @@ impl FromStr for ToTake {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s == "all" {
             Ok(ToTake::All)
+        } else if let Some(percent) = s.strip_suffix('%') {
+            match percent.parse::<f64>()? {
+                percent if percent > 0.0 && percent <= 100.0 => Ok(ToTake::Percent(percent)),
+                _ => Err(anyhow!(
+                    "percentage must be greater than 0% and at most 100%"
+                )),
+            }
         } else {
             Ok(ToTake::N(s.parse()?))
         }
@@ -239,65 +242,148 @@ impl Display for ToTake {
         match self {
             ToTake::All => write!(f, "all"),
             ToTake::N(n) => write!(f, "{}", n),
+            ToTake::Percent(percent) => write!(f, "{}%", percent),
         }
     }
 }
 
-/// Collect the metrics for a compression strategy. Only guaranteed to work with Lichess PGN databases
-pub fn collect_metrics(
-    compress_fn: fn(&PgnData) -> Result<BitVec>,
-    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+/// How games are chosen from a PGN database when only some of them are benchmarked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SampleMode {
+    /// Take the first games in the database, which is fastest as the rest of the file is never read
+    Sequential,
+    /// Take games uniformly at random from the whole database
+    Random,
+}
+
+/// How games are chosen from a PGN database, with the seed used for random sampling
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampling {
+    #[default]
+    Sequential,
+    Random(u64),
+}
+
+impl Sampling {
+    /// Resolve a sample mode and optional seed, choosing a random seed if none is given
+    pub fn new(mode: SampleMode, seed: Option<u64>) -> Self {
+        match mode {
+            SampleMode::Sequential => Sampling::Sequential,
+            SampleMode::Random => Sampling::Random(seed.unwrap_or_else(rand::random)),
+        }
+    }
+}
+
+/// Pick `n` games uniformly at random in a single pass with reservoir sampling, returning them tagged with
+/// their position and in database order
+fn reservoir_sample(
+    games: impl Iterator<Item = String>,
+    n: usize,
+    seed: u64,
+) -> Vec<(usize, String)> {
+    let mut rng = ChaCha12Rng::seed_from_u64(seed);
+    let mut reservoir = Vec::with_capacity(n);
+    for (i, pgn_str) in games.enumerate() {
+        if i < n {
+            reservoir.push((i, pgn_str));
+        } else {
+            // the i-th game replaces a random game in the reservoir with probability n / (i + 1)
+            let j = rng.gen_range(0..=i);
+            if j < n {
+                reservoir[j] = (i, pgn_str);
+            }
+        }
+    }
+    reservoir.sort_by_key(|(i, _)| *i);
+    reservoir
+}
+
+/// Apply a function to the games in a PGN database that match the filter in parallel, returning the results in database order.
+/// The progress bar is advanced once for each game. Fails if the database cannot be opened.
+pub fn map_games<T: Send>(
     db_path: &str,
     n: &ToTake,
-) -> Vec<Result<Metrics>> {
-    if let ToTake::N(n) = n {
-        pgn_db_into_iter(db_path)
-            .expect("Failed to open PGN database file")
-            .par_bridge()
-            .take_any(*n)
-            .map(|pgn_str| collect_single_metric(&pgn_str, compress_fn, decompress_fn))
-            .collect::<Vec<_>>()
-    } else {
-        pgn_db_into_iter(db_path)
-            .expect("Failed to open PGN database file")
-            .par_bridge()
-            .map(|pgn_str| collect_single_metric(&pgn_str, compress_fn, decompress_fn))
-            .collect::<Vec<_>>()
-    }
+    filter: &GameFilter,
+    sampling: Sampling,
+    progress: &ProgressBar,
+    f: impl Fn(&str) -> T + Sync + Send,
+) -> Result<Vec<T>, CgnCliError> {
+    let games = pgn_db_into_iter(db_path)?.filter(|pgn_str| filter.matches(pgn_str));
+
+    // tag each game with its index so the results can be put back in order
+    let games: Box<dyn Iterator<Item = (usize, String)> + Send> = match (sampling, n.count()) {
+        (Sampling::Random(seed), Some(n)) => Box::new(reservoir_sample(games, n, seed).into_iter()),
+        (_, n) => Box::new(games.take(n.unwrap_or(usize::MAX)).enumerate()),
+    };
+    let mut results = games
+        .par_bridge()
+        .map(|(i, pgn_str)| {
+            let result = f(&pgn_str);
+            progress.inc(1);
+            (i, result)
+        })
+        .collect::<Vec<_>>();
+    results.sort_by_key(|(i, _)| *i);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
 }
 
 /// Collect the metrics for a compression strategy. Only guaranteed to work with Lichess PGN databases.
+/// If a stop flag is given, the first failure sets it and every game not yet started is skipped,
+/// so a flag shared between strategies stops all of them.
+/// Annotations are stripped from the comments of each game before it is measured, keeping the size of the game as read.
+#[allow(clippy::too_many_arguments)]
+pub fn collect_metrics(
+    codec: &dyn Codec,
+    db_path: &str,
+    n: &ToTake,
+    filter: &GameFilter,
+    sampling: Sampling,
+    progress: &ProgressBar,
+    stop: Option<&AtomicBool>,
+    strip: StripComments,
+) -> Result<Vec<Result<Metrics>>, CgnCliError> {
+    map_games(db_path, n, filter, sampling, progress, |pgn_str| {
+        if stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return Err(Skipped::Stopped.into());
+        }
+        let metrics = match strip {
+            StripComments::None => collect_single_metric(pgn_str, codec),
+            strip => collect_single_metric(&strip_comments(pgn_str, strip), codec).map(|metrics| {
+                Metrics {
+                    text_size: pgn_str.len() * 8,
+                    ..metrics
+                }
+            }),
+        };
+        if let Some(stop) = stop.filter(|_| is_failure(&metrics)) {
+            stop.store(true, Ordering::Relaxed);
+        }
+        metrics
+    })
+}
+
+/// Collect the metrics for dynamic huffman with tuned parameters. Only guaranteed to work with Lichess PGN databases.
 pub fn collect_metrics_custom(
-    compress_fn: fn(&PgnData, f64, f64) -> Result<BitVec>,
-    decompress_fn: fn(&BitVec, f64, f64) -> Result<PgnData>,
+    tuning: Tuning,
     db_path: &str,
     n: &ToTake,
-    height: f64,
-    dev: f64,
-) -> Vec<Result<Metrics>> {
-    if let ToTake::N(n) = n {
-        pgn_db_into_iter(db_path)
-            .expect("Failed to open PGN database file")
-            .par_bridge()
-            .take_any(*n)
-            .map(|pgn_str| {
-                collect_single_metric_custom(&pgn_str, compress_fn, decompress_fn, height, dev)
-            })
-            .collect::<Vec<_>>()
-    } else {
-        pgn_db_into_iter(db_path)
-            .expect("Failed to open PGN database file")
-            .par_bridge()
-            .map(|pgn_str| {
-                collect_single_metric_custom(&pgn_str, compress_fn, decompress_fn, height, dev)
-            })
-            .collect::<Vec<_>>()
-    }
+) -> Result<Vec<Result<Metrics>>, CgnCliError> {
+    let filter = GameFilter::default();
+    let progress = ProgressBar::hidden();
+    map_games(
+        db_path,
+        n,
+        &filter,
+        Sampling::Sequential,
+        &progress,
+        |pgn_str| collect_single_metric(pgn_str, &tuning),
+    )
 }
 
 /// A summary of the metrics for a compression strategy
 pub struct Summary {
     pub total_games: usize,
+    pub failed_games: usize,
     pub avg_time_to_compress: f64,
     pub avg_time_to_decompress: f64,
     pub avg_compressed_size: usize,
@@ -311,6 +397,7 @@ impl Display for Summary {
     /// Display the summary
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "Total games: {}", self.total_games)?;
+        writeln!(f, "Failed games: {}", self.failed_games)?;
         writeln!(
             f,
             "Average time to compress: {} seconds",
@@ -341,11 +428,33 @@ impl Display for Summary {
     }
 }
 
+/// Get the ratio of a compressed size to the original size, where lower is better
+pub fn compression_ratio(compressed_size: usize, original_size: usize) -> f64 {
+    compressed_size as f64 / original_size as f64
+}
+
 /// Summarize the metrics for a compression strategy.
+/// Games the algorithm failed on are counted rather than silently left out of the averages.
 pub fn metrics_to_summary(metrics: &[Result<Metrics>]) -> Summary {
+    let failed_games = metrics.iter().filter(|x| is_failure(x)).count();
+
+    // filter out and print errors
+    let metrics = metrics
+        .iter()
+        .filter_map(|x| match x {
+            Ok(x) => Some(x),
+            Err(e) => {
+                output::warning(e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // if every game failed, there is nothing to average
     if metrics.is_empty() {
         return Summary {
             total_games: 0,
+            failed_games,
             avg_time_to_compress: 0.0,
             avg_time_to_decompress: 0.0,
             avg_compressed_size: 0,
@@ -356,18 +465,6 @@ pub fn metrics_to_summary(metrics: &[Result<Metrics>]) -> Summary {
         };
     }
 
-    // filter out and print errors
-    let metrics = metrics
-        .iter()
-        .filter_map(|x| match x {
-            Ok(x) => Some(x),
-            Err(e) => {
-                println!("{}", e);
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-
     // compute averages
     let avg_time_to_compress =
         metrics.iter().map(|x| x.time_to_compress).sum::<f64>() / metrics.len() as f64;
@@ -384,10 +481,11 @@ pub fn metrics_to_summary(metrics: &[Result<Metrics>]) -> Summary {
         .map(|x| x.bits_per_move_excluding_headers)
         .sum::<f64>()
         / metrics.len() as f64;
-    let compression_ratio = avg_compressed_size as f64 / avg_decompressed_size as f64;
+    let compression_ratio = compression_ratio(avg_compressed_size, avg_decompressed_size);
 
     Summary {
         total_games: metrics.len(),
+        failed_games,
         avg_time_to_compress,
         avg_time_to_decompress,
         avg_compressed_size,
@@ -398,102 +496,1299 @@ pub fn metrics_to_summary(metrics: &[Result<Metrics>]) -> Summary {
     }
 }
 
-fn metrics_to_file(label: &str, metrics: &[Result<Metrics>], output_path: &String) {
+/// The compression ratio of an algorithm against the games as read and against the games once annotations were
+/// stripped from their comments, showing how much of the games' size was annotations
+#[derive(Serialize)]
+pub struct StrippedRatios {
+    pub strip_comments: String,
+    pub original_ratio: f64,
+    pub stripped_ratio: f64,
+}
+
+/// Get the compression ratio of the games against their size before and after their comments were stripped
+pub fn stripped_ratios(metrics: &[Result<Metrics>], strip: StripComments) -> StrippedRatios {
+    let metrics = metrics.iter().flatten();
+    let compressed_size = metrics.clone().map(|m| m.compressed_size).sum();
+    StrippedRatios {
+        strip_comments: strip.to_string(),
+        original_ratio: compression_ratio(
+            compressed_size,
+            metrics.clone().map(|m| m.text_size).sum(),
+        ),
+        stripped_ratio: compression_ratio(
+            compressed_size,
+            metrics.map(|m| m.stripped_text_size).sum(),
+        ),
+    }
+}
+
+/// The most plies in each game length bucket, with a last bucket for every longer game
+const LENGTH_BUCKET_ENDS: [usize; 3] = [20, 40, 80];
+
+/// The compression ratio of the games in a range of lengths, or None if no game has a length in the range
+#[derive(Serialize)]
+pub struct LengthBucket {
+    pub plies: String,
+    pub games: usize,
+    pub compression_ratio: Option<f64>,
+}
+
+/// Get the range of plies covered by each length bucket, such as `21-40` or `81+`
+fn length_bucket_labels() -> Vec<String> {
+    let mut start = 0;
+    let mut labels = Vec::new();
+    for end in LENGTH_BUCKET_ENDS {
+        labels.push(format!("{}-{}", start, end));
+        start = end + 1;
+    }
+    labels.push(format!("{}+", start));
+    labels
+}
+
+/// Group the games by their number of plies and get the compression ratio of each group,
+/// from its total compressed and original sizes. Games that failed or were skipped are left out.
+pub fn ratio_by_length(metrics: &[Result<Metrics>]) -> Vec<LengthBucket> {
+    let mut totals = [(0, 0, 0); LENGTH_BUCKET_ENDS.len() + 1];
+    for m in metrics.iter().flatten() {
+        let bucket = LENGTH_BUCKET_ENDS
+            .iter()
+            .position(|&end| m.plies <= end)
+            .unwrap_or(LENGTH_BUCKET_ENDS.len());
+        let (games, compressed_size, decompressed_size) = &mut totals[bucket];
+        *games += 1;
+        *compressed_size += m.compressed_size;
+        *decompressed_size += m.decompressed_size;
+    }
+    length_bucket_labels()
+        .into_iter()
+        .zip(totals)
+        .map(
+            |(plies, (games, compressed_size, decompressed_size))| LengthBucket {
+                plies,
+                games,
+                compression_ratio: (games > 0)
+                    .then(|| compression_ratio(compressed_size, decompressed_size)),
+            },
+        )
+        .collect()
+}
+
+/// Format the compression ratio of each algorithm by game length as a table, with a row per algorithm
+/// and a last row with the number of games of each length
+fn ratio_by_length_table(results: &[(Algorithm, Vec<Result<Metrics>>)], markdown: bool) -> String {
+    let rows = results
+        .iter()
+        .map(|(algorithm, metrics)| (label(*algorithm), ratio_by_length(metrics)))
+        .collect::<Vec<_>>();
+    let mut table = String::new();
+    let mut push_row = |first: &str, cells: Vec<String>| {
+        if markdown {
+            table.push_str(&format!("| {} | {} |\n", first, cells.join(" | ")));
+        } else {
+            let cells = cells.iter().map(|cell| format!("{:>10}", cell));
+            table.push_str(&format!("{:<20}{}\n", first, cells.collect::<String>()));
+        }
+    };
+
+    push_row("Plies", length_bucket_labels());
+    if markdown {
+        push_row(
+            "---",
+            vec![String::from("---:"); LENGTH_BUCKET_ENDS.len() + 1],
+        );
+    }
+    for (label, buckets) in &rows {
+        let ratios = buckets.iter().map(|bucket| match bucket.compression_ratio {
+            Some(ratio) => format!("{:.4}", ratio),
+            None => String::from("-"),
+        });
+        push_row(label, ratios.collect());
+    }
+    if let Some((_, buckets)) = rows.first() {
+        push_row(
+            "Games",
+            buckets.iter().map(|b| b.games.to_string()).collect(),
+        );
+    }
+    table
+}
+
+/// The number of openings listed at each end of the ranking by compression ratio
+const ECO_EXTREMES: usize = 5;
+
+/// How games are grouped by the ECO code of their opening
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EcoGrouping {
+    /// Group games by their full ECO code, such as B90
+    Code,
+    /// Group games by the family of their ECO code, from A to E
+    Family,
+}
+
+impl EcoGrouping {
+    /// Get the group of an ECO code
+    fn group(self, eco: &str) -> &str {
+        match self {
+            EcoGrouping::Code => eco,
+            EcoGrouping::Family => &eco[..1],
+        }
+    }
+}
+
+impl Display for EcoGrouping {
+    /// Display the grouping as it reads in a heading
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EcoGrouping::Code => write!(f, "ECO code"),
+            EcoGrouping::Family => write!(f, "ECO family"),
+        }
+    }
+}
+
+/// Get the ECO code of a game from its ECO tag, if it is a letter from A to E followed by two digits
+fn eco_code(pgn_str: &str) -> Option<String> {
+    let eco = header_value(pgn_str, "ECO")?.trim().to_ascii_uppercase();
+    let bytes = eco.as_bytes();
+    let valid = bytes.len() == 3
+        && (b'A'..=b'E').contains(&bytes[0])
+        && bytes[1..].iter().all(u8::is_ascii_digit);
+    valid.then_some(eco)
+}
+
+/// The compression ratio of the games opening with an ECO code or family
+#[derive(Serialize)]
+pub struct EcoGroup {
+    pub eco: String,
+    pub games: usize,
+    pub compression_ratio: f64,
+}
+
+/// Group the games by the ECO code of their opening, or its family, and get the compression ratio of each group from
+/// its total compressed and original sizes. The groups are sorted from the best compressed to the worst.
+/// Games without a valid ECO tag, and games that failed or were skipped, are left out.
+pub fn ratio_by_eco(metrics: &[Result<Metrics>], grouping: EcoGrouping) -> Vec<EcoGroup> {
+    let mut totals = HashMap::<&str, (usize, usize, usize)>::new();
+    for m in metrics.iter().flatten() {
+        let Some(eco) = &m.eco else {
+            continue;
+        };
+        let (games, compressed_size, decompressed_size) =
+            totals.entry(grouping.group(eco)).or_default();
+        *games += 1;
+        *compressed_size += m.compressed_size;
+        *decompressed_size += m.decompressed_size;
+    }
+    let mut groups = totals
+        .into_iter()
+        .map(
+            |(eco, (games, compressed_size, decompressed_size))| EcoGroup {
+                eco: eco.to_string(),
+                games,
+                compression_ratio: compression_ratio(compressed_size, decompressed_size),
+            },
+        )
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| {
+        a.compression_ratio
+            .total_cmp(&b.compression_ratio)
+            .then_with(|| a.eco.cmp(&b.eco))
+    });
+    groups
+}
+
+/// Format the compression ratio of an algorithm by opening as a table, listing only the best and worst compressed
+/// openings if there are too many to list them all
+fn ratio_by_eco_table(groups: &[EcoGroup], grouping: EcoGrouping, markdown: bool) -> String {
+    let mut table = String::new();
+    let mut push_row = |cells: [String; 3]| {
+        if markdown {
+            table.push_str(&format!("| {} |\n", cells.join(" | ")));
+        } else {
+            table.push_str(&format!(
+                "{:<12}{:>10}{:>10}\n",
+                cells[0], cells[1], cells[2]
+            ));
+        }
+    };
+    let heading = match grouping {
+        EcoGrouping::Code => "ECO",
+        EcoGrouping::Family => "Family",
+    };
+    push_row([heading, "Games", "Ratio"].map(String::from));
+    if markdown {
+        push_row(["---", "---:", "---:"].map(String::from));
+    }
+    let row = |group: &EcoGroup| {
+        [
+            group.eco.clone(),
+            group.games.to_string(),
+            format!("{:.4}", group.compression_ratio),
+        ]
+    };
+    if groups.len() <= ECO_EXTREMES * 2 {
+        groups.iter().for_each(|group| push_row(row(group)));
+    } else {
+        groups[..ECO_EXTREMES]
+            .iter()
+            .for_each(|group| push_row(row(group)));
+        push_row(["...", "", ""].map(String::from));
+        groups[groups.len() - ECO_EXTREMES..]
+            .iter()
+            .for_each(|group| push_row(row(group)));
+    }
+    table
+}
+
+/// The most buckets a histogram of compressed sizes is split into
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// The width a histogram is drawn to when the width of the terminal is unknown
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// The number of games with a compressed size in a range of bytes, inclusive at both ends
+#[derive(Serialize)]
+pub struct SizeBucket {
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    pub games: usize,
+}
+
+/// Group the games into equally wide buckets by their compressed size in bytes, from the smallest size to the largest.
+/// Games that failed or were skipped are left out, and no buckets are returned if no game has metrics.
+pub fn size_histogram(metrics: &[Result<Metrics>]) -> Vec<SizeBucket> {
+    let sizes = metrics
+        .iter()
+        .flatten()
+        .map(|m| m.compressed_size.div_ceil(8))
+        .collect::<Vec<_>>();
+    let (Some(&min), Some(&max)) = (sizes.iter().min(), sizes.iter().max()) else {
+        return Vec::new();
+    };
+    let bucket_width = (max - min + 1).div_ceil(HISTOGRAM_BUCKETS);
+    let mut buckets = (0..=(max - min) / bucket_width)
+        .map(|i| SizeBucket {
+            min_bytes: min + i * bucket_width,
+            max_bytes: min + (i + 1) * bucket_width - 1,
+            games: 0,
+        })
+        .collect::<Vec<_>>();
+    for size in sizes {
+        buckets[(size - min) / bucket_width].games += 1;
+    }
+    buckets
+}
+
+/// Get the width of the terminal in columns, or a default width if it is unknown
+fn terminal_width() -> usize {
+    ratatui::crossterm::terminal::size()
+        .map(|(columns, _)| columns as usize)
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// Draw a histogram as a bar chart with a row per bucket, with the longest bar scaled to fit the width.
+/// A bucket with any games always has a bar, however short.
+fn histogram_chart(buckets: &[SizeBucket], width: usize) -> String {
+    let labels = buckets
+        .iter()
+        .map(|bucket| match bucket.max_bytes - bucket.min_bytes {
+            0 => format!("{} bytes", bucket.min_bytes),
+            _ => format!("{}-{} bytes", bucket.min_bytes, bucket.max_bytes),
+        })
+        .collect::<Vec<_>>();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+    let most_games = buckets.iter().map(|bucket| bucket.games).max().unwrap_or(0);
+    let count_width = most_games.to_string().len();
+    let bar_width = width.saturating_sub(label_width + count_width + 4).max(1);
+
+    let mut chart = String::new();
+    for (label, bucket) in labels.iter().zip(buckets) {
+        let bar_len = match bucket.games {
+            0 => 0,
+            games => (games * bar_width / most_games).max(1),
+        };
+        chart.push_str(&format!(
+            "{:>label_width$} | {:<bar_width$} {:>count_width$}\n",
+            label,
+            "#".repeat(bar_len),
+            bucket.games
+        ));
+    }
+    chart
+}
+
+/// Get the metrics collected for an algorithm
+fn metrics_for(
+    results: &[(Algorithm, Vec<Result<Metrics>>)],
+    algorithm: Algorithm,
+) -> &[Result<Metrics>] {
+    results
+        .iter()
+        .find(|(a, _)| *a == algorithm)
+        .map_or(&[], |(_, metrics)| metrics)
+}
+
+/// Append the metrics of every game to a file under a label
+fn metrics_to_file(
+    label: &str,
+    metrics: &[Result<Metrics>],
+    output_path: &str,
+) -> Result<(), CgnCliError> {
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(output_path)
-        .unwrap();
+        .open(output_path)?;
 
     // write the label
-    file.write_all(format!("[{}]\n", label).as_bytes()).unwrap();
+    file.write_all(format!("[{}]\n", label).as_bytes())?;
 
     // write the metrics
-    metrics.iter().flatten().for_each(|m| {
-        file.write_all(format!("{}\n", m).as_bytes()).unwrap();
-    });
+    for m in metrics.iter().flatten() {
+        file.write_all(format!("{}\n", m).as_bytes())?;
+    }
+    Ok(())
 }
 
-/// Collects metrics for the specified compression and decompression functions.
-pub fn bench(n: ToTake, db_path: &str, output_path: &Option<String>) {
-    bench_bincode(&n, db_path, output_path);
-    bench_huffman(&n, db_path, output_path);
-    bench_dynamic_huffman(&n, db_path, output_path);
-    bench_opening_huffman(&n, db_path, output_path);
+/// Quote a CSV field if it contains a delimiter, quote, or newline
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
 }
 
-/// Collects and prints metrics for the bincode_zlib compression strategy.
-fn bench_bincode(n: &ToTake, db_path: &str, output_path: &Option<String>) {
-    println!("[BENCHMARK] Collecting metrics for bincode...");
-    let metrics = collect_metrics(
-        bincode::compress_pgn_data,
-        bincode::decompress_pgn_data,
-        db_path,
-        n,
-    );
+/// Get the median of a list of values, or 0 if the list is empty
+pub fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
 
-    // print the summary to the console
-    println!("{}", metrics_to_summary(&metrics));
+/// Write one CSV row per compression strategy to a file, replacing any existing file
+fn results_to_csv(
+    results: &[(Algorithm, Vec<Result<Metrics>>)],
+    output_path: &str,
+) -> Result<(), CgnCliError> {
+    let mut file = Output::create(Path::new(output_path))?;
 
-    // write the metrics to a file if an output path is specified
-    if let Some(output_path) = output_path {
-        metrics_to_file("BINCODE", &metrics, output_path);
+    // write the header row
+    file.write_all(b"algorithm,compression_ratio,mean_compress_ms,median_compress_ms,mean_decompress_ms,median_decompress_ms,sample_count,failed_count\n")?;
+
+    // write a row for each algorithm
+    for (algorithm, metrics) in results {
+        let summary = metrics_to_summary(metrics);
+        let compress_ms = metrics
+            .iter()
+            .flatten()
+            .map(|m| m.time_to_compress * 1000.0);
+        let decompress_ms = metrics
+            .iter()
+            .flatten()
+            .map(|m| m.time_to_decompress * 1000.0);
+        let row = [
+            csv_field(&algorithm.to_string()),
+            summary.compression_ratio.to_string(),
+            (summary.avg_time_to_compress * 1000.0).to_string(),
+            median(compress_ms.collect()).to_string(),
+            (summary.avg_time_to_decompress * 1000.0).to_string(),
+            median(decompress_ms.collect()).to_string(),
+            summary.total_games.to_string(),
+            summary.failed_games.to_string(),
+        ];
+        file.write_all(format!("{}\n", row.join(",")).as_bytes())?;
     }
+    file.finish()?;
+    Ok(())
 }
 
-/// Collects and prints metrics for the huffman compression strategy.
-fn bench_huffman(n: &ToTake, db_path: &str, output_path: &Option<String>) {
-    println!("[BENCHMARK] Collecting metrics for huffman...");
-    let metrics = collect_metrics(
-        huffman::compress_pgn_data,
-        huffman::decompress_pgn_data,
-        db_path,
-        n,
+/// Format the results as a GitHub-flavored Markdown table, with a row per compression strategy
+fn results_to_markdown(db_path: &str, results: &[(Algorithm, Vec<Result<Metrics>>)]) -> String {
+    let number_of_games = results.first().map_or(0, |(_, metrics)| metrics.len());
+    let mut markdown = format!(
+        "Benchmark of `{}` over {} game(s)\n\n",
+        db_path, number_of_games
     );
 
-    // print the summary to the console
-    println!("{}", metrics_to_summary(&metrics));
+    // numeric columns are right-aligned
+    markdown.push_str(
+        "| Algorithm | Compression ratio | Compress time (ms) | Decompress time (ms) | Failed games |\n",
+    );
+    markdown.push_str("| --- | ---: | ---: | ---: | ---: |\n");
+    for (algorithm, metrics) in results {
+        let summary = metrics_to_summary(metrics);
+        markdown.push_str(&format!(
+            "| {} | {:.2} | {:.3} | {:.3} | {} |\n",
+            algorithm,
+            summary.compression_ratio,
+            summary.avg_time_to_compress * 1000.0,
+            summary.avg_time_to_decompress * 1000.0,
+            summary.failed_games
+        ));
+    }
+    markdown
+}
 
-    // write the metrics to a file if an output path is specified
-    if let Some(output_path) = output_path {
-        metrics_to_file("HUFFMAN", &metrics, output_path);
+/// Format the compression ratio of each algorithm against the games before and after stripping their comments as a
+/// Markdown table
+fn stripped_to_markdown(
+    results: &[(Algorithm, Vec<Result<Metrics>>)],
+    strip: StripComments,
+) -> String {
+    let mut markdown = String::from("| Algorithm | Original games | Stripped games |\n");
+    markdown.push_str("| --- | ---: | ---: |\n");
+    for (algorithm, metrics) in results {
+        let ratios = stripped_ratios(metrics, strip);
+        markdown.push_str(&format!(
+            "| {} | {:.4} | {:.4} |\n",
+            algorithm, ratios.original_ratio, ratios.stripped_ratio
+        ));
     }
+    markdown
 }
 
-/// Collects and prints metrics for the dynamic huffman compression strategy.
-fn bench_dynamic_huffman(n: &ToTake, db_path: &str, output_path: &Option<String>) {
-    println!("[BENCHMARK] Collecting metrics for dynamic huffman...");
-    let metrics = collect_metrics(
-        dynamic_huffman::compress_pgn_data,
-        dynamic_huffman::decompress_pgn_data,
-        db_path,
-        n,
+/// Format the time to the first game of each algorithm as a Markdown table
+fn latency_to_markdown(
+    results: &[(Algorithm, Vec<Result<Metrics>>)],
+    latencies: &[Option<FirstGameLatency>],
+) -> String {
+    let mut markdown = String::from("| Algorithm | Monolithic (ms) | Framed (ms) | Speedup |\n");
+    markdown.push_str("| --- | ---: | ---: | ---: |\n");
+    for ((algorithm, _), latency) in results.iter().zip(latencies) {
+        if let Some(latency) = latency {
+            markdown.push_str(&format!(
+                "| {} | {:.3} | {:.3} | {:.1}x |\n",
+                algorithm,
+                latency.monolithic * 1000.0,
+                latency.framed * 1000.0,
+                latency.monolithic / latency.framed
+            ));
+        }
+    }
+    markdown
+}
+
+/// How many times to run each algorithm over the games, with warmup runs discarded before the measured runs
+#[derive(Clone, Copy, Debug)]
+pub struct Repetitions {
+    pub warmup: usize,
+    pub iterations: usize,
+}
+
+impl Default for Repetitions {
+    /// A single measured run with no warmup
+    fn default() -> Self {
+        Self {
+            warmup: 0,
+            iterations: 1,
+        }
+    }
+}
+
+impl Repetitions {
+    /// Get the total number of runs, including warmup runs
+    fn runs(&self) -> usize {
+        self.warmup + self.iterations
+    }
+}
+
+/// The mean and standard deviation over several measured iterations of the average time per game, in seconds
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TimingSpread {
+    pub iterations: usize,
+    pub compress_mean: f64,
+    pub compress_std_dev: f64,
+    pub decompress_mean: f64,
+    pub decompress_std_dev: f64,
+}
+
+impl TimingSpread {
+    /// Get the spread of the average timings over the metrics of each iteration
+    fn new(iterations: &[Vec<Result<Metrics>>]) -> Self {
+        let averages = |time: fn(&Metrics) -> f64| {
+            iterations
+                .iter()
+                .map(|metrics| {
+                    let times = metrics.iter().flatten().map(time).collect::<Vec<_>>();
+                    times.iter().sum::<f64>() / times.len().max(1) as f64
+                })
+                .collect::<Vec<_>>()
+        };
+        let (compress_mean, compress_std_dev) = mean_and_std_dev(&averages(|m| m.time_to_compress));
+        let (decompress_mean, decompress_std_dev) =
+            mean_and_std_dev(&averages(|m| m.time_to_decompress));
+        Self {
+            iterations: iterations.len(),
+            compress_mean,
+            compress_std_dev,
+            decompress_mean,
+            decompress_std_dev,
+        }
+    }
+}
+
+impl Display for TimingSpread {
+    /// Display the mean and standard deviation of the timings
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Time to compress over {} iterations: {} ± {} seconds",
+            self.iterations, self.compress_mean, self.compress_std_dev
+        )?;
+        writeln!(
+            f,
+            "Time to decompress over {} iterations: {} ± {} seconds",
+            self.iterations, self.decompress_mean, self.decompress_std_dev
+        )
+    }
+}
+
+/// Get the mean and sample standard deviation of a list of values, with a deviation of 0 for fewer than two values
+fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance =
+        values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    (mean, variance.sqrt())
+}
+
+/// Combine the metrics of several measured iterations over the same games, averaging the timings of each game.
+/// Sizes are the same in every iteration, so they are kept from the first. The spread of the timings is only
+/// returned when there is more than one iteration.
+fn combine_iterations(
+    iterations: Vec<Vec<Result<Metrics>>>,
+) -> (Vec<Result<Metrics>>, Option<TimingSpread>) {
+    let spread = (iterations.len() > 1).then(|| TimingSpread::new(&iterations));
+    let count = iterations.len() as f64;
+    let mut iterations = iterations.into_iter();
+    let mut combined = iterations.next().unwrap_or_default();
+    for iteration in iterations {
+        for (game, metrics) in combined.iter_mut().zip(iteration) {
+            if let (Ok(game), Ok(metrics)) = (game, metrics) {
+                game.time_to_compress += metrics.time_to_compress;
+                game.time_to_decompress += metrics.time_to_decompress;
+            }
+        }
+    }
+    if spread.is_some() {
+        for game in combined.iter_mut().flatten() {
+            game.time_to_compress /= count;
+            game.time_to_decompress /= count;
+        }
+    }
+    (combined, spread)
+}
+
+/// Get the default number of threads to run parallel work on, which is the number of logical CPUs
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Get the number of threads to run parallel work on from the number asked for, where 0 means every logical CPU.
+/// Larger counts are capped at the number of logical CPUs, as the extra threads would only compete for them.
+pub fn resolve_threads(threads: usize) -> usize {
+    match threads {
+        0 => default_threads(),
+        threads => threads.min(default_threads()),
+    }
+}
+
+/// The format to print benchmark results in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BenchFormat {
+    Human,
+    Json,
+    Markdown,
+}
+
+/// An extra metric to measure for each algorithm, beyond the per-game metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BenchMetric {
+    /// The time to recover the first game when decompressing an archive of the games with a record per game,
+    /// against an archive compressed as a whole
+    FirstGameLatency,
+}
+
+/// What to favor when recommending an algorithm from benchmark results
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Priority {
+    /// Favor the smallest compressed size
+    Size,
+    /// Favor the fastest compression and decompression
+    Speed,
+    /// Weigh size and speed equally
+    Balanced,
+}
+
+impl Priority {
+    /// Get the weights given to size and speed, which sum to 1
+    fn weights(self) -> (f64, f64) {
+        match self {
+            Priority::Size => (1.0, 0.0),
+            Priority::Speed => (0.0, 1.0),
+            Priority::Balanced => (0.5, 0.5),
+        }
+    }
+}
+
+impl Display for Priority {
+    /// Display the priority as it is given on the command line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("No priority is skipped");
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// Score each algorithm by its compression ratio and total time to compress and decompress, each relative to the best
+/// of any algorithm so that 1 is the best possible, then weighted by the priority. Lower scores are better.
+/// Algorithms with no successful games are not scored, as their results are meaningless.
+pub fn score_algorithms(
+    summaries: &[(Algorithm, Summary)],
+    priority: Priority,
+) -> Vec<(Algorithm, f64)> {
+    let summaries = summaries
+        .iter()
+        .filter(|(_, summary)| summary.total_games > 0)
+        .map(|(algorithm, summary)| {
+            let time = summary.avg_time_to_compress + summary.avg_time_to_decompress;
+            (*algorithm, summary.compression_ratio, time)
+        })
+        .collect::<Vec<_>>();
+    let best_ratio = summaries.iter().map(|s| s.1).fold(f64::INFINITY, f64::min);
+    let best_time = summaries.iter().map(|s| s.2).fold(f64::INFINITY, f64::min);
+
+    // a best of zero would divide by zero, so relative values are clamped to at least the smallest positive value
+    let relative = |value: f64, best: f64| value / best.max(f64::MIN_POSITIVE);
+    let (size_weight, speed_weight) = priority.weights();
+    summaries
+        .into_iter()
+        .map(|(algorithm, ratio, time)| {
+            let score = size_weight * relative(ratio, best_ratio)
+                + speed_weight * relative(time, best_time);
+            (algorithm, score)
+        })
+        .collect()
+}
+
+/// Recommend the algorithm with the lowest score for the priority, if any algorithm was scored
+pub fn recommend(summaries: &[(Algorithm, Summary)], priority: Priority) -> Option<Algorithm> {
+    score_algorithms(summaries, priority)
+        .into_iter()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(algorithm, _)| algorithm)
+}
+
+/// Get a digest of the games a benchmark selects and the settings that change how they are measured, so two runs with
+/// the same digest measured the same games in the same way. Reading the games again costs a pass over the selection.
+fn bench_digest(config: &BenchConfig) -> Result<u64, CgnCliError> {
+    let games = map_games(
+        &config.input_db_path,
+        &config.number_of_games,
+        &config.filter,
+        config.sampling,
+        &ProgressBar::hidden(),
+        |pgn_str| fnv1a(pgn_str.as_bytes()),
+    )?;
+    let settings = format!(
+        "{:?} {} {} {:?} {:?}",
+        config.repetitions,
+        config.strict,
+        config.strict_roundtrip,
+        config.metric,
+        config.strip_comments
     );
+    let mut bytes = games
+        .into_iter()
+        .flat_map(u64::to_le_bytes)
+        .collect::<Vec<_>>();
+    bytes.extend(settings.as_bytes());
+    Ok(fnv1a(&bytes))
+}
+
+/// Configuration for a benchmark run
+pub struct BenchConfig {
+    pub number_of_games: ToTake,
+    pub input_db_path: String,
+    pub output_path: Option<String>,
+    pub format: BenchFormat,
+    pub filter: GameFilter,
+    pub sampling: Sampling,
+    pub measure_memory: bool,
+    pub recommend: Option<Priority>,
+    pub repetitions: Repetitions,
+    pub strict: bool,
+    /// Fail on the first game any algorithm does not round-trip byte for byte, printing the game
+    pub strict_roundtrip: bool,
+    pub ratio_by_length: bool,
+    pub by_eco: Option<EcoGrouping>,
+    pub histogram: Option<Algorithm>,
+    pub metric: Option<BenchMetric>,
+    pub strip_comments: StripComments,
+    /// A previous JSON benchmark report to compare the results against
+    pub baseline: Option<String>,
+    /// The percentage the compression ratio may get worse than the baseline by
+    pub tolerance: f64,
+    /// The percentage a timing may get worse than the baseline by
+    pub time_tolerance: f64,
+}
 
-    // print the summary to the console
-    println!("{}", metrics_to_summary(&metrics));
+/// The benchmark results for a single compression strategy, in a machine-readable form
+#[derive(Serialize)]
+struct AlgorithmReport {
+    algorithm: String,
+    number_of_games: usize,
+    failed_games: usize,
+    compression_ratio: f64,
+    avg_time_to_compress: f64,
+    avg_time_to_decompress: f64,
+    total_bytes_in: usize,
+    total_bytes_out: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_memory_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<TimingSpread>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio_by_length: Option<Vec<LengthBucket>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio_by_eco: Option<Vec<EcoGroup>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_game_latency: Option<FirstGameLatency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stripped: Option<StrippedRatios>,
+}
 
-    // write the metrics to a file if an output path is specified
-    if let Some(output_path) = output_path {
-        metrics_to_file("DYNAMIC HUFFMAN", &metrics, output_path);
+impl AlgorithmReport {
+    /// Create a report from the metrics collected for an algorithm, its peak heap usage if measured,
+    /// the spread of its timings if measured over several iterations, its ratio by game length if asked for,
+    /// its ratio by opening if asked for, its time to the first game if measured, and its ratios before and after
+    /// stripping comments if they were
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        algorithm: Algorithm,
+        metrics: &[Result<Metrics>],
+        peak_memory_bytes: Option<usize>,
+        timing: Option<TimingSpread>,
+        by_length: bool,
+        by_eco: Option<EcoGrouping>,
+        first_game_latency: Option<FirstGameLatency>,
+        strip: StripComments,
+    ) -> Self {
+        let summary = metrics_to_summary(metrics);
+        let games = metrics.iter().flatten();
+        Self {
+            algorithm: algorithm.to_string(),
+            number_of_games: summary.total_games,
+            failed_games: summary.failed_games,
+            compression_ratio: summary.compression_ratio,
+            avg_time_to_compress: summary.avg_time_to_compress,
+            avg_time_to_decompress: summary.avg_time_to_decompress,
+            total_bytes_in: games.clone().map(|m| m.decompressed_size / 8).sum(),
+            total_bytes_out: games.map(|m| m.compressed_size.div_ceil(8)).sum(),
+            peak_memory_bytes,
+            timing,
+            ratio_by_length: by_length.then(|| ratio_by_length(metrics)),
+            ratio_by_eco: by_eco.map(|grouping| ratio_by_eco(metrics, grouping)),
+            first_game_latency,
+            stripped: (strip != StripComments::None).then(|| stripped_ratios(metrics, strip)),
+        }
     }
 }
 
-/// Collects and prints metrics for the opening huffman compression strategy.
-fn bench_opening_huffman(n: &ToTake, db_path: &str, output_path: &Option<String>) {
-    println!("[BENCHMARK] Collecting metrics for opening huffman...");
-    let metrics = collect_metrics(
-        opening_huffman::compress_pgn_data,
-        opening_huffman::decompress_pgn_data,
-        db_path,
-        n,
+/// The benchmark results for every compression strategy, in a machine-readable form
+#[derive(Serialize)]
+struct BenchReport<'a> {
+    digest: String,
+    db_path: &'a str,
+    number_of_games: usize,
+    algorithms: Vec<AlgorithmReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histogram: Option<Vec<SizeBucket>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommendation: Option<String>,
+}
+
+/// Collects metrics for every compression strategy on the global thread pool.
+/// In strict mode, the benchmark stops at the first game an algorithm fails on and returns it as an error.
+/// With a strict round-trip, it also stops there, returning the game with what it decompressed to if it was lossy.
+/// Given a baseline, the results are compared against it, returning an error if any algorithm regressed.
+pub fn bench(config: BenchConfig) -> Result<(), CgnCliError> {
+    // read the baseline up front, so a bad baseline fails before the benchmark runs
+    let baseline = config.baseline.as_deref().map(load_baseline).transpose()?;
+
+    // benchmark the algorithms, in parallel unless measuring memory, keeping the results in a fixed order
+    if config.format == BenchFormat::Human {
+        output::status("[BENCHMARK] Collecting metrics for all algorithms...");
+        if let Sampling::Random(seed) = config.sampling {
+            output::status(format!(
+                "[BENCHMARK] Sampling games at random with seed {}",
+                seed
+            ));
+        }
+        let Repetitions { warmup, iterations } = config.repetitions;
+        if warmup > 0 || iterations > 1 {
+            output::status(format!(
+                "[BENCHMARK] Timing {} iteration(s) after {} warmup run(s)",
+                iterations, warmup
+            ));
+        }
+    }
+    let algorithms = Algorithm::value_variants();
+    let total_games = config
+        .number_of_games
+        .count()
+        .map(|n| (n * algorithms.len() * config.repetitions.runs()) as u64);
+    let progress = progress_bar(total_games, "games");
+    let stop = AtomicBool::new(false);
+    let run = |algorithm: Algorithm| {
+        collect_metrics(
+            codec_for(algorithm).as_ref(),
+            &config.input_db_path,
+            &config.number_of_games,
+            &config.filter,
+            config.sampling,
+            &progress,
+            (config.strict || config.strict_roundtrip).then_some(&stop),
+            config.strip_comments,
+        )
+    };
+
+    // discard the warmup runs, then average the timings of the measured runs
+    let repeat = |algorithm: Algorithm| -> Result<_, CgnCliError> {
+        for _ in 0..config.repetitions.warmup {
+            run(algorithm)?;
+        }
+        let iterations = (0..config.repetitions.iterations)
+            .map(|_| run(algorithm))
+            .collect::<Result<_, _>>()?;
+        Ok(combine_iterations(iterations))
+    };
+    let runs = if config.measure_memory {
+        // run the algorithms one at a time so each peak only covers a single algorithm
+        algorithms
+            .iter()
+            .map(|&algorithm| {
+                memory::reset_peak();
+                let (metrics, spread) = repeat(algorithm)?;
+                Ok(((algorithm, metrics), (memory::peak_bytes(), spread)))
+            })
+            .collect::<Result<Vec<_>, CgnCliError>>()?
+    } else {
+        algorithms
+            .par_iter()
+            .map(|&algorithm| {
+                let (metrics, spread) = repeat(algorithm)?;
+                Ok(((algorithm, metrics), (None, spread)))
+            })
+            .collect::<Result<Vec<_>, CgnCliError>>()?
+    };
+    let (results, extras): (Vec<_>, Vec<_>) = runs.into_iter().unzip();
+    let (peaks, spreads): (Vec<_>, Vec<_>) = extras.into_iter().unzip();
+    progress.finish_and_clear();
+
+    // report the first failure, in algorithm and database order, if strict
+    if config.strict || config.strict_roundtrip {
+        for (algorithm, metrics) in &results {
+            if let Some((game, Err(e))) = metrics.iter().enumerate().find(|(_, x)| is_failure(x)) {
+                if let Some(mismatch) = e
+                    .downcast_ref::<Mismatch>()
+                    .filter(|_| config.strict_roundtrip)
+                {
+                    return Err(CgnCliError::LossyRoundTrip {
+                        algorithm: *algorithm,
+                        game,
+                        expected: mismatch.expected.clone(),
+                        decompressed: mismatch.decompressed.clone(),
+                    });
+                }
+                return Err(CgnCliError::BenchmarkFailed {
+                    algorithm: *algorithm,
+                    game,
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+    if config.strict_roundtrip {
+        let checked = results
+            .iter()
+            .map(|(_, metrics)| metrics.iter().flatten().count());
+        output::note(format!(
+            "[BENCHMARK] Every algorithm round-tripped all {} game(s) with moves losslessly",
+            checked.min().unwrap_or(0)
+        ));
+    }
+
+    // time the first game of archives of every game that no algorithm failed on, if asked
+    let latencies = match config.metric {
+        Some(BenchMetric::FirstGameLatency) => {
+            let games = map_games(
+                &config.input_db_path,
+                &config.number_of_games,
+                &config.filter,
+                config.sampling,
+                &ProgressBar::hidden(),
+                |pgn_str| pgn_str.to_string(),
+            )?;
+            let games = games
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| results.iter().all(|(_, metrics)| metrics[*i].is_ok()))
+                .map(|(_, game)| game)
+                .collect::<Vec<_>>();
+            algorithms
+                .iter()
+                .map(|&algorithm| first_game_latency(&games, algorithm).map(Some))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        None => vec![None; algorithms.len()],
+    };
+
+    // recommend the algorithm that scores best for the priority, if asked
+    let recommendation = config.recommend.and_then(|priority| {
+        let summaries = results
+            .iter()
+            .map(|(algorithm, metrics)| (*algorithm, metrics_to_summary(metrics)))
+            .collect::<Vec<_>>();
+        recommend(&summaries, priority).map(|algorithm| {
+            format!(
+                "For {} priority on this database, use {}",
+                priority, algorithm
+            )
+        })
+    });
+
+    // show the results on the terminal, and write a copy to the output file if given
+    let outcome = BenchOutcome {
+        digest: bench_digest(&config)?,
+        results,
+        peaks,
+        spreads,
+        latencies,
+        recommendation,
+    };
+    print!(
+        "{}",
+        outcome.render(&config, config.format, terminal_width())
     );
+    if let Some(output_path) = &config.output_path {
+        outcome.write_to_file(&config, output_path)?;
+    }
 
-    // print the summary to the console
-    println!("{}", metrics_to_summary(&metrics));
+    // compare against the baseline on stderr, so the results on stdout stay machine-readable
+    if let Some(baseline) = baseline {
+        let current = outcome
+            .results
+            .iter()
+            .map(|(algorithm, metrics)| {
+                let summary = metrics_to_summary(metrics);
+                BaselineResult {
+                    algorithm: algorithm.to_string(),
+                    compression_ratio: summary.compression_ratio,
+                    avg_time_to_compress: summary.avg_time_to_compress,
+                    avg_time_to_decompress: summary.avg_time_to_decompress,
+                }
+            })
+            .collect::<Vec<_>>();
+        let comparisons =
+            compare_to_baseline(&current, &baseline, config.tolerance, config.time_tolerance);
+        output::note(format!(
+            "[BENCHMARK] Compared with {} (tolerance {}%, {}% for timings):\n{}",
+            config.baseline.as_deref().unwrap_or_default(),
+            config.tolerance,
+            config.time_tolerance,
+            comparison_table(&comparisons).trim_end()
+        ));
+        let regressed = comparisons.iter().filter(|c| c.regressed()).count();
+        if regressed > 0 {
+            return Err(CgnCliError::BenchmarkRegressed {
+                algorithms: regressed,
+                tolerance: config.tolerance,
+                time_tolerance: config.time_tolerance,
+            });
+        }
+    }
+    Ok(())
+}
 
-    // write the metrics to a file if an output path is specified
-    if let Some(output_path) = output_path {
-        metrics_to_file("OPENING HUFFMAN", &metrics, output_path);
+/// The results of a benchmark run, in algorithm order, ready to be shown or written in any format
+struct BenchOutcome {
+    digest: u64,
+    results: Vec<(Algorithm, Vec<Result<Metrics>>)>,
+    peaks: Vec<Option<usize>>,
+    spreads: Vec<Option<TimingSpread>>,
+    latencies: Vec<Option<FirstGameLatency>>,
+    recommendation: Option<String>,
+}
+
+impl BenchOutcome {
+    /// Render the results in a format, drawing any histogram to fit the width
+    fn render(&self, config: &BenchConfig, format: BenchFormat, width: usize) -> String {
+        let results = &self.results;
+        let mut out = String::new();
+        match format {
+            BenchFormat::Human => {
+                out += &format!("[BENCHMARK] Digest: {:016x}\n", self.digest);
+                for ((((algorithm, metrics), peak), spread), latency) in results
+                    .iter()
+                    .zip(&self.peaks)
+                    .zip(&self.spreads)
+                    .zip(&self.latencies)
+                {
+                    out += &format!("[BENCHMARK] Metrics for {}:\n", label(*algorithm));
+                    out += &metrics_to_summary(metrics).to_string();
+                    if let Some(spread) = spread {
+                        out += &spread.to_string();
+                    }
+                    if let Some(latency) = latency {
+                        out += &latency.to_string();
+                    }
+                    if let Some(peak) = peak {
+                        out += &format!("Peak memory: {} bytes\n", peak);
+                    }
+                    if config.strip_comments != StripComments::None {
+                        let ratios = stripped_ratios(metrics, config.strip_comments);
+                        out += &format!(
+                            "Compression ratio of the original games: {}\n",
+                            ratios.original_ratio
+                        );
+                        out += &format!(
+                            "Compression ratio with {} comments stripped: {}\n",
+                            config.strip_comments, ratios.stripped_ratio
+                        );
+                    }
+                    out += "\n";
+                }
+                if config.ratio_by_length {
+                    out += "[BENCHMARK] Compression ratio by game length:\n";
+                    out += &format!("{}\n", ratio_by_length_table(results, false));
+                }
+                if let Some(grouping) = config.by_eco {
+                    for (algorithm, metrics) in results {
+                        out += &format!(
+                            "[BENCHMARK] Compression ratio by {} for {}:\n",
+                            grouping,
+                            label(*algorithm)
+                        );
+                        let groups = ratio_by_eco(metrics, grouping);
+                        out += &format!("{}\n", ratio_by_eco_table(&groups, grouping, false));
+                    }
+                }
+                if let Some(algorithm) = config.histogram {
+                    out += &format!("[BENCHMARK] Compressed sizes for {}:\n", label(algorithm));
+                    let buckets = size_histogram(metrics_for(results, algorithm));
+                    out += &format!("{}\n", histogram_chart(&buckets, width));
+                }
+                if let Some(recommendation) = &self.recommendation {
+                    out += &format!("[BENCHMARK] {}\n", recommendation);
+                }
+            }
+            BenchFormat::Json => {
+                let report = BenchReport {
+                    digest: format!("{:016x}", self.digest),
+                    db_path: &config.input_db_path,
+                    number_of_games: results.first().map_or(0, |(_, metrics)| metrics.len()),
+                    algorithms: results
+                        .iter()
+                        .zip(&self.peaks)
+                        .zip(&self.spreads)
+                        .zip(&self.latencies)
+                        .map(|((((algorithm, metrics), peak), spread), latency)| {
+                            AlgorithmReport::new(
+                                *algorithm,
+                                metrics,
+                                *peak,
+                                *spread,
+                                config.ratio_by_length,
+                                config.by_eco,
+                                *latency,
+                                config.strip_comments,
+                            )
+                        })
+                        .collect(),
+                    histogram: config
+                        .histogram
+                        .map(|algorithm| size_histogram(metrics_for(results, algorithm))),
+                    recommendation: self.recommendation.clone(),
+                };
+                out +=
+                    &serde_json::to_string_pretty(&report).expect("Failed to serialize benchmark");
+                out += "\n";
+            }
+            BenchFormat::Markdown => {
+                out += &format!("Digest: `{:016x}`\n\n", self.digest);
+                out += &results_to_markdown(&config.input_db_path, results);
+                if config.metric == Some(BenchMetric::FirstGameLatency) {
+                    out += &format!(
+                        "\nTime to first game\n\n{}",
+                        latency_to_markdown(results, &self.latencies)
+                    );
+                }
+                if config.strip_comments != StripComments::None {
+                    out += &format!(
+                        "\nCompression ratio with {} comments stripped\n\n{}",
+                        config.strip_comments,
+                        stripped_to_markdown(results, config.strip_comments)
+                    );
+                }
+                if config.ratio_by_length {
+                    out += &format!(
+                        "\nCompression ratio by game length\n\n{}",
+                        ratio_by_length_table(results, true)
+                    );
+                }
+                if let Some(grouping) = config.by_eco {
+                    for (algorithm, metrics) in results {
+                        let groups = ratio_by_eco(metrics, grouping);
+                        out += &format!(
+                            "\nCompression ratio by {} for {}\n\n{}",
+                            grouping,
+                            label(*algorithm),
+                            ratio_by_eco_table(&groups, grouping, true)
+                        );
+                    }
+                }
+                if let Some(algorithm) = config.histogram {
+                    let buckets = size_histogram(metrics_for(results, algorithm));
+                    out += &format!(
+                        "\nCompressed sizes for {}\n\n```\n{}```\n",
+                        label(algorithm),
+                        histogram_chart(&buckets, width)
+                    );
+                }
+                if let Some(recommendation) = &self.recommendation {
+                    out += &format!("\n{}\n", recommendation);
+                }
+            }
+        }
+        out
+    }
+
+    /// Write a copy of the results to a file in the format matching its extension: CSV for .csv, JSON for .json,
+    /// and Markdown for .md, replacing any existing file. Any other file has the metrics of every game appended to it.
+    fn write_to_file(&self, config: &BenchConfig, output_path: &str) -> Result<(), CgnCliError> {
+        let format = match Path::new(output_path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("csv") => return results_to_csv(&self.results, output_path),
+            Some("json") => BenchFormat::Json,
+            Some("md") => BenchFormat::Markdown,
+            _ => {
+                for (algorithm, metrics) in &self.results {
+                    metrics_to_file(&label(*algorithm).to_uppercase(), metrics, output_path)?;
+                }
+                return Ok(());
+            }
+        };
+        let report = self.render(config, format, DEFAULT_TERMINAL_WIDTH);
+        write_atomically(Path::new(output_path), report.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Get the label used for an algorithm in benchmark output
+fn label(algorithm: Algorithm) -> String {
+    algorithm.to_string().replace('-', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get a summary of games with a compression ratio and a total time split evenly between compression and
+    /// decompression
+    fn summary(compression_ratio: f64, time: f64) -> Summary {
+        Summary {
+            total_games: 10,
+            failed_games: 0,
+            avg_time_to_compress: time / 2.0,
+            avg_time_to_decompress: time / 2.0,
+            avg_compressed_size: 0,
+            avg_decompressed_size: 0,
+            avg_bits_per_move: 0.0,
+            avg_bits_per_move_excluding_headers: 0.0,
+            compression_ratio,
+        }
+    }
+
+    #[test]
+    fn recommends_by_priority() {
+        let summaries = [
+            (Algorithm::Bincode, summary(0.5, 1.0)),
+            (Algorithm::Huffman, summary(0.3, 4.0)),
+            (Algorithm::OpeningHuffman, summary(0.2, 10.0)),
+        ];
+        assert_eq!(
+            recommend(&summaries, Priority::Size),
+            Some(Algorithm::OpeningHuffman)
+        );
+        assert_eq!(
+            recommend(&summaries, Priority::Speed),
+            Some(Algorithm::Bincode)
+        );
+        // bincode scores (2.5 + 1) / 2 and huffman (1.5 + 4) / 2
+        assert_eq!(
+            recommend(&summaries, Priority::Balanced),
+            Some(Algorithm::Bincode)
+        );
+    }
+
+    #[test]
+    fn scores_relative_to_the_best() {
+        let summaries = [
+            (Algorithm::Bincode, summary(0.4, 1.0)),
+            (Algorithm::Huffman, summary(0.2, 2.0)),
+        ];
+        let scores = score_algorithms(&summaries, Priority::Balanced);
+        assert_eq!(
+            scores,
+            [(Algorithm::Bincode, 1.5), (Algorithm::Huffman, 1.5)]
+        );
+    }
+
+    #[test]
+    fn ties_recommend_the_first_algorithm() {
+        let summaries = [
+            (Algorithm::Huffman, summary(0.3, 2.0)),
+            (Algorithm::Bincode, summary(0.3, 2.0)),
+        ];
+        for priority in [Priority::Size, Priority::Speed, Priority::Balanced] {
+            assert_eq!(recommend(&summaries, priority), Some(Algorithm::Huffman));
+        }
+    }
+
+    #[test]
+    fn recommends_a_single_candidate() {
+        let summaries = [(Algorithm::DynamicHuffman, summary(0.25, 3.0))];
+        assert_eq!(
+            score_algorithms(&summaries, Priority::Balanced),
+            [(Algorithm::DynamicHuffman, 1.0)]
+        );
+        assert_eq!(
+            recommend(&summaries, Priority::Size),
+            Some(Algorithm::DynamicHuffman)
+        );
+    }
+
+    #[test]
+    fn algorithms_without_games_are_not_recommended() {
+        let mut empty = summary(0.0, 0.0);
+        empty.total_games = 0;
+        assert_eq!(
+            recommend(&[(Algorithm::Bincode, empty)], Priority::Balanced),
+            None
+        );
+        assert_eq!(recommend(&[], Priority::Balanced), None);
     }
 }
@@ -0,0 +1,323 @@
+use super::archive::{decompress, decompress_records, extract_game, game_count, is_single_game};
+use super::error::CgnCliError;
+use super::filter::header_value;
+use super::header::{read_header, Header};
+use super::io_util::{check_overwrite, open_reader, write_atomically};
+use super::list::describe_game;
+use super::pgn_db::PgnDbIter;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+
+/// The number of games moved by page up and page down
+const PAGE_LEN: usize = 10;
+
+/// How long to wait for a key press before checking again, once every game is listed
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The keys available in the game list, shown at the bottom of the screen
+const HELP: &str = "↑/↓ move  PgUp/PgDn page  Home/End jump  u/d scroll PGN  / search  n next match  e export  q quit";
+
+/// A game shown in the list, with its players lowercased for searching
+struct ListedGame {
+    description: String,
+    players: String,
+}
+
+impl ListedGame {
+    /// Describe a decompressed game
+    fn new(index: usize, pgn_str: &str) -> Self {
+        let players = ["White", "Black"]
+            .map(|key| header_value(pgn_str, key).unwrap_or_default())
+            .join(" ")
+            .to_lowercase();
+        Self {
+            description: describe_game(index, pgn_str),
+            players,
+        }
+    }
+
+    /// Describe a game that could not be decompressed
+    fn failed(index: usize, e: &CgnCliError) -> Self {
+        Self {
+            description: format!("{}: failed to decompress ({})", index, e),
+            players: String::new(),
+        }
+    }
+}
+
+/// What typed keys are used for
+enum Mode {
+    /// Keys move through the games
+    Normal,
+    /// Keys are typed into the player name to search for
+    Search(String),
+    /// Keys are typed into the path to export the selected game to
+    Export(String),
+}
+
+/// The state of the browser for a CGN file
+struct Browser<'a> {
+    header: &'a Header,
+    payload: &'a [u8],
+    /// The decompressed games of a file without one record per game, which must be decompressed all at once
+    decompressed: Option<Vec<String>>,
+    /// The games not yet listed, which are decompressed one at a time while waiting for key presses
+    pending: Option<Box<dyn Iterator<Item = Result<String, CgnCliError>> + 'a>>,
+    total: usize,
+    games: Vec<ListedGame>,
+    list_state: ListState,
+    /// The selected game's PGN, decompressed when it is selected
+    selected_pgn: Option<(usize, String)>,
+    pgn_scroll: u16,
+    mode: Mode,
+    query: String,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    /// Create a browser for the payload of a CGN file. Files with one record per game are listed as they are browsed.
+    fn new(header: &'a Header, payload: &'a [u8]) -> Result<Self, CgnCliError> {
+        let (decompressed, pending): (_, Option<Box<dyn Iterator<Item = _> + 'a>>) =
+            if is_single_game(header) {
+                let pgn_data = decompress(header, payload)?;
+                let games = PgnDbIter::new(BufReader::new(pgn_data.as_bytes())).collect::<Vec<_>>();
+                (Some(games), None)
+            } else {
+                (None, Some(Box::new(decompress_records(header, payload)?)))
+            };
+
+        let mut browser = Self {
+            header,
+            payload,
+            total: match &decompressed {
+                Some(games) => games.len(),
+                None => game_count(header, payload)?,
+            },
+            decompressed,
+            pending,
+            games: Vec::new(),
+            list_state: ListState::default(),
+            selected_pgn: None,
+            pgn_scroll: 0,
+            mode: Mode::Normal,
+            query: String::new(),
+            status: String::from(HELP),
+        };
+        if let Some(games) = &browser.decompressed {
+            browser.games = games
+                .iter()
+                .enumerate()
+                .map(|(i, pgn_str)| ListedGame::new(i, pgn_str))
+                .collect();
+            if !browser.games.is_empty() {
+                browser.list_state.select(Some(0));
+            }
+        }
+        Ok(browser)
+    }
+
+    /// List the next pending game, returning false once every game is listed
+    fn load_next(&mut self) -> bool {
+        let Some(pending) = &mut self.pending else {
+            return false;
+        };
+        let index = self.games.len();
+        match pending.next() {
+            Some(Ok(pgn_str)) => self.games.push(ListedGame::new(index, &pgn_str)),
+            Some(Err(e)) => self.games.push(ListedGame::failed(index, &e)),
+            None => {
+                self.pending = None;
+                return false;
+            }
+        }
+        if self.list_state.selected().is_none() {
+            self.list_state.select(Some(0));
+        }
+        true
+    }
+
+    /// Get the PGN of the selected game, decompressing it if a different game was selected last
+    fn selected_pgn(&mut self) -> Option<&str> {
+        let index = self.list_state.selected()?;
+        if self.selected_pgn.as_ref().is_none_or(|(i, _)| *i != index) {
+            let pgn_str = match &self.decompressed {
+                Some(games) => games[index].clone(),
+                None => extract_game(self.header, self.payload, index)
+                    .unwrap_or_else(|e| format!("Failed to decompress game {}: {}", index, e)),
+            };
+            self.selected_pgn = Some((index, pgn_str));
+            self.pgn_scroll = 0;
+        }
+        self.selected_pgn
+            .as_ref()
+            .map(|(_, pgn_str)| pgn_str.as_str())
+    }
+
+    /// Move the selection by a number of games, staying within the listed games
+    fn move_selection(&mut self, by: isize) {
+        if self.games.is_empty() {
+            return;
+        }
+        let selected = self.list_state.selected().unwrap_or(0);
+        let selected = selected.saturating_add_signed(by).min(self.games.len() - 1);
+        self.list_state.select(Some(selected));
+    }
+
+    /// Select the next listed game, after the selected one and wrapping around, whose players contain the query
+    fn find_next(&mut self) {
+        if self.query.is_empty() {
+            return;
+        }
+        let start = self.list_state.selected().map_or(0, |i| i + 1);
+        let len = self.games.len();
+        let found = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&i| self.games[i].players.contains(&self.query));
+        match found {
+            Some(i) => {
+                self.list_state.select(Some(i));
+                self.status = format!("Found \"{}\" in game {}", self.query, i);
+            }
+            None => {
+                self.status = format!("No listed game has a player matching \"{}\"", self.query)
+            }
+        }
+    }
+
+    /// Write the selected game to a file, refusing to overwrite an existing file
+    fn export(&mut self, output_path: &str) {
+        let Some(index) = self.list_state.selected() else {
+            self.status = String::from("No game is selected");
+            return;
+        };
+        let written = check_overwrite(Path::new(output_path), false).and_then(|_| {
+            let pgn_str = self.selected_pgn().unwrap_or_default().to_string();
+            write_atomically(Path::new(output_path), pgn_str.as_bytes()).map_err(CgnCliError::from)
+        });
+        self.status = match written {
+            Ok(()) => format!("Exported game {} to {}", index, output_path),
+            Err(e) => format!("Failed to export game {}: {}", index, e),
+        };
+    }
+
+    /// Handle a key press, returning false if the browser should quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match &mut self.mode {
+            Mode::Normal => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::PageDown => self.move_selection(PAGE_LEN as isize),
+                KeyCode::PageUp => self.move_selection(-(PAGE_LEN as isize)),
+                KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN),
+                KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX),
+                KeyCode::Char('d') => self.pgn_scroll = self.pgn_scroll.saturating_add(1),
+                KeyCode::Char('u') => self.pgn_scroll = self.pgn_scroll.saturating_sub(1),
+                KeyCode::Char('/') => self.mode = Mode::Search(String::new()),
+                KeyCode::Char('n') => self.find_next(),
+                KeyCode::Char('e') => {
+                    let index = self.list_state.selected().unwrap_or(0);
+                    self.mode = Mode::Export(format!("game-{}.pgn", index));
+                }
+                _ => {}
+            },
+            Mode::Search(input) | Mode::Export(input) => match key.code {
+                KeyCode::Esc => {
+                    self.mode = Mode::Normal;
+                    self.status = String::from(HELP);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => match std::mem::replace(&mut self.mode, Mode::Normal) {
+                    Mode::Search(query) => {
+                        self.query = query.to_lowercase();
+                        self.find_next();
+                    }
+                    Mode::Export(output_path) => self.export(&output_path),
+                    Mode::Normal => {}
+                },
+                _ => {}
+            },
+        }
+        true
+    }
+
+    /// Draw the game list, the selected game's PGN, and the status line
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, pgn_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let title = if self.games.len() < self.total {
+            format!(" Games (listed {} of {}) ", self.games.len(), self.total)
+        } else {
+            format!(" Games ({}) ", self.total)
+        };
+        let list = List::new(self.games.iter().map(|game| game.description.as_str()))
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let pgn_scroll = self.pgn_scroll;
+        let pgn = Paragraph::new(self.selected_pgn().unwrap_or_default().to_string())
+            .block(Block::bordered().title(" PGN "))
+            .scroll((pgn_scroll, 0));
+        frame.render_widget(pgn, pgn_area);
+
+        let status_line = match &self.mode {
+            Mode::Normal => Line::from(self.status.as_str()),
+            Mode::Search(input) => Line::from(format!("Search players: {}", input)),
+            Mode::Export(input) => Line::from(format!("Export to: {}", input)),
+        };
+        frame.render_widget(status_line, status);
+    }
+
+    /// Draw the browser and handle key presses until it is quit, listing pending games while no key is pressed
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), CgnCliError> {
+        let mut loading = true;
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let timeout = if loading {
+                Duration::ZERO
+            } else {
+                POLL_INTERVAL
+            };
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                        return Ok(());
+                    }
+                }
+            } else if loading {
+                loading = self.load_next();
+            }
+        }
+    }
+}
+
+/// Browse the games of a CGN file in an interactive terminal interface, with a list of games and the PGN of the
+/// selected game. Files with one record per game are listed a game at a time, and each game is only decompressed again
+/// when it is selected.
+pub fn browse(input_path: &str) -> Result<(), CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let (header, payload) = read_header(&compressed_data)?;
+    let mut browser = Browser::new(&header, payload)?;
+
+    // the terminal is restored on panic by ratatui, and here on every other exit
+    let mut terminal = ratatui::try_init()?;
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,48 @@
+use super::archive::{decompress_each, decompress_to, GAME_SEPARATOR};
+use super::error::CgnCliError;
+use super::header::read_header;
+use super::io_util::open_reader;
+use super::normalize;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+
+/// Decompress each CGN file in turn to the writer, with the algorithm of each read from its header.
+/// If `pretty` is set, each game is reformatted for reading as it is decompressed.
+fn cat_to<W: Write>(
+    input_paths: &[String],
+    writer: &mut W,
+    pretty: bool,
+) -> Result<(), CgnCliError> {
+    let mut games = 0;
+    for (i, input_path) in input_paths.iter().enumerate() {
+        let mut compressed_data = Vec::new();
+        open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+        let (header, payload) = read_header(&compressed_data)?;
+        if !pretty {
+            if i > 0 {
+                writer.write_all(GAME_SEPARATOR.as_bytes())?;
+            }
+            decompress_to(&header, payload, writer)?;
+            continue;
+        }
+        decompress_each(&header, payload, |_, pgn_str| {
+            if games > 0 {
+                writer.write_all(b"\n")?;
+            }
+            writer.write_all(normalize::pretty(&pgn_str).as_bytes())?;
+            games += 1;
+            Ok(())
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decompress CGN files to stdout, writing each game as it is decompressed so the output can be paged.
+/// A pager or other reader closing the pipe early stops the output without an error.
+pub fn cat(input_paths: &[String], pretty: bool) -> Result<(), CgnCliError> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    match cat_to(input_paths, &mut stdout, pretty) {
+        Err(CgnCliError::Io(e)) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,214 @@
+use super::algorithm::{Algorithm, CompressFn, DecompressFn, Tuning};
+use super::profile;
+use bit_vec::BitVec;
+use cgn::compression::dynamic_huffman::{compress_pgn_data_custom, decompress_pgn_data_custom};
+use cgn::compression::{bincode, dynamic_huffman, huffman, opening_huffman};
+use cgn::pgn_data::PgnData;
+use std::fmt::{self, Display, Formatter};
+use std::panic;
+use std::str::FromStr;
+
+/// The reasons the cgn library can fail to compress or decompress a game
+#[derive(Debug)]
+pub enum CodecError {
+    /// The input could not be parsed as a PGN game
+    InvalidPgn(String),
+    /// The library returned an error
+    Library(String),
+    /// The library panicked, which it does on some corrupt data
+    Panicked,
+}
+
+impl Display for CodecError {
+    /// Display the reason for the failure
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::InvalidPgn(e) => write!(f, "input is not a valid PGN game ({})", e),
+            CodecError::Library(e) => write!(f, "{}", e),
+            CodecError::Panicked => write!(f, "the cgn library panicked"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Check if an input holds no games, being empty or only whitespace.
+/// Such inputs compress to an empty payload, which decompresses back to an empty string.
+fn is_empty_pgn(pgn_str: &str) -> bool {
+    pgn_str.trim().is_empty()
+}
+
+/// Parse a PGN string and compress it with a library function. An input with no games compresses to an empty vector.
+fn compress_with<F>(pgn_str: &str, compress_fn: F) -> Result<Vec<u8>, CodecError>
+where
+    F: FnOnce(&PgnData) -> anyhow::Result<BitVec>,
+{
+    if is_empty_pgn(pgn_str) {
+        return Ok(Vec::new());
+    }
+    let pgn_data = profile::time("parse", || PgnData::from_str(pgn_str))
+        .map_err(|e| CodecError::InvalidPgn(e.to_string()))?;
+    if pgn_data.is_empty() {
+        return Err(CodecError::InvalidPgn(String::from(
+            "game has no headers or moves",
+        )));
+    }
+    profile::time("compress", || compress_fn(&pgn_data))
+        .map(|compressed_data| compressed_data.to_bytes())
+        .map_err(|e| CodecError::Library(e.to_string()))
+}
+
+/// Decompress bytes with a library function into a PGN string. An empty payload decompresses to an empty string.
+/// Panics in the library are caught and returned as errors.
+fn decompress_with<F>(compressed_data: &[u8], decompress_fn: F) -> Result<String, CodecError>
+where
+    F: FnOnce(&BitVec) -> anyhow::Result<PgnData>,
+{
+    // an empty payload holds no games, and indexing into it panics in the library
+    if compressed_data.is_empty() {
+        return Ok(String::new());
+    }
+
+    let bit_vec = BitVec::from_bytes(compressed_data);
+    profile::time("decompress", || {
+        // the library functions hold no state, so nothing is left broken by a panic
+        panic::catch_unwind(panic::AssertUnwindSafe(|| decompress_fn(&bit_vec)))
+            .map_err(|_| CodecError::Panicked)?
+            .map(|pgn_data| pgn_data.to_string())
+            .map_err(|e| CodecError::Library(e.to_string()))
+    })
+}
+
+/// A way of compressing games, such as one of the algorithms or dynamic huffman with tuned parameters.
+/// Implementors provide the library functions working on parsed games, and get the handling of PGN strings, inputs
+/// with no games, and panics in the library from the provided methods.
+pub trait Codec: Send + Sync {
+    /// Compress a parsed game into a bit vector
+    fn compress_pgn_data(&self, pgn_data: &PgnData) -> anyhow::Result<BitVec>;
+
+    /// Decompress a bit vector into a parsed game
+    fn decompress_pgn_data(&self, bit_vec: &BitVec) -> anyhow::Result<PgnData>;
+
+    /// Compress a PGN string. An input with no games compresses to an empty vector.
+    fn compress(&self, pgn_str: &str) -> Result<Vec<u8>, CodecError> {
+        compress_with(pgn_str, |pgn_data| self.compress_pgn_data(pgn_data))
+    }
+
+    /// Decompress bytes into a PGN string. An empty payload decompresses to an empty string.
+    fn decompress(&self, compressed_data: &[u8]) -> Result<String, CodecError> {
+        decompress_with(compressed_data, |bit_vec| self.decompress_pgn_data(bit_vec))
+    }
+
+    /// Check that compressed data decompresses back to the PGN string it was compressed from.
+    /// The cgn library stores games in PGN reduced export format, so the comparison is made in that format.
+    fn round_trips(&self, pgn_str: &str, compressed_data: &[u8]) -> bool {
+        matches_original(pgn_str, self.decompress(compressed_data))
+    }
+}
+
+/// An algorithm of the cgn library, as the pair of functions compressing and decompressing with it
+struct LibraryCodec {
+    compress_fn: CompressFn,
+    decompress_fn: DecompressFn,
+}
+
+impl Codec for LibraryCodec {
+    /// Compress a parsed game with the library function
+    fn compress_pgn_data(&self, pgn_data: &PgnData) -> anyhow::Result<BitVec> {
+        (self.compress_fn)(pgn_data)
+    }
+
+    /// Decompress a bit vector with the library function
+    fn decompress_pgn_data(&self, bit_vec: &BitVec) -> anyhow::Result<PgnData> {
+        (self.decompress_fn)(bit_vec)
+    }
+}
+
+impl Codec for Tuning {
+    /// Compress a parsed game with dynamic huffman using the tuned parameters
+    fn compress_pgn_data(&self, pgn_data: &PgnData) -> anyhow::Result<BitVec> {
+        compress_pgn_data_custom(pgn_data, self.height, self.dev)
+    }
+
+    /// Decompress a bit vector with dynamic huffman using the tuned parameters
+    fn decompress_pgn_data(&self, bit_vec: &BitVec) -> anyhow::Result<PgnData> {
+        decompress_pgn_data_custom(bit_vec, self.height, self.dev)
+    }
+}
+
+/// Get the codec that compresses and decompresses with an algorithm. This is the only place an algorithm is mapped to
+/// the library functions implementing it, so a new algorithm only needs adding here and to `Algorithm`.
+pub fn codec_for(algorithm: Algorithm) -> Box<dyn Codec> {
+    let (compress_fn, decompress_fn): (CompressFn, DecompressFn) = match algorithm {
+        Algorithm::Bincode => (bincode::compress_pgn_data, bincode::decompress_pgn_data),
+        Algorithm::Huffman => (huffman::compress_pgn_data, huffman::decompress_pgn_data),
+        Algorithm::DynamicHuffman => (
+            dynamic_huffman::compress_pgn_data,
+            dynamic_huffman::decompress_pgn_data,
+        ),
+        Algorithm::OpeningHuffman => (
+            opening_huffman::compress_pgn_data,
+            opening_huffman::decompress_pgn_data,
+        ),
+    };
+    Box::new(LibraryCodec {
+        compress_fn,
+        decompress_fn,
+    })
+}
+
+/// Get the codec for an algorithm, or for dynamic huffman with tuned parameters if they are given, which only
+/// apply to dynamic huffman
+pub fn codec_with_tuning(algorithm: Algorithm, tuning: Option<Tuning>) -> Box<dyn Codec> {
+    match tuning {
+        Some(tuning) => Box::new(tuning),
+        None => codec_for(algorithm),
+    }
+}
+
+/// Compress a PGN string with an algorithm
+pub fn compress(algorithm: Algorithm, pgn_str: &str) -> Result<Vec<u8>, CodecError> {
+    codec_for(algorithm).compress(pgn_str)
+}
+
+/// Decompress bytes compressed with an algorithm into a PGN string
+pub fn decompress(algorithm: Algorithm, compressed_data: &[u8]) -> Result<String, CodecError> {
+    codec_for(algorithm).decompress(compressed_data)
+}
+
+/// Check the result of decompressing matches the PGN string that was compressed.
+/// The cgn library stores games in PGN reduced export format, so the comparison is made in that format.
+pub fn matches_original(pgn_str: &str, decompressed: Result<String, CodecError>) -> bool {
+    let Ok(decompressed) = decompressed else {
+        return false;
+    };
+    if is_empty_pgn(pgn_str) {
+        return decompressed.is_empty();
+    }
+    match PgnData::from_str(pgn_str) {
+        Ok(pgn_data) => decompressed == pgn_data.to_string(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn empty_inputs_round_trip() {
+        for &algorithm in Algorithm::value_variants() {
+            for pgn_str in ["", " \n\t\r\n "] {
+                let compressed_data = compress(algorithm, pgn_str).unwrap();
+                assert!(compressed_data.is_empty(), "{:?}", algorithm);
+                assert_eq!(decompress(algorithm, &compressed_data).unwrap(), "");
+                assert!(matches_original(
+                    pgn_str,
+                    decompress(algorithm, &compressed_data)
+                ));
+                assert!(algorithm.round_trips(pgn_str, &compressed_data));
+            }
+        }
+    }
+}
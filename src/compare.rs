@@ -0,0 +1,85 @@
+use super::algorithm::Algorithm;
+use super::benchmark::compression_ratio;
+use super::codec;
+use super::error::CgnCliError;
+use super::io_util::open_reader;
+use super::output;
+use super::pgn_db::PgnDbIter;
+use std::cmp::Ordering;
+use std::io::{BufReader, Read, Write};
+use std::time::Instant;
+
+/// The result of compressing every game in a file with a single algorithm
+struct CompareResult {
+    algorithm: Algorithm,
+    compressed_bytes: usize,
+    time_to_compress: f64,
+    time_to_decompress: f64,
+}
+
+/// Compress and decompress each game with an algorithm, recording the total size and time taken
+fn run(algorithm: Algorithm, games: &[String]) -> Result<CompareResult, CgnCliError> {
+    let mut result = CompareResult {
+        algorithm,
+        compressed_bytes: 0,
+        time_to_compress: 0.0,
+        time_to_decompress: 0.0,
+    };
+
+    for (i, pgn_str) in games.iter().enumerate() {
+        let start = Instant::now();
+        let compressed_pgn_data = codec::compress(algorithm, pgn_str)
+            .map_err(|e| CgnCliError::GameCompressionFailed(i, e))?;
+        result.time_to_compress += start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        codec::decompress(algorithm, &compressed_pgn_data)
+            .map_err(|e| CgnCliError::GameDecompressionFailed(i, e))?;
+        result.time_to_decompress += start.elapsed().as_secs_f64();
+        result.compressed_bytes += compressed_pgn_data.len();
+    }
+    Ok(result)
+}
+
+/// Compress every game in a PGN file with two algorithms and print which produced the smaller output
+pub fn compare(input_path: &str, algo_a: Algorithm, algo_b: Algorithm) -> Result<(), CgnCliError> {
+    let mut pgn_data = String::new();
+    open_reader(input_path)?.read_to_string(&mut pgn_data)?;
+    let games = PgnDbIter::new(BufReader::new(pgn_data.as_bytes())).collect::<Vec<_>>();
+
+    let a = run(algo_a, &games)?;
+    let b = run(algo_b, &games)?;
+
+    // print the results of each algorithm, then the winner
+    output::report(|out| {
+        writeln!(
+            out,
+            "Compared {} and {} on {} game(s) from {} ({} bytes)",
+            algo_a,
+            algo_b,
+            games.len(),
+            input_path,
+            pgn_data.len()
+        )?;
+        for result in [&a, &b] {
+            writeln!(
+                out,
+                "{}: {} bytes, a compression ratio of {:.4}, compressed in {:.3} seconds and decompressed in {:.3} seconds",
+                result.algorithm,
+                result.compressed_bytes,
+                compression_ratio(result.compressed_bytes, pgn_data.len()),
+                result.time_to_compress,
+                result.time_to_decompress
+            )?;
+        }
+        let difference = compression_ratio(a.compressed_bytes, pgn_data.len())
+            - compression_ratio(b.compressed_bytes, pgn_data.len());
+        writeln!(out, "Ratio difference: {:.4}", difference.abs())?;
+        match a.compressed_bytes.cmp(&b.compressed_bytes) {
+            Ordering::Less => writeln!(out, "Winner: {}", algo_a)?,
+            Ordering::Greater => writeln!(out, "Winner: {}", algo_b)?,
+            Ordering::Equal => writeln!(out, "Winner: tie")?,
+        }
+        Ok(())
+    })
+}
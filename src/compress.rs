@@ -0,0 +1,343 @@
+use super::algorithm::{Algorithm, AlgorithmSelection, Tuning};
+use super::archive::{compress_indexed, compress_stream};
+use super::codec::codec_with_tuning;
+use super::crypto::encrypt_file;
+use super::encoding::{DecodingReader, Encoding};
+use super::error::CgnCliError;
+use super::framed::compress_framed;
+use super::header::{write_extended_file, Extensions, Metadata};
+use super::io_util::{
+    inputs_len, is_url, open_readers, open_writer, strip_compression_extension, strip_url_query,
+    Counting, Output, SharedCounting, STDIO_PATH,
+};
+use super::normalize::{NormalizingReader, StripComments};
+use super::output;
+use super::pgn_db::GameWindow;
+use super::profile;
+use super::progress::bytes_progress_bar;
+use super::split::rotate;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+/// The sizes of the input and output of a compression, in bytes
+pub struct CompressSummary {
+    pub original_bytes: u64,
+    /// The size of the input once annotations were stripped from its comments, if they were
+    pub stripped_bytes: Option<u64>,
+    pub compressed_bytes: u64,
+}
+
+/// Options for compressing a single input
+pub struct CompressOptions {
+    pub algorithm: AlgorithmSelection,
+    pub verify: bool,
+    pub progress: bool,
+    pub stream: bool,
+    pub per_game: bool,
+    pub max_input_size: u64,
+    pub tuning: Option<Tuning>,
+    pub passphrase: Option<String>,
+    pub window: Option<GameWindow>,
+    pub encoding: Encoding,
+    pub normalize: bool,
+    pub strip_comments: StripComments,
+    /// Write the games as a bare framed stream for piping, with no header
+    pub framed: bool,
+    /// The number of games after which a streamed output is closed and the next numbered archive started
+    pub max_games_per_archive: Option<usize>,
+    /// Overwrite existing numbered archives when rotating
+    pub force: bool,
+    /// Store the time of creation and the version of the tool in the header
+    pub metadata: bool,
+}
+
+/// Open the output, or a sink that discards everything written to it if there is no output path
+fn open_output(output_path: Option<&str>) -> io::Result<Output> {
+    match output_path {
+        Some(output_path) => open_writer(output_path),
+        None => Ok(Output::sink()),
+    }
+}
+
+/// Compress with each candidate algorithm, keeping whichever produces the smallest output.
+/// The winning algorithm and the size of its payload, without the file header, are reported on stderr when there is
+/// more than one candidate.
+fn compress_smallest<F>(
+    selection: AlgorithmSelection,
+    mut compress: F,
+) -> Result<(Algorithm, Vec<u8>), CgnCliError>
+where
+    F: FnMut(Algorithm) -> Result<Vec<u8>, CgnCliError>,
+{
+    let candidates = selection.candidates();
+    let mut smallest: Option<(Algorithm, Vec<u8>)> = None;
+    for &algorithm in &candidates {
+        let compressed_pgn_data = compress(algorithm)?;
+        log::debug!(
+            "{} produced {} payload bytes",
+            algorithm,
+            compressed_pgn_data.len()
+        );
+        if smallest
+            .as_ref()
+            .is_none_or(|(_, smallest)| compressed_pgn_data.len() < smallest.len())
+        {
+            smallest = Some((algorithm, compressed_pgn_data));
+        }
+    }
+
+    let (algorithm, compressed_pgn_data) =
+        smallest.expect("an algorithm selection always has at least one candidate");
+    if candidates.len() > 1 {
+        output::note(format!(
+            "Best algorithm: {} ({} payload bytes)",
+            algorithm,
+            compressed_pgn_data.len()
+        ));
+    }
+    Ok((algorithm, compressed_pgn_data))
+}
+
+/// Encrypt a complete file with the passphrase, if one is given
+fn seal_output(file: Vec<u8>, passphrase: Option<&str>) -> Result<Vec<u8>, CgnCliError> {
+    match passphrase {
+        Some(passphrase) => encrypt_file(&file, passphrase),
+        None => Ok(file),
+    }
+}
+
+/// Get the base name of the input to store in the header, if there is a single input that is not stdin.
+/// For a URL, this is the last segment of its path. A compressed input is named after the file it decompresses to.
+fn original_name(input_paths: &[String]) -> Option<String> {
+    match input_paths {
+        [input_path] if input_path != STDIO_PATH => {
+            strip_compression_extension(Path::new(strip_url_query(input_path)))
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from)
+        }
+        _ => None,
+    }
+}
+
+/// Compress the inputs to the output path as a single input, returning the sizes of the input and output.
+/// Several inputs are concatenated in order with a newline between them.
+/// If there is no output path, the input is compressed in memory and nothing is written.
+/// Streamed output cannot be encrypted, as the payload is written before it has all been read.
+/// If a window of games is given, only the games in it are compressed, skipping the rest of the input as it is read.
+/// If normalizing, each game is rewritten in a canonical form as it is read, and the sizes are of the normalized games.
+/// If stripping comments, each game is also normalized, and the size of the input is given both before and after.
+/// If streaming with a limit on the games per archive, the output path is the prefix of the numbered archives.
+pub fn compress_input(
+    input_paths: &[String],
+    output_path: Option<&str>,
+    options: &CompressOptions,
+) -> Result<CompressSummary, CgnCliError> {
+    let input_path = input_paths.join(", ");
+    log::info!("Compressing {} with {}", input_path, options.algorithm);
+    let len = inputs_len(input_paths);
+    // the download of a URL shows its own progress
+    let downloading = input_paths.iter().any(|input_path| is_url(input_path));
+    let progress = bytes_progress_bar(len, options.progress && !downloading);
+    let reader = DecodingReader::new(
+        progress.wrap_read(open_readers(input_paths)?),
+        options.encoding,
+    );
+    let windowed: Box<dyn Read> = match options.window {
+        Some(window) => {
+            log::debug!(
+                "Compressing games of {} after skipping {}, taking {}",
+                input_path,
+                window.skip,
+                window
+                    .take
+                    .map_or(String::from("the rest"), |take| take.to_string())
+            );
+            Box::new(window.reader(BufReader::new(reader)))
+        }
+        None => Box::new(reader),
+    };
+    let (windowed, unstripped_bytes) = SharedCounting::new(windowed);
+    let stripping = options.strip_comments != StripComments::None;
+    let mut input: Counting<Box<dyn Read>> = if options.normalize || stripping {
+        log::debug!(
+            "Normalizing the games of {}, stripping {} comments",
+            input_path,
+            options.strip_comments
+        );
+        Counting::new(Box::new(NormalizingReader::stripping(
+            windowed,
+            options.strip_comments,
+        )))
+    } else {
+        Counting::new(Box::new(windowed))
+    };
+    let summary = |input_bytes: u64, compressed_bytes: u64| CompressSummary {
+        original_bytes: if stripping {
+            unstripped_bytes.get()
+        } else {
+            input_bytes
+        },
+        stripped_bytes: stripping.then_some(input_bytes),
+        compressed_bytes,
+    };
+
+    // store the name of the input in the header when there is a single input file
+    let extensions = Extensions {
+        tuning: options.tuning,
+        name: original_name(input_paths),
+        normalized: options.normalize || stripping,
+        metadata: options.metadata.then(Metadata::current),
+        ..Extensions::default()
+    };
+
+    // compress the input game by game into bare length-prefixed frames with no header if writing a framed stream
+    if options.framed {
+        let AlgorithmSelection::Explicit(algorithm) = options.algorithm else {
+            return Err(CgnCliError::BestUnsupported("--framed-stdout"));
+        };
+        log::debug!("Writing {} as a framed stream", input_path);
+        let mut output = Counting::new(open_output(output_path)?);
+        compress_framed(
+            BufReader::new(&mut input),
+            &mut output,
+            algorithm,
+            options.verify,
+        )?;
+        let compressed_bytes = output.count;
+        output.into_inner().finish()?;
+        progress.finish_and_clear();
+        return Ok(summary(input.count, compressed_bytes));
+    }
+
+    // compress the input game by game into length-prefixed records if streaming
+    if options.stream {
+        let AlgorithmSelection::Explicit(algorithm) = options.algorithm else {
+            return Err(CgnCliError::BestUnsupported("--stream"));
+        };
+        log::debug!("Streaming {} one game at a time", input_path);
+
+        // rotate through numbered archives named after the output path if there is a limit on their games
+        if let Some((output_prefix, max_games)) = output_path.zip(options.max_games_per_archive) {
+            let archives = rotate(
+                BufReader::new(&mut input),
+                output_prefix,
+                max_games,
+                algorithm,
+                &extensions,
+                options.verify,
+                options.force,
+            )?;
+            progress.finish_and_clear();
+            let compressed_bytes = archives.iter().map(|archive| archive.bytes).sum();
+            return Ok(summary(input.count, compressed_bytes));
+        }
+        let mut output = Counting::new(open_output(output_path)?);
+        compress_stream(
+            BufReader::new(&mut input),
+            &mut output,
+            algorithm,
+            &extensions,
+            options.verify,
+        )?;
+        let compressed_bytes = output.count;
+        output.into_inner().finish()?;
+        progress.finish_and_clear();
+        return Ok(summary(input.count, compressed_bytes));
+    }
+
+    // refuse to buffer an input larger than the limit, checking the file length up front when it is known
+    let too_large = CgnCliError::InputTooLarge(options.max_input_size);
+    if options.window.is_none() && len.is_some_and(|len| len > options.max_input_size) {
+        return Err(too_large);
+    }
+
+    // compress each game separately behind an index of the games if compressing per game
+    if options.per_game {
+        log::debug!("Compressing each game in {} separately", input_path);
+
+        // read the input, reading at most one byte past the limit to detect oversized inputs
+        let mut pgn_data = Vec::new();
+        profile::time("read", || {
+            input
+                .by_ref()
+                .take(options.max_input_size.saturating_add(1))
+                .read_to_end(&mut pgn_data)
+        })?;
+        if pgn_data.len() as u64 > options.max_input_size {
+            return Err(too_large);
+        }
+        let (_, compressed) = compress_smallest(options.algorithm, |algorithm| {
+            let mut compressed = Vec::new();
+            compress_indexed(
+                &pgn_data[..],
+                &mut compressed,
+                algorithm,
+                &extensions,
+                options.verify,
+            )?;
+            Ok(compressed)
+        })?;
+        let compressed = seal_output(compressed, options.passphrase.as_deref())?;
+        progress.finish_and_clear();
+        profile::time("write", || {
+            let mut output = open_output(output_path)?;
+            output.write_all(&compressed)?;
+            output.finish()
+        })?;
+        return Ok(summary(input.count, compressed.len() as u64));
+    }
+
+    // read the input into a string, reading at most one byte past the limit to detect oversized inputs
+    let mut pgn_str = String::new();
+    profile::time("read", || {
+        input
+            .by_ref()
+            .take(options.max_input_size.saturating_add(1))
+            .read_to_string(&mut pgn_str)
+    })?;
+    progress.finish_and_clear();
+    if pgn_str.len() as u64 > options.max_input_size {
+        return Err(too_large);
+    }
+    log::debug!("Read {} bytes from {}", pgn_str.len(), input_path);
+    let (algorithm, compressed_pgn_data) = compress_smallest(options.algorithm, |algorithm| {
+        // compress with the tuned parameters if given, which only apply to dynamic huffman
+        codec_with_tuning(algorithm, options.tuning)
+            .compress(&pgn_str)
+            .map_err(CgnCliError::CompressionFailed)
+    })?;
+    log::debug!(
+        "Compressed {} bytes to {} bytes of payload",
+        pgn_str.len(),
+        compressed_pgn_data.len()
+    );
+
+    // check the compressed data decompresses back to the input, only when asked as it costs a full decompression
+    if options.verify
+        && !codec_with_tuning(algorithm, options.tuning).round_trips(&pgn_str, &compressed_pgn_data)
+    {
+        return Err(CgnCliError::RoundTripFailed(algorithm));
+    }
+
+    // write the header, checksum, any tuned parameters and name, and compressed PGN data to the output
+    let mut file = Vec::with_capacity(compressed_pgn_data.len() + 24);
+    write_extended_file(&mut file, algorithm, 0, &extensions, &compressed_pgn_data)?;
+    let file = seal_output(file, options.passphrase.as_deref())?;
+    profile::time("write", || {
+        let mut output = open_output(output_path)?;
+        output.write_all(&file)?;
+        output.finish()
+    })?;
+    Ok(summary(input.count, file.len() as u64))
+}
+
+/// Warn that an output path already exists, as writing to it would overwrite it
+pub fn warn_if_exists(output_path: &Path) {
+    if output_path != Path::new(STDIO_PATH) && output_path.exists() {
+        output::warning(format!(
+            "{} already exists and would be overwritten",
+            output_path.display()
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+use super::batch::OutputTemplate;
+use super::error::CgnCliError;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the config file searched for in the current directory and the user's config directory
+const CONFIG_FILE_NAME: &str = "cgn.toml";
+
+/// Default options read from a `cgn.toml` file, used for any option not given on the command line
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub optimization_level: Option<u8>,
+    pub threads: Option<usize>,
+    pub output_template: Option<String>,
+    pub verify: Option<bool>,
+}
+
+impl Config {
+    /// Get the output template, if one is set
+    pub fn output_template(&self) -> Result<Option<OutputTemplate>, CgnCliError> {
+        self.output_template
+            .as_deref()
+            .map(|template| template.parse().map_err(CgnCliError::InvalidConfig))
+            .transpose()
+    }
+}
+
+/// Get the paths searched for a config file, in order: the current directory, then `$XDG_CONFIG_HOME/cgn/`,
+/// falling back to `~/.config/cgn/` if `XDG_CONFIG_HOME` is not set
+fn search_paths() -> Vec<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+    paths.extend(config_home.map(|dir| dir.join("cgn").join(CONFIG_FILE_NAME)));
+    paths
+}
+
+/// Read and validate a config file
+fn read_config(path: &Path) -> Result<Config, CgnCliError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| CgnCliError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+    let config: Config = toml::from_str(&contents)
+        .map_err(|e| CgnCliError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+    if let Some(level) = config.optimization_level.filter(|&level| level > 3) {
+        return Err(CgnCliError::InvalidConfig(format!(
+            "{}: optimization_level must be between 0 and 3, not {}",
+            path.display(),
+            level
+        )));
+    }
+    config.output_template()?;
+    Ok(config)
+}
+
+/// Load the config file at the given path, or the first one found in the search paths.
+/// Without a config file, every option keeps its built-in default.
+pub fn load_config(path: Option<&Path>) -> Result<Config, CgnCliError> {
+    if let Some(path) = path {
+        return read_config(path);
+    }
+    match search_paths().into_iter().find(|path| path.is_file()) {
+        Some(path) => {
+            log::info!("Using config file {}", path.display());
+            read_config(&path)
+        }
+        None => Ok(Config::default()),
+    }
+}
@@ -0,0 +1,157 @@
+use super::error::CgnCliError;
+use super::header::{
+    read_header, write_extended_file, Encryption, Extensions, Header, FLAG_CHECKSUM,
+    FLAG_ENCRYPTED, FLAG_INDEXED, FLAG_NORMALIZED, FLAG_STREAM,
+};
+use std::env;
+
+/// The environment variable the passphrase is read from instead of prompting for it
+const PASSPHRASE_VAR: &str = "CGN_PASSPHRASE";
+
+/// Check if files can be encrypted and decrypted in this build
+pub fn is_available() -> bool {
+    cfg!(feature = "encryption")
+}
+
+/// Prompt for a passphrase on the terminal without echoing it
+#[cfg(feature = "encryption")]
+fn prompt(prompt: &str) -> Result<String, CgnCliError> {
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn prompt(_prompt: &str) -> Result<String, CgnCliError> {
+    Err(CgnCliError::EncryptionUnavailable)
+}
+
+/// Get the passphrase from `CGN_PASSPHRASE`, or prompt for it if it is not set.
+/// When encrypting, a prompted passphrase is asked for twice to guard against typos.
+pub fn read_passphrase(confirm: bool) -> Result<String, CgnCliError> {
+    if !is_available() {
+        return Err(CgnCliError::EncryptionUnavailable);
+    }
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        if passphrase.is_empty() {
+            return Err(CgnCliError::InvalidPassphrase("the passphrase is empty"));
+        }
+        return Ok(passphrase);
+    }
+    let passphrase = prompt("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(CgnCliError::InvalidPassphrase("the passphrase is empty"));
+    }
+    if confirm && prompt("Confirm passphrase: ")? != passphrase {
+        return Err(CgnCliError::InvalidPassphrase(
+            "the passphrases do not match",
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Derive a 256-bit key from the passphrase and salt with Argon2
+#[cfg(feature = "encryption")]
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<aes_gcm::Key<aes_gcm::Aes256Gcm>, CgnCliError> {
+    let mut key = [0; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CgnCliError::EncryptionFailed(e.to_string()))?;
+    Ok(key.into())
+}
+
+/// Encrypt a payload with AES-256-GCM under a key derived from the passphrase and a random salt,
+/// returning the salt and nonce used along with the ciphertext
+#[cfg(feature = "encryption")]
+fn seal(passphrase: &str, plaintext: &[u8]) -> Result<(Encryption, Vec<u8>), CgnCliError> {
+    use aes_gcm::aead::{Aead, KeyInit};
+    use rand::RngCore;
+
+    let mut encryption = Encryption {
+        salt: [0; super::header::SALT_LEN],
+        nonce: [0; super::header::NONCE_LEN],
+    };
+    rand::rngs::OsRng.fill_bytes(&mut encryption.salt);
+    rand::rngs::OsRng.fill_bytes(&mut encryption.nonce);
+    let cipher = aes_gcm::Aes256Gcm::new(&derive_key(passphrase, &encryption.salt)?);
+    let ciphertext = cipher
+        .encrypt(&encryption.nonce.into(), plaintext)
+        .map_err(|e| CgnCliError::EncryptionFailed(e.to_string()))?;
+    Ok((encryption, ciphertext))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn seal(_passphrase: &str, _plaintext: &[u8]) -> Result<(Encryption, Vec<u8>), CgnCliError> {
+    Err(CgnCliError::EncryptionUnavailable)
+}
+
+/// Decrypt a payload, failing authentication if the passphrase is wrong or the ciphertext was modified
+#[cfg(feature = "encryption")]
+fn open(
+    passphrase: &str,
+    encryption: &Encryption,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CgnCliError> {
+    use aes_gcm::aead::{Aead, KeyInit};
+
+    let cipher = aes_gcm::Aes256Gcm::new(&derive_key(passphrase, &encryption.salt)?);
+    cipher
+        .decrypt(&encryption.nonce.into(), ciphertext)
+        .map_err(|_| CgnCliError::AuthenticationFailed)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open(
+    _passphrase: &str,
+    _encryption: &Encryption,
+    _ciphertext: &[u8],
+) -> Result<Vec<u8>, CgnCliError> {
+    Err(CgnCliError::EncryptionUnavailable)
+}
+
+/// Encrypt the payload of a complete CGN file, keeping its layout, tuned parameters, modification time, and name.
+/// The checksum of the encrypted file is of the ciphertext, so corruption is detected before decrypting.
+pub fn encrypt_file(file: &[u8], passphrase: &str) -> Result<Vec<u8>, CgnCliError> {
+    let (header, payload) = read_header(file)?;
+    let (encryption, ciphertext) = seal(passphrase, payload)?;
+    let normalized = header.has_flag(FLAG_NORMALIZED);
+    let extensions = Extensions {
+        tuning: header.tuning,
+        mtime: header.mtime,
+        name: header.name,
+        encryption: Some(encryption),
+        normalized,
+        metadata: header.metadata,
+    };
+    let flags = header.flags & (FLAG_STREAM | FLAG_INDEXED);
+    let mut output = Vec::with_capacity(file.len() + 48);
+    write_extended_file(
+        &mut output,
+        header.algorithm,
+        flags,
+        &extensions,
+        &ciphertext,
+    )?;
+    Ok(output)
+}
+
+/// Decrypt the payload of an encrypted file, returning the header of the file as it was before it was encrypted
+pub fn decrypt_payload(
+    header: &Header,
+    payload: &[u8],
+    passphrase: &str,
+) -> Result<(Header, Vec<u8>), CgnCliError> {
+    let Some(encryption) = &header.encryption else {
+        return Ok((header.clone(), payload.to_vec()));
+    };
+    header.verify_checksum(payload)?;
+    let plaintext = open(passphrase, encryption, payload)?;
+    let header = Header {
+        flags: header.flags & !(FLAG_CHECKSUM | FLAG_ENCRYPTED),
+        checksum: None,
+        encryption: None,
+        ..header.clone()
+    };
+    Ok((header, plaintext))
+}
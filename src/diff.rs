@@ -0,0 +1,128 @@
+use super::archive::decompress;
+use super::error::CgnCliError;
+use super::filter::header_value;
+use super::header::read_header;
+use super::io_util::open_reader;
+use super::output;
+use super::pgn_db::PgnDbIter;
+use cgn::pgn_data::PgnData;
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use std::str::FromStr;
+
+/// The tags identifying a game, so the same game can be found in both archives
+const IDENTITY_TAGS: [&str; 6] = ["Event", "Site", "Date", "Round", "White", "Black"];
+
+/// Hash bytes with 64-bit FNV-1a, which unlike the standard library's hasher is the same across builds
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Hash the moves and result of a game, so comments, formatting, and tags other than the result are ignored.
+/// A game that cannot be parsed is hashed from its text with the whitespace collapsed.
+fn game_hash(pgn_str: &str) -> u64 {
+    let normalized = match PgnData::from_str(pgn_str) {
+        Ok(pgn_data) => {
+            let mut moves = pgn_data
+                .moves
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            moves.push(pgn_data.headers.result);
+            moves.join(" ")
+        }
+        Err(_) => pgn_str.split_whitespace().collect::<Vec<_>>().join(" "),
+    };
+    fnv1a(normalized.as_bytes())
+}
+
+/// Get the key identifying a game from its tags
+fn game_key(pgn_str: &str) -> String {
+    IDENTITY_TAGS
+        .map(|tag| header_value(pgn_str, tag).unwrap_or_default())
+        .join("\n")
+}
+
+/// The games found in only one of two archives, or in both with different moves or results
+#[derive(Debug, Default)]
+pub struct GameDiff {
+    pub only_in_a: Vec<usize>,
+    pub only_in_b: Vec<usize>,
+    /// The index in each archive of the games with different moves or results
+    pub differing: Vec<(usize, usize)>,
+    pub identical: usize,
+}
+
+/// Compare the games of two archives, matching games by their identifying tags and comparing the hash of their moves
+/// and result. Games with the same tags are matched in the order they appear in each archive.
+pub fn diff_games(games_a: &[String], games_b: &[String]) -> GameDiff {
+    let mut unmatched_b = HashMap::<String, Vec<(usize, u64)>>::new();
+    for (i, pgn_str) in games_b.iter().enumerate().rev() {
+        unmatched_b
+            .entry(game_key(pgn_str))
+            .or_default()
+            .push((i, game_hash(pgn_str)));
+    }
+
+    let mut diff = GameDiff::default();
+    for (i, pgn_str) in games_a.iter().enumerate() {
+        match unmatched_b.get_mut(&game_key(pgn_str)).and_then(Vec::pop) {
+            Some((_, hash)) if hash == game_hash(pgn_str) => diff.identical += 1,
+            Some((j, _)) => diff.differing.push((i, j)),
+            None => diff.only_in_a.push(i),
+        }
+    }
+    diff.only_in_b = unmatched_b
+        .into_values()
+        .flatten()
+        .map(|(j, _)| j)
+        .collect();
+    diff.only_in_b.sort_unstable();
+    diff
+}
+
+/// Decompress every game in a CGN file
+fn read_games(input_path: &str) -> Result<Vec<String>, CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let (header, payload) = read_header(&compressed_data)?;
+    let pgn_data = decompress(&header, payload)?;
+    Ok(PgnDbIter::new(BufReader::new(pgn_data.as_bytes())).collect())
+}
+
+/// Join game indices into a list for printing
+fn index_list(indices: impl Iterator<Item = String>) -> String {
+    indices.collect::<Vec<_>>().join(", ")
+}
+
+/// Compare the games of two CGN files and print how many are only in one of them, differ, or are identical.
+/// If `verbose` is set, the indices of the games only in one file or differing are listed too.
+pub fn diff(path_a: &str, path_b: &str, verbose: bool) -> Result<(), CgnCliError> {
+    let diff = diff_games(&read_games(path_a)?, &read_games(path_b)?);
+    output::report(|out| {
+        writeln!(out, "Only in {}: {}", path_a, diff.only_in_a.len())?;
+        writeln!(out, "Only in {}: {}", path_b, diff.only_in_b.len())?;
+        writeln!(out, "Differing: {}", diff.differing.len())?;
+        writeln!(out, "Identical: {}", diff.identical)?;
+        if verbose {
+            if !diff.only_in_a.is_empty() {
+                let indices = diff.only_in_a.iter().map(ToString::to_string);
+                writeln!(out, "Games only in {}: {}", path_a, index_list(indices))?;
+            }
+            if !diff.only_in_b.is_empty() {
+                let indices = diff.only_in_b.iter().map(ToString::to_string);
+                writeln!(out, "Games only in {}: {}", path_b, index_list(indices))?;
+            }
+            if !diff.differing.is_empty() {
+                let pairs = diff
+                    .differing
+                    .iter()
+                    .map(|(i, j)| format!("{} -> {}", i, j));
+                writeln!(out, "Differing games: {}", index_list(pairs))?;
+            }
+        }
+        Ok(())
+    })
+}
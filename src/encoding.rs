@@ -0,0 +1,147 @@
+use clap::ValueEnum;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+use std::str;
+
+/// The byte order mark some tools write at the start of UTF-8 files
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The number of bytes read from the input at a time
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// The text encoding of a PGN input
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// UTF-8, failing on any invalid byte
+    #[default]
+    #[value(name = "utf-8")]
+    Utf8,
+    /// Latin-1, read as its Windows-1252 superset
+    Latin1,
+    /// UTF-8, falling back to Latin-1 from the first byte that is not valid UTF-8
+    Auto,
+}
+
+impl Display for Encoding {
+    /// Display the name of the encoding as it is given on the command line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf-8"),
+            Encoding::Latin1 => write!(f, "latin1"),
+            Encoding::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// Decode Latin-1 bytes to UTF-8. Every byte is a whole character, so chunks can be decoded independently.
+fn decode_latin1(bytes: &[u8]) -> Vec<u8> {
+    let (decoded, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes);
+    decoded.into_owned().into_bytes()
+}
+
+/// A reader transcoding an input in some encoding to UTF-8 as it is read, dropping a UTF-8 byte order mark
+/// at the start of UTF-8 and auto-detected inputs
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: Encoding,
+    /// Whether the start of the input has been checked for a byte order mark
+    started: bool,
+    /// Input bytes not yet decoded, such as a UTF-8 character split between two reads
+    pending: Vec<u8>,
+    /// The number of input bytes decoded so far, to locate invalid bytes
+    offset: usize,
+    decoded: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    /// Create a reader decoding an input in the encoding
+    pub fn new(inner: R, encoding: Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            started: false,
+            pending: Vec::new(),
+            offset: 0,
+            decoded: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    /// Read and decode the next chunk of the input, which may decode nothing if more must be read first
+    fn decode_chunk(&mut self) -> io::Result<()> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        let start = bytes.len();
+        bytes.resize(start + CHUNK_LEN, 0);
+        let n = self.inner.read(&mut bytes[start..])?;
+        bytes.truncate(start + n);
+        self.finished = n == 0;
+
+        // drop a byte order mark, waiting for enough of the input to tell if it starts with one
+        if !self.started && self.encoding != Encoding::Latin1 {
+            if !self.finished && bytes.len() < UTF8_BOM.len() && UTF8_BOM.starts_with(&bytes) {
+                self.pending = bytes;
+                return Ok(());
+            }
+            if bytes.starts_with(UTF8_BOM) {
+                bytes.drain(..UTF8_BOM.len());
+                self.offset += UTF8_BOM.len();
+            }
+        }
+        self.started = true;
+        let available = bytes.len();
+
+        let decoded = match self.encoding {
+            Encoding::Latin1 => decode_latin1(&bytes),
+            Encoding::Utf8 | Encoding::Auto => match str::from_utf8(&bytes) {
+                Ok(_) => bytes,
+                // keep a character split between reads until the rest of it is read
+                Err(e) if e.error_len().is_none() && !self.finished => {
+                    self.pending = bytes.split_off(e.valid_up_to());
+                    bytes
+                }
+                Err(e) if self.encoding == Encoding::Auto => {
+                    log::info!(
+                        "Input is not valid UTF-8 at byte {}, reading the rest of it as Latin-1",
+                        self.offset + e.valid_up_to()
+                    );
+                    self.encoding = Encoding::Latin1;
+                    let rest = decode_latin1(&bytes[e.valid_up_to()..]);
+                    bytes.truncate(e.valid_up_to());
+                    bytes.extend(rest);
+                    bytes
+                }
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Input is not valid UTF-8 at byte {}. Use --encoding latin1 or --encoding auto for other encodings",
+                            self.offset + e.valid_up_to()
+                        ),
+                    ))
+                }
+            },
+        };
+        self.offset += available - self.pending.len();
+        self.decoded = decoded;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() {
+            if self.finished && self.pending.is_empty() {
+                return Ok(0);
+            }
+            self.decode_chunk()?;
+        }
+        let n = buf.len().min(self.decoded.len() - self.position);
+        buf[..n].copy_from_slice(&self.decoded[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
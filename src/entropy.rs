@@ -0,0 +1,116 @@
+use super::algorithm::Algorithm;
+use super::codec::codec_for;
+use super::error::CgnCliError;
+use super::io_util::open_reader;
+use super::output;
+use super::pgn_db::PgnDbIter;
+use cgn::pgn_data::PgnData;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::str::FromStr;
+
+/// Get the Shannon entropy, in bits per symbol, of symbols drawn independently with the given frequencies
+pub fn shannon_entropy(frequencies: impl IntoIterator<Item = usize>) -> f64 {
+    let frequencies = frequencies.into_iter().collect::<Vec<_>>();
+    let total = frequencies.iter().sum::<usize>() as f64;
+    frequencies
+        .into_iter()
+        .filter(|&count| count > 0)
+        .map(|count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Parse every game in a PGN file and print the entropy of its moves, as the bits per move needed by a code that
+/// only knows how often each move is played, along with the bits per move each algorithm achieves on the moves of
+/// the same games. Headers are left out of the compressed sizes, as the entropy only covers the moves. Algorithms
+/// that model the position can beat this entropy, so it is a reference point rather than a strict limit.
+pub fn entropy(input_path: &str) -> Result<(), CgnCliError> {
+    let reader = BufReader::new(open_reader(input_path)?);
+
+    // parse the games, counting how often each move is played
+    let mut games = Vec::new();
+    let mut frequencies = HashMap::<String, usize>::new();
+    let mut failed = 0;
+    for (i, pgn_str) in PgnDbIter::new(reader).enumerate() {
+        match PgnData::from_str(&pgn_str) {
+            Ok(mut pgn_data) => {
+                for san_plus in &pgn_data.moves {
+                    *frequencies.entry(san_plus.to_string()).or_default() += 1;
+                }
+                pgn_data.clear_headers();
+                games.push(pgn_data);
+            }
+            Err(e) => {
+                log::warn!("Skipping game {}: {}", i, e);
+                failed += 1;
+            }
+        }
+    }
+
+    let moves = frequencies.values().sum::<usize>();
+    let bits_per_move = shannon_entropy(frequencies.values().copied());
+    output::report(|out| {
+        writeln!(
+            out,
+            "Parsed {} game(s) with {} move(s), {} of them distinct, from {}",
+            games.len(),
+            moves,
+            frequencies.len(),
+            input_path
+        )?;
+        if failed > 0 {
+            writeln!(out, "Skipped {} game(s) that failed to parse", failed)?;
+        }
+        writeln!(out, "Entropy: {:.2} bits per move", bits_per_move)?;
+        writeln!(
+            out,
+            "Minimum size of the moves: {} bytes",
+            (bits_per_move * moves as f64 / 8.0).ceil() as u64
+        )?;
+        writeln!(out)?;
+
+        // compress the moves of the games with each algorithm to compare against the entropy
+        writeln!(
+            out,
+            "{:<20} {:>12} {:>14} {:>12}",
+            "Algorithm", "Bytes", "Bits per move", "vs entropy"
+        )?;
+        for &algorithm in Algorithm::value_variants() {
+            let codec = codec_for(algorithm);
+            let mut bits = 0;
+            let mut compressed_moves = 0;
+            for pgn_data in &games {
+                match codec.compress_pgn_data(pgn_data) {
+                    Ok(compressed) => {
+                        bits += compressed.len();
+                        compressed_moves += pgn_data.moves.len();
+                    }
+                    Err(e) => log::warn!("{} failed to compress a game: {}", algorithm, e),
+                }
+            }
+            let algorithm_bits_per_move = if compressed_moves == 0 {
+                0.0
+            } else {
+                bits as f64 / compressed_moves as f64
+            };
+            let vs_entropy = if bits_per_move == 0.0 {
+                String::from("-")
+            } else {
+                format!("{:.2}x", algorithm_bits_per_move / bits_per_move)
+            };
+            writeln!(
+                out,
+                "{:<20} {:>12} {:>14.2} {:>12}",
+                algorithm.to_string(),
+                bits.div_ceil(8),
+                algorithm_bits_per_move,
+                vs_entropy
+            )?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,510 @@
+use super::algorithm::Algorithm;
+use super::codec::CodecError;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+/// The exit codes returned by the command line interface when a command fails. A successful command exits with 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Generic = 1,
+    InvalidArguments = 2,
+    Io = 3,
+    CodecFailure = 4,
+    CorruptFile = 5,
+    /// Following the shell convention of 128 plus the number of SIGINT
+    Interrupted = 130,
+}
+
+/// The errors that can be returned by the command line interface
+#[derive(Debug)]
+pub enum CgnCliError {
+    /// An error occurred while reading from or writing to a file
+    Io(std::io::Error),
+    /// No compression algorithm exists for the requested optimization level
+    InvalidAlgorithm(u8),
+    /// The input is larger than the maximum size that will be read into memory
+    InputTooLarge(u64),
+    /// The input could not be compressed by the selected algorithm
+    CompressionFailed(CodecError),
+    /// The input could not be decompressed by the selected algorithm
+    DecompressionFailed(CodecError),
+    /// A game in a multi-game database could not be compressed
+    GameCompressionFailed(usize, CodecError),
+    /// A game in a streamed file could not be decompressed
+    GameDecompressionFailed(usize, CodecError),
+    /// A decompressed game is not valid PGN
+    InvalidGame(usize),
+    /// A game was requested by an index past the end of the file
+    GameOutOfRange { index: usize, count: usize },
+    /// A record in a streamed file is cut short
+    TruncatedRecord,
+    /// A record of a streamed or indexed payload does not match its checksum
+    RecordChecksumMismatch(usize),
+    /// A scan of the records of a file found some of them corrupt
+    CorruptRecords { corrupt: usize, total: usize },
+    /// Games can only be salvaged from a file with one record per game
+    NotSalvageable(String),
+    /// A record or index is too large for the 32-bit length fields of the file format
+    RecordTooLarge(usize),
+    /// The compressed data did not decompress back to the input
+    RoundTripFailed(Algorithm),
+    /// The glob pattern given as an input path is malformed
+    InvalidGlob(String),
+    /// The glob pattern given as an input path matched no files
+    NoMatches(String),
+    /// Several inputs were given, but the output path is not a directory
+    OutputNotDirectory(PathBuf),
+    /// The output path already exists and overwriting was not forced
+    OutputExists(PathBuf),
+    /// A file in a batch operation failed, stopping the operation
+    FileFailed {
+        path: PathBuf,
+        source: Box<CgnCliError>,
+    },
+    /// Some of the files in a batch operation failed
+    BatchFailed(usize),
+    /// A file would be transcoded to the algorithm it was already compressed with
+    SameAlgorithm(Algorithm),
+    /// A genetic algorithm checkpoint could not be read or written
+    InvalidCheckpoint(String),
+    /// A genetic algorithm checkpoint was written by a run with a different config
+    CheckpointMismatch(String),
+    /// The input does not start with a CGN header
+    NotCgnFile,
+    /// The payload does not match the checksum in the header
+    ChecksumMismatch,
+    /// The header has no checksum to verify the payload against
+    NoChecksum,
+    /// Memory usage was requested but the binary was built without the `measure-memory` feature
+    MemoryMeasurementUnavailable,
+    /// A folded stack profile was requested but the binary was built without the `profiling` feature
+    ProfilingUnavailable,
+    /// The input has no CGN header and no algorithm was given to decompress it with
+    LegacyFile,
+    /// A framed stream was given to decompress without the algorithm it was compressed with
+    FramedAlgorithmRequired,
+    /// The decompressed file differs from the original, with the context around the first difference
+    VerificationFailed {
+        offset: usize,
+        expected: String,
+        found: String,
+    },
+    /// The input was written with a version of the file format this tool cannot read
+    UnsupportedVersion(u8),
+    /// The header uses extended flags this tool does not understand
+    UnsupportedExtendedFlags(u8),
+    /// The algorithm requested by the user differs from the one recorded in the header
+    AlgorithmMismatch {
+        requested: Algorithm,
+        found: Algorithm,
+    },
+    /// `--algorithm best` was combined with an option that cannot buffer the input to try every algorithm
+    BestUnsupported(&'static str),
+    /// Tuned dynamic huffman parameters were given for an algorithm or input they cannot be used with
+    TuningUnsupported(String),
+    /// Only one path was given to compress, so there is no output path
+    MissingOutputPath,
+    /// Games can only be appended to a file with one record per game
+    NotAppendable,
+    /// Listing the games of a file without one record per game needs the whole payload to be decompressed
+    ListRequiresFull,
+    /// The config file could not be parsed or holds an invalid option
+    InvalidConfig(String),
+    /// An option that only applies to batch compression was given for a single input
+    BatchOnly(&'static str),
+    /// An option that only applies to a single input was given for a batch of files
+    SingleOnly(&'static str),
+    /// The output template names every file of a batch the same, as it has no {stem} placeholder
+    TemplateWithoutStem,
+    /// Two inputs of a batch would be compressed to the same output path
+    DuplicateOutput {
+        output: PathBuf,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    /// The output path is a directory but the file stores no original name to write to inside it
+    NoStoredName(PathBuf),
+    /// The output of the self-test differed from the golden files for this many games and algorithms
+    SelftestFailed(usize),
+    /// A baseline to compare a benchmark against could not be read
+    InvalidBaseline(String),
+    /// Metrics of a benchmark got worse than the baseline by more than their tolerance
+    BenchmarkRegressed {
+        algorithms: usize,
+        tolerance: f64,
+        time_tolerance: f64,
+    },
+    /// An algorithm failed on a game while benchmarking in strict mode
+    BenchmarkFailed {
+        algorithm: Algorithm,
+        game: usize,
+        reason: String,
+    },
+    /// An algorithm decompressed a game to different text than it was compressed from in a strict round-trip
+    LossyRoundTrip {
+        algorithm: Algorithm,
+        game: usize,
+        expected: String,
+        decompressed: String,
+    },
+    /// Ctrl-C stopped a batch after this many files were finished
+    Interrupted(usize),
+    /// The payload is encrypted and `--decrypt` was not given
+    Encrypted,
+    /// Encryption was requested but the binary was built without the `encryption` feature
+    EncryptionUnavailable,
+    /// The payload could not be encrypted
+    EncryptionFailed(String),
+    /// The passphrase is wrong or the encrypted payload was modified
+    AuthenticationFailed,
+    /// The passphrase given to encrypt with is unusable
+    InvalidPassphrase(&'static str),
+    /// An option that reads the input more than once was given stdin as the input
+    StdinUnsupported(&'static str),
+    /// An option that writes several files was given stdout as the output
+    StdoutUnsupported(&'static str),
+    /// A file to merge holds a single compressed payload rather than a record per game
+    NotMergeable(String),
+    /// A file to merge was compressed with a different algorithm from the first file
+    MergeMismatch {
+        path: String,
+        algorithm: Algorithm,
+        first_path: String,
+        expected: Algorithm,
+    },
+}
+
+impl CgnCliError {
+    /// Get the exit code the process should return for the error
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            CgnCliError::Io(_) => ExitCode::Io,
+            CgnCliError::FileFailed { source, .. } => source.exit_code(),
+            CgnCliError::Interrupted(_) => ExitCode::Interrupted,
+            CgnCliError::InvalidAlgorithm(_)
+            | CgnCliError::InvalidGlob(_)
+            | CgnCliError::NoMatches(_)
+            | CgnCliError::OutputNotDirectory(_)
+            | CgnCliError::SameAlgorithm(_)
+            | CgnCliError::CheckpointMismatch(_)
+            | CgnCliError::GameOutOfRange { .. }
+            | CgnCliError::AlgorithmMismatch { .. }
+            | CgnCliError::MemoryMeasurementUnavailable
+            | CgnCliError::ProfilingUnavailable
+            | CgnCliError::BestUnsupported(_)
+            | CgnCliError::TuningUnsupported(_)
+            | CgnCliError::MissingOutputPath
+            | CgnCliError::NotAppendable
+            | CgnCliError::ListRequiresFull
+            | CgnCliError::InvalidConfig(_)
+            | CgnCliError::BatchOnly(_)
+            | CgnCliError::SingleOnly(_)
+            | CgnCliError::TemplateWithoutStem
+            | CgnCliError::DuplicateOutput { .. }
+            | CgnCliError::NoStoredName(_)
+            | CgnCliError::Encrypted
+            | CgnCliError::EncryptionUnavailable
+            | CgnCliError::InvalidPassphrase(_)
+            | CgnCliError::StdinUnsupported(_)
+            | CgnCliError::StdoutUnsupported(_)
+            | CgnCliError::FramedAlgorithmRequired
+            | CgnCliError::NotMergeable(_)
+            | CgnCliError::MergeMismatch { .. }
+            | CgnCliError::NotSalvageable(_) => ExitCode::InvalidArguments,
+            CgnCliError::CompressionFailed(_)
+            | CgnCliError::DecompressionFailed(_)
+            | CgnCliError::GameCompressionFailed(..)
+            | CgnCliError::GameDecompressionFailed(..)
+            | CgnCliError::InvalidGame(_)
+            | CgnCliError::RoundTripFailed(_)
+            | CgnCliError::VerificationFailed { .. }
+            | CgnCliError::SelftestFailed(_)
+            | CgnCliError::BenchmarkFailed { .. }
+            | CgnCliError::LossyRoundTrip { .. } => ExitCode::CodecFailure,
+            CgnCliError::TruncatedRecord
+            | CgnCliError::RecordChecksumMismatch(_)
+            | CgnCliError::CorruptRecords { .. }
+            | CgnCliError::ChecksumMismatch
+            | CgnCliError::InvalidCheckpoint(_)
+            | CgnCliError::InvalidBaseline(_)
+            | CgnCliError::NotCgnFile
+            | CgnCliError::LegacyFile
+            | CgnCliError::UnsupportedVersion(_)
+            | CgnCliError::UnsupportedExtendedFlags(_)
+            | CgnCliError::AuthenticationFailed => ExitCode::CorruptFile,
+            CgnCliError::InputTooLarge(_)
+            | CgnCliError::OutputExists(_)
+            | CgnCliError::BatchFailed(_)
+            | CgnCliError::BenchmarkRegressed { .. }
+            | CgnCliError::RecordTooLarge(_)
+            | CgnCliError::NoChecksum
+            | CgnCliError::EncryptionFailed(_) => ExitCode::Generic,
+        }
+    }
+}
+
+impl Display for CgnCliError {
+    /// Display the error message
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CgnCliError::Io(e) => write!(f, "{}", e),
+            CgnCliError::InvalidAlgorithm(level) => {
+                write!(f, "No algorithm exists for optimization level {}", level)
+            }
+            CgnCliError::InputTooLarge(limit) => write!(
+                f,
+                "Input is larger than the maximum input size of {} bytes. Use --stream to compress it one game at a time, or raise --max-input-size",
+                limit
+            ),
+            CgnCliError::CompressionFailed(e) => write!(f, "Compression failed: {}", e),
+            CgnCliError::DecompressionFailed(e) => write!(f, "Decompression failed: {}", e),
+            CgnCliError::GameCompressionFailed(index, e) => {
+                write!(f, "Compression failed for game {}: {}", index, e)
+            }
+            CgnCliError::GameDecompressionFailed(index, e) => {
+                write!(f, "Decompression failed for game {}: {}", index, e)
+            }
+            CgnCliError::InvalidGame(index) => {
+                write!(f, "Decompressed game {} is not valid PGN", index)
+            }
+            CgnCliError::GameOutOfRange { index, count } => write!(
+                f,
+                "Game {} is out of range, as the file holds {} game(s)",
+                index, count
+            ),
+            CgnCliError::TruncatedRecord => write!(f, "Input ends part way through a record"),
+            CgnCliError::RecordChecksumMismatch(index) => {
+                write!(f, "Checksum mismatch in game {} - record corrupt", index)
+            }
+            CgnCliError::CorruptRecords { corrupt, total } => {
+                write!(f, "{} of {} game(s) are corrupt", corrupt, total)
+            }
+            CgnCliError::NotSalvageable(path) => write!(
+                f,
+                "{} holds a single compressed payload rather than a record per game, so no games can be salvaged from it",
+                path
+            ),
+            CgnCliError::RecordTooLarge(len) => write!(
+                f,
+                "{} bytes is too large to record in a CGN file, which uses 32-bit lengths",
+                len
+            ),
+            CgnCliError::RoundTripFailed(algorithm) => write!(
+                f,
+                "Round trip verification failed for {}, so the output was not written",
+                algorithm
+            ),
+            CgnCliError::InvalidGlob(e) => write!(f, "Invalid glob pattern: {}", e),
+            CgnCliError::NoMatches(pattern) => write!(f, "No files match {}", pattern),
+            CgnCliError::OutputNotDirectory(path) => write!(
+                f,
+                "Output path {} must be an existing directory when compressing several files",
+                path.display()
+            ),
+            CgnCliError::OutputExists(path) => write!(
+                f,
+                "{} already exists. Use --force to overwrite it",
+                path.display()
+            ),
+            CgnCliError::FileFailed { path, source } => {
+                write!(f, "Failed to compress {}: {}", path.display(), source)
+            }
+            CgnCliError::BatchFailed(failures) => write!(f, "{} file(s) failed", failures),
+            CgnCliError::SameAlgorithm(algorithm) => write!(
+                f,
+                "File is already compressed with {}. Use --force to transcode it anyway",
+                algorithm
+            ),
+            CgnCliError::InvalidCheckpoint(e) => write!(f, "Invalid checkpoint: {}", e),
+            CgnCliError::InvalidBaseline(e) => write!(f, "Invalid baseline: {}", e),
+            CgnCliError::BenchmarkRegressed {
+                algorithms,
+                tolerance,
+                time_tolerance,
+            } => write!(
+                f,
+                "{} algorithm(s) regressed from the baseline by more than {}% in compression ratio or {}% in timings",
+                algorithms, tolerance, time_tolerance
+            ),
+            CgnCliError::CheckpointMismatch(e) => {
+                write!(f, "Checkpoint does not match the config: {}", e)
+            }
+            CgnCliError::NotCgnFile => write!(f, "Input is not a CGN file"),
+            CgnCliError::ChecksumMismatch => write!(f, "Checksum mismatch - file corrupt"),
+            CgnCliError::NoChecksum => write!(f, "File has no checksum to verify"),
+            CgnCliError::MemoryMeasurementUnavailable => write!(
+                f,
+                "Memory usage can only be measured when built with the measure-memory feature"
+            ),
+            CgnCliError::ProfilingUnavailable => write!(
+                f,
+                "Folded stack profiles can only be written when built with the profiling feature"
+            ),
+            CgnCliError::LegacyFile => write!(
+                f,
+                "Input has no CGN header, so it either is not a CGN file or predates file headers. Use -o or --algorithm to decompress a legacy file"
+            ),
+            CgnCliError::FramedAlgorithmRequired => write!(
+                f,
+                "A framed stream does not record its algorithm. Use -o or --algorithm to give the algorithm it was compressed with"
+            ),
+            CgnCliError::VerificationFailed {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Decompressed output differs from the original at byte {}: expected \"{}\", found \"{}\"",
+                offset, expected, found
+            ),
+            CgnCliError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported CGN format version {}", version)
+            }
+            CgnCliError::UnsupportedExtendedFlags(flags) => {
+                write!(f, "Unsupported CGN extended header flags {:#010b}", flags)
+            }
+            CgnCliError::AlgorithmMismatch { requested, found } => write!(
+                f,
+                "File was compressed with {} but {} was requested",
+                found, requested
+            ),
+            CgnCliError::BestUnsupported(option) => {
+                write!(f, "--algorithm best cannot be used with {}", option)
+            }
+            CgnCliError::TuningUnsupported(e) => write!(
+                f,
+                "--height and --dev only apply to a single input compressed with dynamic-huffman, not {}",
+                e
+            ),
+            CgnCliError::MissingOutputPath => write!(
+                f,
+                "No output path was given. Give an input and an output path, or use --stdout-size"
+            ),
+            CgnCliError::NotAppendable => write!(
+                f,
+                "Games can only be appended to a file compressed with --stream or --per-game"
+            ),
+            CgnCliError::ListRequiresFull => write!(
+                f,
+                "File was not compressed with --stream or --per-game, so listing its games requires decompressing all of it. Use --full to do so"
+            ),
+            CgnCliError::InvalidConfig(e) => write!(f, "Invalid config file: {}", e),
+            CgnCliError::BatchOnly(option) => write!(
+                f,
+                "{} only applies when compressing a glob pattern or a directory",
+                option
+            ),
+            CgnCliError::SingleOnly(option) => write!(
+                f,
+                "{} only applies when compressing a single input, not a batch of files",
+                option
+            ),
+            CgnCliError::TemplateWithoutStem => write!(
+                f,
+                "Output template must contain {{stem}} when compressing several files, or every file is written to the same path"
+            ),
+            CgnCliError::DuplicateOutput {
+                output,
+                first,
+                second,
+            } => write!(
+                f,
+                "{} and {} would both be compressed to {}",
+                first.display(),
+                second.display(),
+                output.display()
+            ),
+            CgnCliError::NoStoredName(dir) => write!(
+                f,
+                "File stores no original name to restore inside {}. Give an explicit output path",
+                dir.display()
+            ),
+            CgnCliError::SelftestFailed(mismatches) => write!(
+                f,
+                "Self-test failed: {} output(s) differ from the golden files",
+                mismatches
+            ),
+            CgnCliError::BenchmarkFailed {
+                algorithm,
+                game,
+                reason,
+            } => write!(
+                f,
+                "{} failed on game {} of the benchmark: {}",
+                algorithm, game, reason
+            ),
+            CgnCliError::LossyRoundTrip {
+                algorithm,
+                game,
+                expected,
+                decompressed,
+            } => write!(
+                f,
+                "{} did not round-trip game {} of the benchmark losslessly.\nExpected:\n{}\nDecompressed:\n{}",
+                algorithm,
+                game,
+                expected.trim_end(),
+                decompressed.trim_end()
+            ),
+            CgnCliError::Interrupted(files) => write!(f, "Interrupted after {} files", files),
+            CgnCliError::Encrypted => write!(f, "File is encrypted. Decompress it with --decrypt"),
+            CgnCliError::EncryptionUnavailable => write!(
+                f,
+                "Encryption is only available when built with the encryption feature"
+            ),
+            CgnCliError::EncryptionFailed(reason) => write!(f, "Failed to encrypt: {}", reason),
+            CgnCliError::AuthenticationFailed => write!(
+                f,
+                "Authentication failed: wrong passphrase or corrupted file"
+            ),
+            CgnCliError::InvalidPassphrase(reason) => write!(f, "Invalid passphrase: {}", reason),
+            CgnCliError::StdinUnsupported(option) => write!(
+                f,
+                "{} reads the input twice, so it cannot read from stdin",
+                option
+            ),
+            CgnCliError::StdoutUnsupported(option) => write!(
+                f,
+                "{} writes several files, so it cannot write to stdout",
+                option
+            ),
+            CgnCliError::NotMergeable(path) => write!(
+                f,
+                "{} was not compressed with --stream or --per-game, so its games cannot be merged without re-compressing them",
+                path
+            ),
+            CgnCliError::MergeMismatch {
+                path,
+                algorithm,
+                first_path,
+                expected,
+            } => write!(
+                f,
+                "{} was compressed with {} but {} with {}. Transcode it to {} first",
+                path, algorithm, first_path, expected, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CgnCliError {
+    /// Get the underlying cause of the error, if any
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CgnCliError::Io(e) => Some(e),
+            CgnCliError::FileFailed { source, .. } => Some(source.as_ref()),
+            CgnCliError::CompressionFailed(e)
+            | CgnCliError::DecompressionFailed(e)
+            | CgnCliError::GameCompressionFailed(_, e)
+            | CgnCliError::GameDecompressionFailed(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CgnCliError {
+    /// Wrap an I/O error
+    fn from(e: std::io::Error) -> Self {
+        CgnCliError::Io(e)
+    }
+}
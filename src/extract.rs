@@ -0,0 +1,39 @@
+use super::archive::{extract_game, extract_game_from, game_count};
+use super::error::CgnCliError;
+use super::header::read_header;
+use super::io_util::{input_len, open_reader, open_writer};
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// Read a CGN file into memory
+fn read_input(input_path: &str) -> Result<Vec<u8>, CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    Ok(compressed_data)
+}
+
+/// Decompress a single game from a CGN file and write it to the output path.
+/// Only the requested game is decompressed. An uncompressed file is seeked through to read only the record of the
+/// game, while stdin, URLs, and compressed inputs cannot be seeked through, so they are read into memory.
+pub fn extract(input_path: &str, game_index: usize, output_path: &str) -> Result<(), CgnCliError> {
+    let pgn_str = if input_len(input_path).is_some() {
+        extract_game_from(&mut File::open(input_path)?, game_index)?
+    } else {
+        let compressed_data = read_input(input_path)?;
+        let (header, payload) = read_header(&compressed_data)?;
+        extract_game(&header, payload, game_index)?
+    };
+
+    let mut output = open_writer(output_path)?;
+    output.write_all(pgn_str.as_bytes())?;
+    output.finish()?;
+    Ok(())
+}
+
+/// Print the number of games in a CGN file
+pub fn count(input_path: &str) -> Result<(), CgnCliError> {
+    let compressed_data = read_input(input_path)?;
+    let (header, payload) = read_header(&compressed_data)?;
+    println!("{}", game_count(&header, payload)?);
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use clap::ValueEnum;
+use pgn_reader::{BufferedReader, RawComment, SanPlus, Skip, Visitor};
+
+/// The estimated game lengths at which bullet, blitz, and rapid games end, in seconds
+const BULLET_MAX_SECS: u32 = 180;
+const BLITZ_MAX_SECS: u32 = 480;
+const RAPID_MAX_SECS: u32 = 1500;
+
+/// The number of moves assumed when estimating the length of a game from its increment
+const ESTIMATED_MOVES: u32 = 40;
+
+/// The time control classes used by Lichess
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TimeControlClass {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+    Correspondence,
+    #[value(skip)]
+    Unknown,
+}
+
+/// Whether to select games by their comments and variations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CommentFilter {
+    /// Only games with at least one comment or variation
+    With,
+    /// Only games with no comments or variations
+    Without,
+    /// Games with or without comments and variations
+    #[default]
+    Any,
+}
+
+/// The shape of the movetext of a game, as counted by `MovetextCounter`
+#[derive(Clone, Copy, Debug, Default)]
+struct Movetext {
+    plies: usize,
+    annotated: bool,
+}
+
+impl Movetext {
+    /// Get the number of moves, counting a move by only white as a whole move
+    fn moves(&self) -> usize {
+        self.plies.div_ceil(2)
+    }
+}
+
+/// A visitor that counts the plies of the main line of a game and whether it has any comments or variations
+#[derive(Default)]
+struct MovetextCounter {
+    movetext: Movetext,
+}
+
+impl Visitor for MovetextCounter {
+    /// The movetext of the game
+    type Result = Movetext;
+
+    /// Reset the counts at the start of each game
+    fn begin_game(&mut self) {
+        self.movetext = Movetext::default();
+    }
+
+    /// Count a move of the main line
+    fn san(&mut self, _san_plus: SanPlus) {
+        self.movetext.plies += 1;
+    }
+
+    /// Mark the game as annotated
+    fn comment(&mut self, _comment: RawComment<'_>) {
+        self.movetext.annotated = true;
+    }
+
+    /// Mark the game as annotated, skipping the variation so its moves are not counted
+    fn begin_variation(&mut self) -> Skip {
+        self.movetext.annotated = true;
+        Skip(true)
+    }
+
+    /// Report the movetext of the game
+    fn end_game(&mut self) -> Self::Result {
+        self.movetext
+    }
+}
+
+/// Count the moves of a game and check for comments and variations with pgn-reader, or None if it does not parse
+fn read_movetext(pgn_str: &str) -> Option<Movetext> {
+    let mut reader = BufferedReader::new_cursor(pgn_str.as_bytes());
+    reader.read_game(&mut MovetextCounter::default()).ok()?
+}
+
+/// Classify a PGN `TimeControl` header value such as `600+0` (base seconds + increment seconds).
+/// Like Lichess, the class is chosen by the estimated game length of the base time plus 40 increments.
+/// Correspondence games are recorded as `-`.
+pub fn classify_time_control(time_control: &str) -> TimeControlClass {
+    let time_control = time_control.trim();
+    if time_control == "-" {
+        return TimeControlClass::Correspondence;
+    }
+
+    // parse the base time and the optional increment
+    let (base, increment) = time_control.split_once('+').unwrap_or((time_control, "0"));
+    let estimate = match (base.parse::<u32>(), increment.parse::<u32>()) {
+        (Ok(base), Ok(increment)) => base.saturating_add(increment.saturating_mul(ESTIMATED_MOVES)),
+        _ => return TimeControlClass::Unknown,
+    };
+
+    match estimate {
+        0..BULLET_MAX_SECS => TimeControlClass::Bullet,
+        BULLET_MAX_SECS..BLITZ_MAX_SECS => TimeControlClass::Blitz,
+        BLITZ_MAX_SECS..RAPID_MAX_SECS => TimeControlClass::Rapid,
+        _ => TimeControlClass::Classical,
+    }
+}
+
+/// Criteria for selecting games from a PGN database. Games match every criterion that is set.
+#[derive(Clone, Debug, Default)]
+pub struct GameFilter {
+    pub min_elo: Option<u32>,
+    pub max_elo: Option<u32>,
+    pub time_control: Option<TimeControlClass>,
+    pub min_moves: Option<usize>,
+    pub max_moves: Option<usize>,
+    pub comments: CommentFilter,
+}
+
+impl GameFilter {
+    /// Check if a game meets every criterion of the filter
+    pub fn matches(&self, pgn_str: &str) -> bool {
+        self.matches_elo(pgn_str)
+            && self.matches_time_control(pgn_str)
+            && self.matches_movetext(pgn_str)
+    }
+
+    /// Check the number of moves is within the range and the game has or lacks comments and variations as required.
+    /// The movetext is only parsed if one of these criteria is set, and games that fail to parse never match.
+    fn matches_movetext(&self, pgn_str: &str) -> bool {
+        if self.min_moves.is_none()
+            && self.max_moves.is_none()
+            && self.comments == CommentFilter::Any
+        {
+            return true;
+        }
+
+        let Some(movetext) = read_movetext(pgn_str) else {
+            return false;
+        };
+        let moves = movetext.moves();
+        self.min_moves.is_none_or(|min| moves >= min)
+            && self.max_moves.is_none_or(|max| moves <= max)
+            && match self.comments {
+                CommentFilter::With => movetext.annotated,
+                CommentFilter::Without => !movetext.annotated,
+                CommentFilter::Any => true,
+            }
+    }
+
+    /// Check the game was played with the time control class. Games without a time control only match if no class is set.
+    fn matches_time_control(&self, pgn_str: &str) -> bool {
+        match self.time_control {
+            Some(class) => header_value(pgn_str, "TimeControl")
+                .is_some_and(|time_control| classify_time_control(time_control) == class),
+            None => true,
+        }
+    }
+
+    /// Check both players are rated within the Elo range. Games without ratings only match if no range is set.
+    fn matches_elo(&self, pgn_str: &str) -> bool {
+        if self.min_elo.is_none() && self.max_elo.is_none() {
+            return true;
+        }
+
+        ["WhiteElo", "BlackElo"].iter().all(|key| {
+            match header_value(pgn_str, key).and_then(|elo| elo.parse::<u32>().ok()) {
+                Some(elo) => {
+                    self.min_elo.is_none_or(|min| elo >= min)
+                        && self.max_elo.is_none_or(|max| elo <= max)
+                }
+                None => false,
+            }
+        })
+    }
+}
+
+/// Get the value of a tag from the header section of a PGN game
+pub fn header_value<'a>(pgn_str: &'a str, key: &str) -> Option<&'a str> {
+    pgn_str
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.starts_with('[') || line.is_empty())
+        .filter_map(|line| line.strip_prefix('[')?.strip_suffix(']'))
+        .find_map(|tag| {
+            let (tag_key, value) = tag.split_once(' ')?;
+            if tag_key != key {
+                return None;
+            }
+            value.trim().strip_prefix('"')?.strip_suffix('"')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_time_controls() {
+        assert_eq!(classify_time_control("-"), TimeControlClass::Correspondence);
+        assert_eq!(classify_time_control("60+0"), TimeControlClass::Bullet);
+        assert_eq!(classify_time_control("180+0"), TimeControlClass::Blitz);
+        assert_eq!(classify_time_control("600+0"), TimeControlClass::Rapid);
+        assert_eq!(classify_time_control("1500+0"), TimeControlClass::Classical);
+    }
+
+    #[test]
+    fn classifies_time_controls_without_increment() {
+        assert_eq!(classify_time_control("300"), TimeControlClass::Blitz);
+    }
+
+    #[test]
+    fn malformed_time_controls_are_unknown() {
+        assert_eq!(classify_time_control("abc"), TimeControlClass::Unknown);
+        assert_eq!(classify_time_control("10+x"), TimeControlClass::Unknown);
+    }
+}
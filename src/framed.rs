@@ -0,0 +1,123 @@
+use super::algorithm::Algorithm;
+use super::archive::GAME_SEPARATOR;
+use super::codec::codec_for;
+use super::error::CgnCliError;
+use super::pgn_db::PgnDbIter;
+use super::profile;
+use std::io::{self, BufRead, Read, Write};
+
+/// The size of the little-endian length prefix of each frame
+const FRAME_LEN_BYTES: usize = 4;
+
+/// Write a compressed game as a frame: its length as a little-endian u32 followed by its bytes
+fn write_frame<W: Write>(writer: &mut W, record: &[u8]) -> Result<(), CgnCliError> {
+    let len = u32::try_from(record.len()).map_err(|_| CgnCliError::RecordTooLarge(record.len()))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(record)?;
+    Ok(())
+}
+
+/// Compress the games read from a PGN database one at a time into a framed stream, for piping into another process.
+/// The stream is only the frames of the games one after another, with no header, index, or checksums, so it can be
+/// written as the games are read and read back as they arrive. Each frame is flushed as soon as it is written.
+/// The algorithm is not recorded, so it must be given again to decompress the stream. Returns the number of games.
+pub fn compress_framed<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    algorithm: Algorithm,
+    verify: bool,
+) -> Result<usize, CgnCliError> {
+    let codec = codec_for(algorithm);
+    let mut games = PgnDbIter::new(reader);
+    let mut written = 0;
+    while let Some(pgn_str) = profile::time("read", || games.next()) {
+        let compressed_pgn_data = codec
+            .compress(&pgn_str)
+            .map_err(|e| CgnCliError::GameCompressionFailed(written, e))?;
+        if verify && !codec.round_trips(&pgn_str, &compressed_pgn_data) {
+            return Err(CgnCliError::RoundTripFailed(algorithm));
+        }
+        profile::time("write", || {
+            write_frame(writer, &compressed_pgn_data)?;
+            writer.flush().map_err(CgnCliError::from)
+        })?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// An iterator over the frames of a framed stream, reading each frame only once it is needed
+pub struct Frames<R> {
+    reader: R,
+}
+
+impl<R: Read> Frames<R> {
+    /// Read the frames of a framed stream
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Read the length prefix of the next frame, or None if the stream ends before it starts
+    fn read_len(&mut self) -> Result<Option<usize>, CgnCliError> {
+        let mut len = [0; FRAME_LEN_BYTES];
+        let mut filled = 0;
+        while filled < len.len() {
+            match self.reader.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(CgnCliError::TruncatedRecord),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(u32::from_le_bytes(len) as usize))
+    }
+}
+
+impl<R: Read> Iterator for Frames<R> {
+    type Item = Result<Vec<u8>, CgnCliError>;
+
+    /// Read the next frame, failing if the stream ends part way through it
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = match self.read_len() {
+            Ok(len) => len?,
+            Err(e) => return Some(Err(e)),
+        };
+        // read up to the length rather than allocating it up front, so a corrupt length cannot exhaust memory
+        let mut record = Vec::new();
+        Some(
+            match (&mut self.reader).take(len as u64).read_to_end(&mut record) {
+                Ok(read) if read < len => Err(CgnCliError::TruncatedRecord),
+                Ok(_) => Ok(record),
+                Err(e) => Err(e.into()),
+            },
+        )
+    }
+}
+
+/// Decompress a framed stream written by `compress_framed` with the algorithm it was compressed with, writing each
+/// game as soon as its frame arrives, with a blank line between games. Returns the number of games.
+pub fn decompress_framed<R: Read, W: Write>(
+    reader: R,
+    writer: &mut W,
+    algorithm: Algorithm,
+) -> Result<usize, CgnCliError> {
+    let codec = codec_for(algorithm);
+    let mut games = 0;
+    let mut frames = Frames::new(reader);
+    while let Some(record) = profile::time("read", || frames.next()) {
+        let record = record?;
+        let pgn_str = codec
+            .decompress(&record)
+            .map_err(|e| CgnCliError::GameDecompressionFailed(games, e))?;
+        profile::time("write", || {
+            if games > 0 {
+                writer.write_all(GAME_SEPARATOR.as_bytes())?;
+            }
+            writer.write_all(pgn_str.as_bytes())?;
+            writer.flush()
+        })?;
+        games += 1;
+    }
+    Ok(games)
+}
@@ -0,0 +1,124 @@
+use super::archive::{indexed_file, is_single_game, scan_records};
+use super::error::CgnCliError;
+use super::header::read_header;
+use super::io_util::{check_overwrite, open_reader, write_atomically};
+use super::output;
+use rayon::prelude::*;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Get the path of the manifest of the games lost from a salvaged file, written beside it
+pub fn manifest_path(salvage_path: &str) -> String {
+    format!("{}.lost", salvage_path)
+}
+
+/// Scan every record of a CGN file, checking each against its checksum and that it decompresses, and report the
+/// index of every corrupt game. Records of files from before record checksums can only be checked by decompressing.
+/// If a salvage path is given, the intact records are written there as a new indexed file, along with a manifest of
+/// the lost games beside it, one index and reason per line. The salvaged file is only overwritten if `force` is set.
+pub fn fsck(input_path: &str, salvage_path: Option<&str>, force: bool) -> Result<(), CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let (header, payload) = read_header(&compressed_data)?;
+
+    // a single payload is either intact or lost as a whole
+    if is_single_game(&header) {
+        if salvage_path.is_some() {
+            return Err(CgnCliError::NotSalvageable(input_path.to_string()));
+        }
+        header.verify_checksum(payload)?;
+        header
+            .decompress(payload)
+            .map_err(CgnCliError::DecompressionFailed)?;
+        output::ok(format_args!("{}: 1 game, intact", input_path));
+        return Ok(());
+    }
+
+    if header.verify_checksum(payload).is_err() {
+        output::warning(format!(
+            "{} does not match its checksum, checking each record",
+            input_path
+        ));
+    }
+    if !header.has_record_checksums() {
+        output::note(format!(
+            "Records of format version {} have no checksums, so they are only checked to decompress",
+            header.version
+        ));
+    }
+
+    // check every record, keeping the intact ones in order
+    let records = scan_records(&header, payload)?;
+    let total = records.len();
+    let checked = records
+        .into_par_iter()
+        .map(|record| {
+            let record = record?;
+            header
+                .decompress(record)
+                .map_err(CgnCliError::DecompressionFailed)?;
+            Ok(record)
+        })
+        .collect::<Vec<Result<_, CgnCliError>>>();
+    let mut intact = Vec::new();
+    let mut lost = Vec::new();
+    for (i, record) in checked.into_iter().enumerate() {
+        match record {
+            Ok(record) => intact.push(record),
+            Err(e) => {
+                // the index of the game is given alongside the reason it was lost
+                let reason = match e {
+                    CgnCliError::RecordChecksumMismatch(_) => {
+                        String::from("Record does not match its checksum")
+                    }
+                    e => e.to_string(),
+                };
+                output::warning(format!("Game {}: {}", i, reason));
+                lost.push(format!("{}\t{}", i, reason));
+            }
+        }
+    }
+    output::report(|out| {
+        writeln!(
+            out,
+            "Checked {} game(s) in {}: {} intact, {} corrupt",
+            total,
+            input_path,
+            intact.len(),
+            lost.len()
+        )?;
+        Ok(())
+    })?;
+
+    // write the intact records to a new file, with the list of lost games beside it
+    if let Some(salvage_path) = salvage_path {
+        let manifest_path = manifest_path(salvage_path);
+        check_overwrite(Path::new(salvage_path), force)?;
+        check_overwrite(Path::new(&manifest_path), force)?;
+        write_atomically(Path::new(salvage_path), &indexed_file(&header, &intact)?)?;
+        let mut manifest = lost.join("\n");
+        if !manifest.is_empty() {
+            manifest.push('\n');
+        }
+        write_atomically(Path::new(&manifest_path), manifest.as_bytes())?;
+        output::status(format!(
+            "Salvaged {} game(s) into {}, listing {} lost game(s) in {}",
+            intact.len(),
+            salvage_path,
+            lost.len(),
+            manifest_path
+        ));
+    }
+
+    if !lost.is_empty() {
+        return Err(CgnCliError::CorruptRecords {
+            corrupt: lost.len(),
+            total,
+        });
+    }
+    output::ok(format_args!(
+        "{}: {} game(s), all intact",
+        input_path, total
+    ));
+    Ok(())
+}
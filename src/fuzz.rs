@@ -0,0 +1,174 @@
+use super::algorithm::Algorithm;
+use super::benchmark::{map_games, BenchConfig};
+use super::codec::{self, CodecError};
+use super::error::CgnCliError;
+use super::output;
+use super::progress::progress_bar;
+use clap::ValueEnum;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::ops::AddAssign;
+use std::panic;
+
+/// The number of corrupted copies decompressed for each game, half with a flipped byte and half truncated
+const CORRUPTIONS_PER_GAME: usize = 8;
+
+/// How decompressing a corrupted copy of a game turned out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    /// The decompressor panicked
+    Panicked,
+    /// The decompressor returned an error
+    Errored,
+    /// The decompressor succeeded but produced a different game
+    WrongOutput,
+    /// The decompressor produced the original game, so the corruption went unnoticed but did no harm
+    Unchanged,
+}
+
+/// The number of corruptions of each outcome for a single algorithm
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FuzzReport {
+    pub games: usize,
+    pub skipped_games: usize,
+    pub panicked: usize,
+    pub errored: usize,
+    pub wrong_output: usize,
+    pub unchanged: usize,
+}
+
+impl FuzzReport {
+    /// Record the outcome of a single corruption
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Panicked => self.panicked += 1,
+            Outcome::Errored => self.errored += 1,
+            Outcome::WrongOutput => self.wrong_output += 1,
+            Outcome::Unchanged => self.unchanged += 1,
+        }
+    }
+
+    /// Get the total number of corruptions tried
+    pub fn corruptions(&self) -> usize {
+        self.panicked + self.errored + self.wrong_output + self.unchanged
+    }
+}
+
+impl AddAssign for FuzzReport {
+    /// Add the counts of another report
+    fn add_assign(&mut self, other: Self) {
+        self.games += other.games;
+        self.skipped_games += other.skipped_games;
+        self.panicked += other.panicked;
+        self.errored += other.errored;
+        self.wrong_output += other.wrong_output;
+        self.unchanged += other.unchanged;
+    }
+}
+
+impl Display for FuzzReport {
+    /// Display the counts of each outcome
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Games: {} ({} skipped as they failed to compress)",
+            self.games, self.skipped_games
+        )?;
+        writeln!(f, "Corruptions: {}", self.corruptions())?;
+        writeln!(f, "Panicked: {}", self.panicked)?;
+        writeln!(f, "Clean errors: {}", self.errored)?;
+        writeln!(f, "Silent wrong output: {}", self.wrong_output)?;
+        writeln!(f, "Unchanged output: {}", self.unchanged)
+    }
+}
+
+/// Corrupt a copy of compressed data, flipping the bits of one random byte or truncating it at a random length
+fn corrupt(compressed_data: &[u8], truncate: bool, rng: &mut ChaCha8Rng) -> Vec<u8> {
+    let mut corrupted = compressed_data.to_vec();
+    if truncate && corrupted.len() > 1 {
+        corrupted.truncate(rng.gen_range(1..corrupted.len()));
+    } else {
+        let i = rng.gen_range(0..corrupted.len());
+        corrupted[i] ^= rng.gen_range(1..=u8::MAX);
+    }
+    corrupted
+}
+
+/// Classify the result of decompressing corrupted data against the intact game
+fn classify(decompressed: Result<String, CodecError>, intact: &str) -> Outcome {
+    match decompressed {
+        Err(CodecError::Panicked) => Outcome::Panicked,
+        Err(_) => Outcome::Errored,
+        Ok(pgn_str) if pgn_str == intact => Outcome::Unchanged,
+        Ok(_) => Outcome::WrongOutput,
+    }
+}
+
+/// Compress a game, then decompress corrupted copies of it and count the outcomes.
+/// The corruptions are seeded from the game so each run tries the same ones.
+fn fuzz_game(algorithm: Algorithm, pgn_str: &str) -> FuzzReport {
+    let mut report = FuzzReport {
+        games: 1,
+        ..FuzzReport::default()
+    };
+    let intact = codec::compress(algorithm, pgn_str).and_then(|compressed_data| {
+        codec::decompress(algorithm, &compressed_data).map(|intact| (compressed_data, intact))
+    });
+    let (compressed_data, intact) = match intact {
+        Ok((compressed_data, intact)) if !compressed_data.is_empty() => (compressed_data, intact),
+        _ => {
+            report.skipped_games += 1;
+            return report;
+        }
+    };
+
+    let mut rng = ChaCha8Rng::seed_from_u64(crc32fast::hash(pgn_str.as_bytes()) as u64);
+    for i in 0..CORRUPTIONS_PER_GAME {
+        let corrupted = corrupt(&compressed_data, i % 2 == 1, &mut rng);
+        report.record(classify(codec::decompress(algorithm, &corrupted), &intact));
+    }
+    report
+}
+
+/// Decompress corrupted copies of the games in a database with every algorithm,
+/// reporting how many corruptions caused panics, clean errors, or silently wrong output
+pub fn fuzz_robustness(config: BenchConfig) -> Result<(), CgnCliError> {
+    // silence the panic messages of the cgn library, as panics are expected and counted
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    output::status("[FUZZ] Decompressing corrupted games for all algorithms...");
+    let algorithms = Algorithm::value_variants();
+    let total_games = config
+        .number_of_games
+        .count()
+        .map(|n| (n * algorithms.len()) as u64);
+    let progress = progress_bar(total_games, "games");
+    let reports = algorithms
+        .iter()
+        .map(|&algorithm| {
+            let games = map_games(
+                &config.input_db_path,
+                &config.number_of_games,
+                &config.filter,
+                config.sampling,
+                &progress,
+                |pgn_str| fuzz_game(algorithm, pgn_str),
+            )?;
+            let mut report = FuzzReport::default();
+            for game in games {
+                report += game;
+            }
+            Ok((algorithm, report))
+        })
+        .collect::<Result<Vec<_>, CgnCliError>>();
+    progress.finish_and_clear();
+    panic::set_hook(hook);
+
+    for (algorithm, report) in reports? {
+        println!("[FUZZ] Robustness of {}:", algorithm);
+        println!("{}", report);
+    }
+    Ok(())
+}
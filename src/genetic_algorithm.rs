@@ -1,11 +1,17 @@
+use super::algorithm::Tuning;
 use super::benchmark::{collect_metrics_custom, metrics_to_summary, ToTake};
-use cgn::compression::dynamic_huffman::compress_pgn_data_custom;
-use cgn::compression::dynamic_huffman::decompress_pgn_data_custom;
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use super::error::CgnCliError;
+use super::io_util::Output;
+use super::output;
+use clap::ValueEnum;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
 
 /// Configuration for the genetic algorithm used to find the optimal height and dev values for the dynamic Huffman compression algorithm
 pub struct GeneticAlgorithmConfig {
@@ -20,6 +26,22 @@ pub struct GeneticAlgorithmConfig {
     pub dev_max: f64,
     pub input_db_path: String,
     pub output_path: String,
+    pub seed: u64,
+    pub checkpoint_path: Option<String>,
+    pub checkpoint_every: u32,
+    pub resume_path: Option<String>,
+    pub history_path: Option<String>,
+    pub history_format: HistoryFormat,
+    pub patience: Option<u32>,
+    pub min_delta: f64,
+    pub elitism: usize,
+}
+
+/// The format to write the per-generation history of a run in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HistoryFormat {
+    Json,
+    Csv,
 }
 
 impl Display for GeneticAlgorithmConfig {
@@ -27,106 +49,386 @@ impl Display for GeneticAlgorithmConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "init_population: {}, number_of_games: {}, generations: {}, mutation_rate: {}, tournament_size: {}, height_min: {}, height_max: {}, dev_min: {}, dev_max: {}, input_db_path: {}, output_path: {}",
-            self.init_population, self.number_of_games, self.generations, self.mutation_rate, self.tournament_size, self.height_min, self.height_max, self.dev_min, self.dev_max, self.input_db_path, self.output_path
-        )
+            "init_population: {}, number_of_games: {}, generations: {}, mutation_rate: {}, tournament_size: {}, height_min: {}, height_max: {}, dev_min: {}, dev_max: {}, input_db_path: {}, output_path: {}, seed: {}, elitism: {}",
+            self.init_population, self.number_of_games, self.generations, self.mutation_rate, self.tournament_size, self.height_min, self.height_max, self.dev_min, self.dev_max, self.input_db_path, self.output_path, self.seed, self.elitism
+        )?;
+        if let Some(patience) = self.patience {
+            write!(f, ", patience: {}, min_delta: {}", patience, self.min_delta)?;
+        }
+        Ok(())
     }
 }
 
 /// An individual in the genetic algorithm
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Individual {
     height: f64,
     deviation: f64,
 }
 
-/// Runs a genetic algorithm to find the optimal height and dev values for the dynamic Huffman compression algorithm
-pub fn genetic_algorithm(config: GeneticAlgorithmConfig) {
-    // create the initial population and create the output file
-    let mut population = init_population(&config);
-    let mut file = File::create(&config.output_path).unwrap();
+/// Statistics about the population after a generation, for tracking how a run converged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GenerationStats {
+    generation: u32,
+    best_fitness: f64,
+    mean_fitness: f64,
+    best_height: f64,
+    best_dev: f64,
+    diversity: f64,
+}
 
-    // write the genetic algorithm configuration to the output file
-    file.write_all(format!("{}\n", config).as_bytes()).unwrap();
+impl GenerationStats {
+    /// Summarize a population sorted by fitness, best first
+    fn new(
+        config: &GeneticAlgorithmConfig,
+        generation: u32,
+        population: &[(Individual, f64)],
+    ) -> Self {
+        let (best, best_fitness) = &population[0];
+        let n = population.len() as f64;
+        GenerationStats {
+            generation,
+            best_fitness: *best_fitness,
+            mean_fitness: population.iter().map(|(_, fitness)| fitness).sum::<f64>() / n,
+            best_height: best.height,
+            best_dev: best.deviation,
+            diversity: diversity(config, population),
+        }
+    }
+}
 
-    // run the genetic algorithm for the specified number of generations
-    for gen_num in 0..config.generations {
-        population = create_new_generation(&config, population);
-        population.sort_by(|x, y| x.1.partial_cmp(&y.1).unwrap());
+/// Measure the diversity of a population as the mean distance of its individuals from the centroid,
+/// with height and dev scaled by their configured ranges so both contribute equally
+fn diversity(config: &GeneticAlgorithmConfig, population: &[(Individual, f64)]) -> f64 {
+    let scale = |value: f64, min: f64, max: f64| {
+        if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.0
+        }
+    };
+    let points = population
+        .iter()
+        .map(|(individual, _)| {
+            (
+                scale(individual.height, config.height_min, config.height_max),
+                scale(individual.deviation, config.dev_min, config.dev_max),
+            )
+        })
+        .collect::<Vec<_>>();
 
-        // write the individuals to the output file
-        population
+    let n = points.len() as f64;
+    let centroid = (
+        points.iter().map(|p| p.0).sum::<f64>() / n,
+        points.iter().map(|p| p.1).sum::<f64>() / n,
+    );
+    points
+        .iter()
+        .map(|p| ((p.0 - centroid.0).powi(2) + (p.1 - centroid.1).powi(2)).sqrt())
+        .sum::<f64>()
+        / n
+}
+
+/// Check if the best fitness has failed to improve by more than `min_delta` over the last `patience` generations.
+/// Lower fitness is better, so an improvement is a decrease in the best fitness.
+fn has_plateaued(history: &[GenerationStats], patience: u32, min_delta: f64) -> bool {
+    let patience = patience as usize;
+    if history.len() <= patience {
+        return false;
+    }
+
+    // compare the best fitness before the window with the best fitness within it
+    let (before, window) = history.split_at(history.len() - patience);
+    let best = |stats: &[GenerationStats]| {
+        stats
             .iter()
-            .enumerate()
-            .for_each(|(rank, individual)| {
-                file.write_all(
+            .map(|stats| stats.best_fitness)
+            .fold(f64::INFINITY, f64::min)
+    };
+    best(before) - best(window) <= min_delta
+}
+
+/// The history of a run, with the best individual found in any generation at the top level
+#[derive(Serialize)]
+struct History<'a> {
+    best_generation: u32,
+    best_fitness: f64,
+    best_height: f64,
+    best_dev: f64,
+    generations: &'a [GenerationStats],
+}
+
+/// Write the per-generation history of a run to a file, replacing any existing file.
+/// CSV files have one row per generation, while JSON files also summarize the best individual of the run.
+fn write_history(
+    path: &str,
+    format: HistoryFormat,
+    history: &[GenerationStats],
+) -> Result<(), CgnCliError> {
+    let mut writer = BufWriter::new(Output::create(Path::new(path))?);
+    match format {
+        HistoryFormat::Json => {
+            let best = history
+                .iter()
+                .min_by(|x, y| x.best_fitness.total_cmp(&y.best_fitness));
+            let report = History {
+                best_generation: best.map_or(0, |best| best.generation),
+                best_fitness: best.map_or(f64::NAN, |best| best.best_fitness),
+                best_height: best.map_or(f64::NAN, |best| best.best_height),
+                best_dev: best.map_or(f64::NAN, |best| best.best_dev),
+                generations: history,
+            };
+            serde_json::to_writer_pretty(&mut writer, &report)
+                .map_err(|e| CgnCliError::Io(e.into()))?;
+        }
+        HistoryFormat::Csv => {
+            writer.write_all(
+                b"generation,best_fitness,mean_fitness,best_height,best_dev,diversity\n",
+            )?;
+            for stats in history {
+                writer.write_all(
                     format!(
-                        "Generation: {}, Rank: {}, Height: {}, Dev: {}, Fitness: {}\n",
-                        gen_num, rank, individual.0.height, individual.0.deviation, individual.1
+                        "{},{},{},{},{},{}\n",
+                        stats.generation,
+                        stats.best_fitness,
+                        stats.mean_fitness,
+                        stats.best_height,
+                        stats.best_dev,
+                        stats.diversity
                     )
                     .as_bytes(),
+                )?;
+            }
+        }
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+    Ok(())
+}
+
+/// The state of a genetic algorithm run after a generation, from which the run can be resumed
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    next_generation: u32,
+    init_population: usize,
+    height_min: f64,
+    height_max: f64,
+    dev_min: f64,
+    dev_max: f64,
+    rng: ChaCha12Rng,
+    population: Vec<(Individual, f64)>,
+    #[serde(default)]
+    history: Vec<GenerationStats>,
+}
+
+impl Checkpoint {
+    /// Check the checkpoint was written by a run with the same population size and bounds as the config
+    fn check_config(&self, config: &GeneticAlgorithmConfig) -> Result<(), CgnCliError> {
+        let fields = [
+            (
+                "population size",
+                self.init_population as f64,
+                config.init_population as f64,
+            ),
+            ("height_min", self.height_min, config.height_min),
+            ("height_max", self.height_max, config.height_max),
+            ("dev_min", self.dev_min, config.dev_min),
+            ("dev_max", self.dev_max, config.dev_max),
+        ];
+        match fields
+            .iter()
+            .find(|(_, checkpoint, config)| checkpoint != config)
+        {
+            Some((name, checkpoint, config)) => Err(CgnCliError::CheckpointMismatch(format!(
+                "{} is {} in the checkpoint but {} in the config",
+                name, checkpoint, config
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Read a checkpoint from a file
+fn load_checkpoint(path: &str) -> Result<Checkpoint, CgnCliError> {
+    let reader = BufReader::new(File::open(path)?);
+    serde_json::from_reader(reader).map_err(|e| CgnCliError::InvalidCheckpoint(e.to_string()))
+}
+
+/// Write a checkpoint to a file, replacing any existing checkpoint
+fn save_checkpoint(path: &str, checkpoint: &Checkpoint) -> Result<(), CgnCliError> {
+    let mut writer = BufWriter::new(Output::create(Path::new(path))?);
+    serde_json::to_writer(&mut writer, checkpoint)
+        .map_err(|e| CgnCliError::InvalidCheckpoint(e.to_string()))?;
+    writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+    Ok(())
+}
+
+/// Runs a genetic algorithm to find the optimal height and dev values for the dynamic Huffman compression algorithm.
+/// Every random choice is drawn from an RNG seeded with the configured seed, so runs with the same seed are identical.
+/// A resumed run continues from the state in its checkpoint, adding to the existing output file.
+/// The output file is only replaced once the run finishes, so an interrupted run leaves the earlier output in place.
+/// Fitness is evaluated in parallel on the current rayon thread pool, while selection and mutation stay sequential.
+pub fn genetic_algorithm(config: GeneticAlgorithmConfig) -> Result<(), CgnCliError> {
+    evolve(&config)
+}
+
+/// Run the generations of the genetic algorithm on the current thread pool
+fn evolve(config: &GeneticAlgorithmConfig) -> Result<(), CgnCliError> {
+    let (mut population, mut rng, first_generation, mut file, mut history) =
+        match &config.resume_path {
+            // restore the population and RNG from the checkpoint
+            Some(resume_path) => {
+                let checkpoint = load_checkpoint(resume_path)?;
+                checkpoint.check_config(config)?;
+
+                // carry over the output of the earlier run, which is replaced once this run finishes
+                let earlier_output = match fs::read(&config.output_path) {
+                    Ok(earlier_output) => earlier_output,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                let mut file = Output::create(Path::new(&config.output_path))?;
+                file.write_all(&earlier_output)?;
+                (
+                    checkpoint.population,
+                    checkpoint.rng,
+                    checkpoint.next_generation,
+                    file,
+                    checkpoint.history,
                 )
-                .unwrap()
-            });
+            }
+            // create the initial population and create the output file
+            None => {
+                let mut rng = ChaCha12Rng::seed_from_u64(config.seed);
+                let population = init_population(config, &mut rng)?;
+                let mut file = Output::create(Path::new(&config.output_path))?;
+
+                // write the genetic algorithm configuration to the output file
+                file.write_all(format!("{}\n", config).as_bytes())?;
+                (population, rng, 0, file, Vec::new())
+            }
+        };
+
+    // run the genetic algorithm for the specified number of generations
+    for gen_num in first_generation..config.generations {
+        population = create_new_generation(config, population, &mut rng)?;
+        population.sort_by(|x, y| x.1.partial_cmp(&y.1).unwrap());
+        history.push(GenerationStats::new(config, gen_num, &population));
+
+        // write the individuals to the output file
+        for (rank, individual) in population.iter().enumerate() {
+            file.write_all(
+                format!(
+                    "Generation: {}, Rank: {}, Height: {}, Dev: {}, Fitness: {}\n",
+                    gen_num, rank, individual.0.height, individual.0.deviation, individual.1
+                )
+                .as_bytes(),
+            )?;
+        }
+
+        // save the state of the run every checkpoint interval
+        if let Some(checkpoint_path) = &config.checkpoint_path {
+            if (gen_num + 1).is_multiple_of(config.checkpoint_every) {
+                let checkpoint = Checkpoint {
+                    next_generation: gen_num + 1,
+                    init_population: config.init_population,
+                    height_min: config.height_min,
+                    height_max: config.height_max,
+                    dev_min: config.dev_min,
+                    dev_max: config.dev_max,
+                    rng: rng.clone(),
+                    population: population.clone(),
+                    history: history.clone(),
+                };
+                save_checkpoint(checkpoint_path, &checkpoint)?;
+            }
+        }
+
+        // stop early once the best fitness stops improving
+        if let Some(patience) = config.patience {
+            if has_plateaued(&history, patience, config.min_delta) {
+                let reason = format!(
+                    "Stopped early after generation {}, as the best fitness did not improve by more than {} in {} generations\n",
+                    gen_num, config.min_delta, patience
+                );
+                file.write_all(reason.as_bytes())?;
+                output::status(reason.trim_end());
+                break;
+            }
+        }
     }
+
+    file.finish()?;
+
+    if let Some(history_path) = &config.history_path {
+        write_history(history_path, config.history_format, &history)?;
+    }
+    Ok(())
 }
 
 /// Create an inital population of random individuals and evaluate their fitness
-fn init_population(config: &GeneticAlgorithmConfig) -> Vec<(Individual, f64)> {
+fn init_population(
+    config: &GeneticAlgorithmConfig,
+    rng: &mut ChaCha12Rng,
+) -> Result<Vec<(Individual, f64)>, CgnCliError> {
     // create a population of random individuals
     let mut population = Vec::with_capacity(config.init_population);
-    let mut rng = thread_rng();
     for _ in 0..config.init_population {
         let height = rng.gen_range(config.height_min..=config.height_max);
         let deviation = rng.gen_range(config.dev_min..=config.dev_max);
         population.push(Individual { height, deviation });
     }
 
-    // evaluate the fitness of each individual in the initial population
+    // evaluate the fitness of each individual in the initial population, keeping the population in order
     population
-        .into_iter()
-        .par_bridge()
+        .into_par_iter()
         .map(|individual| {
-            let fitness = fitness_function(config, &individual);
-            (individual, fitness)
+            let fitness = fitness_function(config, &individual)?;
+            Ok((individual, fitness))
         })
         .collect()
 }
 
-/// Create a new generation of individuals using crossover and mutation of randomly selected parents
+/// Create a new generation of individuals using crossover and mutation of randomly selected parents.
+/// The fittest `elitism` individuals are carried into the new generation unchanged, so the best fitness never regresses.
 fn create_new_generation(
     config: &GeneticAlgorithmConfig,
     population: Vec<(Individual, f64)>,
-) -> Vec<(Individual, f64)> {
-    let mut rng = rand::thread_rng();
-    let parents = select_parents(config, &population);
-    let mut children = Vec::with_capacity(population.len() / 2);
+    rng: &mut ChaCha12Rng,
+) -> Result<Vec<(Individual, f64)>, CgnCliError> {
+    let parents = select_parents(config, &population, rng);
+    let elitism = config.elitism.min(population.len());
+    let mut children = Vec::with_capacity(population.len() - elitism);
 
     // create children by crossover of randomly selected parents
-    for _ in 0..population.len() {
-        let parent1 = parents.choose(&mut rng).unwrap();
-        let parent2 = parents.choose(&mut rng).unwrap();
-        let child = crossover(config, &parent1.0, &parent2.0);
+    for _ in elitism..population.len() {
+        let parent1 = parents.choose(rng).unwrap();
+        let parent2 = parents.choose(rng).unwrap();
+        let child = crossover(config, &parent1.0, &parent2.0, rng);
         children.push(child);
     }
 
-    // evaluate the fitness of each child
-    children
-        .into_iter()
-        .par_bridge()
+    // keep the fittest individuals, which already have their fitness evaluated
+    let mut elites = population;
+    elites.sort_by(|x, y| x.1.partial_cmp(&y.1).unwrap());
+    elites.truncate(elitism);
+
+    // evaluate the fitness of each child, keeping the children in order
+    let children = children
+        .into_par_iter()
         .map(|x| {
-            let fitness = fitness_function(config, &x);
-            (x, fitness)
+            let fitness = fitness_function(config, &x)?;
+            Ok((x, fitness))
         })
-        .collect()
+        .collect::<Result<Vec<_>, CgnCliError>>()?;
+    Ok(elites.into_iter().chain(children).collect())
 }
 
 /// Select parents for crossover using tournament selection
 fn select_parents<'a>(
     config: &GeneticAlgorithmConfig,
-    population: &'a Vec<(Individual, f64)>,
+    population: &'a [(Individual, f64)],
+    rng: &mut ChaCha12Rng,
 ) -> Vec<&'a (Individual, f64)> {
-    let mut rng = rand::thread_rng();
     let mut parents = Vec::with_capacity(population.len() / 2);
 
     // take 50% of the population to be parents
@@ -134,7 +436,7 @@ fn select_parents<'a>(
         // randomly select individuals from the population to compete in the tournament
         let mut tournament = Vec::with_capacity(config.tournament_size);
         for _ in 0..config.tournament_size {
-            tournament.push(population.choose(&mut rng).unwrap());
+            tournament.push(population.choose(rng).unwrap());
         }
 
         // sort the tournament by fitness (ascending) and select the individual with the lowest fitness
@@ -150,6 +452,7 @@ fn crossover(
     config: &GeneticAlgorithmConfig,
     parent1: &Individual,
     parent2: &Individual,
+    rng: &mut ChaCha12Rng,
 ) -> Individual {
     // create a child by averaging the height and dev values of the parents
     let mut child = Individual {
@@ -158,7 +461,6 @@ fn crossover(
     };
 
     // randomly mutate the child by changing its height and dev values
-    let mut rng = rand::thread_rng();
     if rng.gen_range(0.0..=1.0) < config.mutation_rate {
         child.height = rng.gen_range(config.height_min..=config.height_max);
     }
@@ -169,15 +471,15 @@ fn crossover(
     child
 }
 
-/// Calculate the fitness of an individual
-fn fitness_function(config: &GeneticAlgorithmConfig, individual: &Individual) -> f64 {
-    metrics_to_summary(&collect_metrics_custom(
-        compress_pgn_data_custom,
-        decompress_pgn_data_custom,
-        &config.input_db_path,
-        &config.number_of_games,
-        individual.height,
-        individual.deviation,
-    ))
-    .avg_bits_per_move_excluding_headers
+/// Calculate the fitness of an individual, failing if the database cannot be read
+fn fitness_function(
+    config: &GeneticAlgorithmConfig,
+    individual: &Individual,
+) -> Result<f64, CgnCliError> {
+    let tuning = Tuning {
+        height: individual.height,
+        dev: individual.deviation,
+    };
+    let metrics = collect_metrics_custom(tuning, &config.input_db_path, &config.number_of_games)?;
+    Ok(metrics_to_summary(&metrics).avg_bits_per_move_excluding_headers)
 }
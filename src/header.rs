@@ -0,0 +1,484 @@
+use super::algorithm::{Algorithm, AlgorithmChoice, Tuning};
+use super::codec::{codec_with_tuning, Codec, CodecError};
+use super::error::CgnCliError;
+use filetime::FileTime;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The magic bytes at the start of every CGN file
+pub const MAGIC: &[u8; 4] = b"CGN1";
+
+/// The current version of the CGN file format
+pub const FORMAT_VERSION: u8 = 4;
+
+/// The first version of the CGN file format with a CRC32 checksum of each record in streamed and indexed payloads
+pub const RECORD_CHECKSUM_VERSION: u8 = 3;
+
+/// The first version of the CGN file format with a second flags byte, for the fields that no longer fit in the first
+pub const EXTENDED_FLAGS_VERSION: u8 = 4;
+
+/// The version reported for legacy files, which have no header
+pub const LEGACY_VERSION: u8 = 0;
+
+/// Flag set when the payload is a sequence of length-prefixed records, one per game
+pub const FLAG_STREAM: u8 = 0b0000_0001;
+
+/// Flag set when a CRC32 checksum of the payload follows the flags byte
+pub const FLAG_CHECKSUM: u8 = 0b0000_0010;
+
+/// Flag set when the payload is an index of the games followed by one compressed record per game
+pub const FLAG_INDEXED: u8 = 0b0000_0100;
+
+/// Flag set when the dynamic huffman height and dev used to compress the payload follow the checksum
+pub const FLAG_TUNED: u8 = 0b0000_1000;
+
+/// Flag set when the modification time of the original file follows any tuned parameters
+pub const FLAG_MTIME: u8 = 0b0001_0000;
+
+/// Flag set when the base name of the original file follows any modification time
+pub const FLAG_NAME: u8 = 0b0010_0000;
+
+/// Flag set when the payload is encrypted, with the salt and nonce it was encrypted with following any name
+pub const FLAG_ENCRYPTED: u8 = 0b0100_0000;
+
+/// Flag set when the games were rewritten in a canonical form before compression, so they are not byte-identical to the input
+pub const FLAG_NORMALIZED: u8 = 0b1000_0000;
+
+/// Extended flag set when the time the file was created and the version of the tool that created it follow any
+/// encryption parameters
+pub const EXTENDED_FLAG_METADATA: u8 = 0b0000_0001;
+
+/// Every extended flag understood by this version of the tool
+const KNOWN_EXTENDED_FLAGS: u8 = EXTENDED_FLAG_METADATA;
+
+/// The length in bytes of the salt the key of an encrypted payload is derived with
+pub const SALT_LEN: usize = 16;
+
+/// The length in bytes of the nonce an encrypted payload is encrypted with
+pub const NONCE_LEN: usize = 12;
+
+/// The most bytes a header can take up: the magic bytes, the version, algorithm, and both flags bytes, the checksum,
+/// tuning, and modification time, the longest name, the encryption parameters, and the metadata with the longest tool
+/// version
+pub const MAX_HEADER_LEN: usize = MAGIC.len()
+    + 4
+    + 4
+    + 16
+    + 12
+    + 2
+    + u16::MAX as usize
+    + SALT_LEN
+    + NONCE_LEN
+    + 8
+    + 2
+    + u16::MAX as usize;
+
+/// The parameters needed, along with the passphrase, to decrypt an encrypted payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encryption {
+    pub salt: [u8; SALT_LEN],
+    pub nonce: [u8; NONCE_LEN],
+}
+
+/// When a file was created and by which version of the tool, for tracing a file back to the release that wrote it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// Seconds since the Unix epoch
+    pub created: i64,
+    pub tool_version: String,
+}
+
+impl Metadata {
+    /// Get the metadata of a file created now by this version of the tool
+    pub fn current() -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        Self {
+            created,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// The header at the start of a CGN file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub flags: u8,
+    pub checksum: Option<u32>,
+    pub tuning: Option<Tuning>,
+    pub mtime: Option<FileTime>,
+    pub name: Option<String>,
+    pub encryption: Option<Encryption>,
+    pub metadata: Option<Metadata>,
+}
+
+impl Header {
+    /// Get the extensions to keep when the payload is rewritten, such as when transcoding or appending.
+    /// Tuned parameters and encryption only apply to the payload they were used for, so they are not kept.
+    /// Normalization is kept, as the games themselves are unchanged. Metadata is renewed, as the rewritten payload
+    /// was created now by this version of the tool.
+    pub fn kept_extensions(&self) -> Extensions {
+        Extensions {
+            tuning: None,
+            mtime: self.mtime,
+            name: self.name.clone(),
+            encryption: None,
+            normalized: self.has_flag(FLAG_NORMALIZED),
+            metadata: self.metadata.as_ref().map(|_| Metadata::current()),
+        }
+    }
+
+    /// Check if the given flag is set in the header
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Check if each record of a streamed or indexed payload carries a checksum, as in files of version 3 and later
+    pub fn has_record_checksums(&self) -> bool {
+        self.version >= RECORD_CHECKSUM_VERSION
+    }
+
+    /// Check the payload matches the checksum in the header, if it has one
+    pub fn verify_checksum(&self, payload: &[u8]) -> Result<(), CgnCliError> {
+        match self.checksum {
+            Some(checksum) if checksum != crc32fast::hash(payload) => {
+                Err(CgnCliError::ChecksumMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the codec the payload was compressed with, using the tuned parameters if the file was compressed with them
+    pub fn codec(&self) -> Box<dyn Codec> {
+        codec_with_tuning(self.algorithm, self.tuning)
+    }
+
+    /// Decompress a payload or record with the codec it was compressed with
+    pub fn decompress(&self, compressed_data: &[u8]) -> Result<String, CodecError> {
+        self.codec().decompress(compressed_data)
+    }
+}
+
+/// Write the header for a file compressed with the given algorithm, flags, and extended flags
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    algorithm: Algorithm,
+    flags: u8,
+    extended_flags: u8,
+) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION, algorithm.level(), flags, extended_flags])
+}
+
+/// The optional fields stored in the header after the checksum, each marked by a flag
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extensions {
+    pub tuning: Option<Tuning>,
+    pub mtime: Option<FileTime>,
+    pub name: Option<String>,
+    pub encryption: Option<Encryption>,
+    /// Whether the games were normalized, which only sets a flag
+    pub normalized: bool,
+    pub metadata: Option<Metadata>,
+}
+
+/// Write the header for a file with the given flags, followed by the checksum if given and then any extensions,
+/// setting the flag of each field written. The tuned parameters are written as two f64s, the modification time as
+/// i64 seconds and u32 nanoseconds since the Unix epoch, and the name as UTF-8 prefixed by its u16 length, all little-endian,
+/// followed by the salt and nonce of an encrypted payload. The metadata comes last, as the time of creation in i64 seconds
+/// since the Unix epoch followed by the tool version as UTF-8 prefixed by its u16 length.
+pub fn write_extended_header<W: Write>(
+    writer: &mut W,
+    algorithm: Algorithm,
+    flags: u8,
+    checksum: Option<u32>,
+    extensions: &Extensions,
+) -> io::Result<()> {
+    // names are base names, so a name longer than the length prefix can hold is not stored
+    let name = extensions
+        .name
+        .as_deref()
+        .and_then(|name| Some((u16::try_from(name.len()).ok()?, name)));
+
+    let mut flags = flags;
+    for (present, flag) in [
+        (checksum.is_some(), FLAG_CHECKSUM),
+        (extensions.tuning.is_some(), FLAG_TUNED),
+        (extensions.mtime.is_some(), FLAG_MTIME),
+        (name.is_some(), FLAG_NAME),
+        (extensions.encryption.is_some(), FLAG_ENCRYPTED),
+        (extensions.normalized, FLAG_NORMALIZED),
+    ] {
+        if present {
+            flags |= flag;
+        }
+    }
+    let extended_flags = if extensions.metadata.is_some() {
+        EXTENDED_FLAG_METADATA
+    } else {
+        0
+    };
+    write_header(writer, algorithm, flags, extended_flags)?;
+    if let Some(checksum) = checksum {
+        writer.write_all(&checksum.to_le_bytes())?;
+    }
+    if let Some(tuning) = extensions.tuning {
+        writer.write_all(&tuning.height.to_le_bytes())?;
+        writer.write_all(&tuning.dev.to_le_bytes())?;
+    }
+    if let Some(mtime) = extensions.mtime {
+        writer.write_all(&mtime.unix_seconds().to_le_bytes())?;
+        writer.write_all(&mtime.nanoseconds().to_le_bytes())?;
+    }
+    if let Some((len, name)) = name {
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+    }
+    if let Some(encryption) = extensions.encryption {
+        writer.write_all(&encryption.salt)?;
+        writer.write_all(&encryption.nonce)?;
+    }
+    if let Some(metadata) = &extensions.metadata {
+        writer.write_all(&metadata.created.to_le_bytes())?;
+        write_prefixed_str(writer, &metadata.tool_version)?;
+    }
+    Ok(())
+}
+
+/// Write a string as UTF-8 prefixed by its u16 length, cutting it short at the longest length the prefix can hold
+fn write_prefixed_str<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
+    let bytes = &s.as_bytes()[..s.floor_char_boundary(u16::MAX as usize)];
+    writer.write_all(&(bytes.len() as u16).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Read a UTF-8 string prefixed by its u16 length, returning it with the bytes after it
+fn read_prefixed_str(data: &[u8]) -> Result<(String, &[u8]), CgnCliError> {
+    let prefixed = data
+        .split_first_chunk::<2>()
+        .map(|(len, rest)| (u16::from_le_bytes(*len) as usize, rest))
+        .filter(|(len, rest)| *len <= rest.len())
+        .map(|(len, rest)| rest.split_at(len));
+    match prefixed {
+        Some((s, rest)) => match std::str::from_utf8(s) {
+            Ok(s) => Ok((s.to_string(), rest)),
+            Err(_) => Err(CgnCliError::NotCgnFile),
+        },
+        None => Err(CgnCliError::NotCgnFile),
+    }
+}
+
+/// Write a file whose whole payload is held in memory, with a checksum of the payload and any extensions in the header.
+/// Streamed files are written as they are compressed, so they cannot carry a checksum.
+pub fn write_extended_file<W: Write>(
+    writer: &mut W,
+    algorithm: Algorithm,
+    flags: u8,
+    extensions: &Extensions,
+    payload: &[u8],
+) -> io::Result<()> {
+    let checksum = crc32fast::hash(payload);
+    write_extended_header(writer, algorithm, flags, Some(checksum), extensions)?;
+    writer.write_all(payload)
+}
+
+/// Read the header from the start of a CGN file, returning the header and the remaining payload.
+/// Version 1 headers (magic, version, algorithm ID) have no flags byte.
+/// Version 2 and 3 headers are followed by a checksum if the checksum flag is set, then the tuned parameters if the tuned flag is set,
+/// then the modification time of the original file if the mtime flag is set, then its name if the name flag is set,
+/// then the salt and nonce if the payload is encrypted. Encrypted files are rejected, as their payload must be decrypted first.
+/// Version 3 headers are the same as version 2 headers, with only the records of the payload carrying checksums.
+/// Version 4 headers add an extended flags byte after the flags byte, and then the metadata after every other field if
+/// the metadata flag is set. The metadata is only informational, so decompressing ignores it.
+pub fn read_header(data: &[u8]) -> Result<(Header, &[u8]), CgnCliError> {
+    let (header, payload) = parse_header(data)?;
+    if header.encryption.is_some() {
+        return Err(CgnCliError::Encrypted);
+    }
+    Ok((header, payload))
+}
+
+/// Read the header of a CGN file like `read_header` from the start of a seekable reader, such as a file, returning it
+/// with its length and leaving the reader at the start of the payload. At most `MAX_HEADER_LEN` bytes are read.
+pub fn read_header_from<R: Read + Seek>(reader: &mut R) -> Result<(Header, u64), CgnCliError> {
+    let mut prefix = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_HEADER_LEN as u64)
+        .read_to_end(&mut prefix)?;
+    let (header, payload) = read_header(&prefix)?;
+    let header_len = (prefix.len() - payload.len()) as u64;
+    reader.seek(SeekFrom::Start(header_len))?;
+    Ok((header, header_len))
+}
+
+/// Read the header of a CGN file like `read_header`, returning the payload as it is stored even if it is encrypted
+pub fn parse_header(data: &[u8]) -> Result<(Header, &[u8]), CgnCliError> {
+    // check the magic bytes
+    let rest = match data.strip_prefix(MAGIC) {
+        Some(rest) => rest,
+        None => return Err(CgnCliError::NotCgnFile),
+    };
+
+    // read the fields for the version of the header
+    let (version, algorithm, flags, extended_flags, payload) = match rest {
+        [1, algorithm, payload @ ..] => (1, *algorithm, 0, 0, payload),
+        [version @ (2 | 3), algorithm, flags, payload @ ..] => {
+            (*version, *algorithm, *flags, 0, payload)
+        }
+        [4, algorithm, flags, extended_flags, payload @ ..] => {
+            (4, *algorithm, *flags, *extended_flags, payload)
+        }
+        [1..=4, ..] | [] => return Err(CgnCliError::NotCgnFile),
+        [version, ..] => return Err(CgnCliError::UnsupportedVersion(*version)),
+    };
+
+    // every bit of the flags byte is in use, so only the extended flags can hold one we do not know how to handle
+    if extended_flags & !KNOWN_EXTENDED_FLAGS != 0 {
+        return Err(CgnCliError::UnsupportedExtendedFlags(
+            extended_flags & !KNOWN_EXTENDED_FLAGS,
+        ));
+    }
+
+    // read the checksum if the header has one
+    let (checksum, payload) = if flags & FLAG_CHECKSUM != 0 {
+        match payload.split_first_chunk() {
+            Some((checksum, payload)) => (Some(u32::from_le_bytes(*checksum)), payload),
+            None => return Err(CgnCliError::NotCgnFile),
+        }
+    } else {
+        (None, payload)
+    };
+
+    // read the height and dev if the payload was compressed with tuned parameters
+    let (tuning, payload) = if flags & FLAG_TUNED != 0 {
+        match payload
+            .split_first_chunk::<8>()
+            .and_then(|(height, rest)| Some((height, rest.split_first_chunk::<8>()?)))
+        {
+            Some((height, (dev, payload))) => {
+                let tuning = Tuning {
+                    height: f64::from_le_bytes(*height),
+                    dev: f64::from_le_bytes(*dev),
+                };
+                (Some(tuning), payload)
+            }
+            None => return Err(CgnCliError::NotCgnFile),
+        }
+    } else {
+        (None, payload)
+    };
+
+    // read the modification time if the header stores one
+    let (mtime, payload) = if flags & FLAG_MTIME != 0 {
+        match payload
+            .split_first_chunk::<8>()
+            .and_then(|(seconds, rest)| Some((seconds, rest.split_first_chunk::<4>()?)))
+        {
+            Some((seconds, (nanos, payload))) => {
+                let mtime = FileTime::from_unix_time(
+                    i64::from_le_bytes(*seconds),
+                    u32::from_le_bytes(*nanos),
+                );
+                (Some(mtime), payload)
+            }
+            None => return Err(CgnCliError::NotCgnFile),
+        }
+    } else {
+        (None, payload)
+    };
+
+    // read the name of the original file if the header stores one
+    let (name, payload) = if flags & FLAG_NAME != 0 {
+        let (name, payload) = read_prefixed_str(payload)?;
+        (Some(name), payload)
+    } else {
+        (None, payload)
+    };
+
+    // read the salt and nonce if the payload is encrypted
+    let (encryption, payload) = if flags & FLAG_ENCRYPTED != 0 {
+        match payload
+            .split_first_chunk::<SALT_LEN>()
+            .and_then(|(salt, rest)| Some((salt, rest.split_first_chunk::<NONCE_LEN>()?)))
+        {
+            Some((salt, (nonce, payload))) => {
+                let encryption = Encryption {
+                    salt: *salt,
+                    nonce: *nonce,
+                };
+                (Some(encryption), payload)
+            }
+            None => return Err(CgnCliError::NotCgnFile),
+        }
+    } else {
+        (None, payload)
+    };
+
+    // read the time the file was created and the version of the tool that created it if the header stores them
+    let (metadata, payload) = if extended_flags & EXTENDED_FLAG_METADATA != 0 {
+        let Some((created, rest)) = payload.split_first_chunk::<8>() else {
+            return Err(CgnCliError::NotCgnFile);
+        };
+        let (tool_version, payload) = read_prefixed_str(rest)?;
+        let metadata = Metadata {
+            created: i64::from_le_bytes(*created),
+            tool_version,
+        };
+        (Some(metadata), payload)
+    } else {
+        (None, payload)
+    };
+
+    let header = Header {
+        version,
+        algorithm: Algorithm::from_level(algorithm)?,
+        flags,
+        checksum,
+        tuning,
+        mtime,
+        name,
+        encryption,
+        metadata,
+    };
+    Ok((header, payload))
+}
+
+/// Select the header to decompress a file with, returning it along with the compressed payload.
+/// Files without a header predate the CGN file format and can only be decompressed with an explicit algorithm.
+pub fn resolve_header(
+    data: &[u8],
+    choice: AlgorithmChoice,
+) -> Result<(Header, &[u8]), CgnCliError> {
+    match (read_header(data), choice) {
+        (Ok((header, payload)), AlgorithmChoice::Auto) => Ok((header, payload)),
+        (Ok((header, payload)), AlgorithmChoice::Explicit(requested)) => {
+            if requested != header.algorithm {
+                return Err(CgnCliError::AlgorithmMismatch {
+                    requested,
+                    found: header.algorithm,
+                });
+            }
+            Ok((header, payload))
+        }
+        (Err(CgnCliError::NotCgnFile), AlgorithmChoice::Explicit(algorithm)) => {
+            let header = Header {
+                version: LEGACY_VERSION,
+                algorithm,
+                flags: 0,
+                checksum: None,
+                tuning: None,
+                mtime: None,
+                name: None,
+                encryption: None,
+                metadata: None,
+            };
+            Ok((header, data))
+        }
+        (Err(CgnCliError::NotCgnFile), AlgorithmChoice::Auto) => Err(CgnCliError::LegacyFile),
+        (Err(e), _) => Err(e),
+    }
+}
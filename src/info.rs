@@ -0,0 +1,64 @@
+use super::archive;
+use super::benchmark::compression_ratio;
+use super::error::CgnCliError;
+use super::header::{parse_header, FLAG_INDEXED, FLAG_NORMALIZED, FLAG_STREAM};
+use super::io_util::open_reader;
+use super::output;
+use std::io::{Read, Write};
+
+/// Print the metadata of a compressed CGN file. The original size of an encrypted file is not known without its
+/// passphrase, so it is left out.
+pub fn info(input_path: &str) -> Result<(), CgnCliError> {
+    // read the compressed file and its header
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let (header, payload) = parse_header(&compressed_data)?;
+
+    output::report(|out| {
+        writeln!(out, "Algorithm: {}", header.algorithm)?;
+        writeln!(out, "Format version: {}", header.version)?;
+        writeln!(out, "Streamed: {}", header.has_flag(FLAG_STREAM))?;
+        writeln!(out, "Indexed: {}", header.has_flag(FLAG_INDEXED))?;
+        writeln!(out, "Normalized: {}", header.has_flag(FLAG_NORMALIZED))?;
+        match header.checksum {
+            Some(checksum) => writeln!(out, "Checksum: {:#010x}", checksum)?,
+            None => writeln!(out, "Checksum: none")?,
+        }
+        if let Some(tuning) = header.tuning {
+            writeln!(out, "Tuning: height {}, dev {}", tuning.height, tuning.dev)?;
+        }
+        if let Some(mtime) = header.mtime {
+            writeln!(
+                out,
+                "Original modification time: {} seconds since the Unix epoch",
+                mtime.unix_seconds()
+            )?;
+        }
+        if let Some(name) = &header.name {
+            writeln!(out, "Original name: {}", name)?;
+        }
+        if let Some(metadata) = &header.metadata {
+            writeln!(
+                out,
+                "Created: {} seconds since the Unix epoch",
+                metadata.created
+            )?;
+            writeln!(out, "Created by: cgn-cli {}", metadata.tool_version)?;
+        }
+        writeln!(out, "Encrypted: {}", header.encryption.is_some())?;
+        writeln!(out, "Compressed size: {} bytes", compressed_data.len())?;
+        if header.encryption.is_some() {
+            return Ok(());
+        }
+
+        // decompress the file to find the original size
+        let pgn_str = archive::decompress(&header, payload)?;
+        writeln!(out, "Original size: {} bytes", pgn_str.len())?;
+        writeln!(
+            out,
+            "Compression ratio: {}",
+            compression_ratio(compressed_data.len(), pgn_str.len())
+        )?;
+        Ok(())
+    })
+}
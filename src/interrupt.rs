@@ -0,0 +1,56 @@
+use super::error::ExitCode;
+use super::output;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Set once Ctrl-C is pressed, so long-running loops can stop between units of work
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The temporary files being written, removed when a second Ctrl-C exits without unwinding
+static TEMP_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Lock the temporary files being written, even if a thread panicked while holding them
+fn temp_paths() -> MutexGuard<'static, Vec<PathBuf>> {
+    TEMP_PATHS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record a temporary file being written, so it is removed if the process exits on a second Ctrl-C
+pub fn register_temp_path(path: &Path) {
+    temp_paths().push(path.into());
+}
+
+/// Stop recording a temporary file once it is renamed into place or removed
+pub fn unregister_temp_path(path: &Path) {
+    let mut paths = temp_paths();
+    if let Some(i) = paths.iter().position(|p| p == path) {
+        paths.swap_remove(i);
+    }
+}
+
+/// Catch Ctrl-C, setting the interrupted flag instead of killing the process.
+/// A second Ctrl-C removes the temporary files being written and exits straight away, for when the work in progress
+/// takes too long to finish.
+pub fn catch_interrupts() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            for path in temp_paths().iter() {
+                let _ = fs::remove_file(path);
+            }
+            process::exit(ExitCode::Interrupted as i32);
+        }
+        output::note(
+            "Interrupted, finishing the files in progress. Press Ctrl-C again to stop now",
+        );
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to catch Ctrl-C: {}", e);
+    }
+}
+
+/// Check if Ctrl-C has been pressed since interrupts were caught
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,361 @@
+use super::error::CgnCliError;
+use super::interrupt::{register_temp_path, unregister_temp_path};
+#[cfg(feature = "network")]
+use super::progress::bytes_progress_bar;
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The path used to refer to stdin or stdout
+pub const STDIO_PATH: &str = "-";
+
+/// The extension of zstd compressed inputs, as used for Lichess databases
+const ZSTD_EXTENSION: &str = ".zst";
+
+/// The extension of gzip compressed inputs
+const GZIP_EXTENSION: &str = ".gz";
+
+/// The extension of bzip2 compressed inputs, as used for some older Lichess databases
+const BZIP2_EXTENSION: &str = ".bz2";
+
+/// Check if an input path is an `http://` or `https://` URL to download rather than a file
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Get an input path without the query or fragment of a URL, so its extension and name can be read from it
+pub fn strip_url_query(path: &str) -> &str {
+    match is_url(path) {
+        true => path.split(['?', '#']).next().unwrap_or(path),
+        false => path,
+    }
+}
+
+/// Check if an input path has the extension of a compression format that is decompressed when read
+fn is_compressed_input(path: &str) -> bool {
+    let path = strip_url_query(path);
+    [ZSTD_EXTENSION, GZIP_EXTENSION, BZIP2_EXTENSION]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+/// Get a file path without the extension of the compression format it is decompressed from when read, if it has one,
+/// such as `games.pgn` for `games.pgn.zst`
+pub fn strip_compression_extension(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    [ZSTD_EXTENSION, GZIP_EXTENSION, BZIP2_EXTENSION]
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .map_or_else(|| path.into(), |name| path.with_file_name(name))
+}
+
+/// Wrap a reader of an input in a decoder for the compression format of its extension, if it has one
+fn decoding_reader<R: Read + Send + 'static>(
+    path: &str,
+    reader: R,
+) -> io::Result<Box<dyn Read + Send>> {
+    let path = strip_url_query(path);
+    if path.ends_with(ZSTD_EXTENSION) {
+        Ok(Box::new(zstd::Decoder::new(reader)?))
+    } else if path.ends_with(GZIP_EXTENSION) {
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(reader)))
+    } else if path.ends_with(BZIP2_EXTENSION) {
+        Ok(Box::new(bzip2::read::MultiBzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Open a file for reading, transparently decompressing it if it ends in `.zst`, `.gz`, or `.bz2`
+pub fn open_file_reader(path: &str) -> io::Result<Box<dyn Read + Send>> {
+    log::debug!("Opening {} for reading", path);
+    decoding_reader(path, File::open(path)?)
+}
+
+/// Download a URL as it is read, following redirects and showing the progress of the download on the terminal
+/// unless quiet. URLs whose path ends in `.zst`, `.gz`, or `.bz2` are decompressed as they are downloaded.
+/// Requires the `network` feature.
+#[cfg(feature = "network")]
+pub fn open_url_reader(url: &str) -> io::Result<Box<dyn Read + Send>> {
+    log::debug!("Downloading {}", url);
+    let response = ureq::get(url).call().map_err(io::Error::other)?;
+    let len = response
+        .headers()
+        .get("content-length")
+        .and_then(|len| len.to_str().ok()?.parse::<u64>().ok());
+    let progress = bytes_progress_bar(len, true);
+    let body = response
+        .into_body()
+        .into_with_config()
+        .limit(u64::MAX)
+        .reader();
+    decoding_reader(url, progress.wrap_read(body))
+}
+
+/// Download a URL as it is read. Requires the `network` feature.
+#[cfg(not(feature = "network"))]
+pub fn open_url_reader(url: &str) -> io::Result<Box<dyn Read + Send>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} cannot be downloaded without the network feature", url),
+    ))
+}
+
+/// Open a file for reading, stdin if the path is `-`, or a download if it is an `http://` or `https://` URL.
+/// Inputs ending in `.zst`, `.gz`, or `.bz2` are decompressed as they are read.
+pub fn open_reader(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == STDIO_PATH {
+        log::debug!("Reading from stdin");
+        Ok(Box::new(io::stdin().lock()))
+    } else if is_url(path) {
+        Ok(open_url_reader(path)?)
+    } else {
+        Ok(open_file_reader(path)?)
+    }
+}
+
+/// Open several inputs as a single reader, reading each in order with a newline between them
+pub fn open_readers(paths: &[String]) -> io::Result<Box<dyn Read>> {
+    let mut reader = open_reader(&paths[0])?;
+    for path in &paths[1..] {
+        reader = Box::new(reader.chain(&b"\n"[..]).chain(open_reader(path)?));
+    }
+    Ok(reader)
+}
+
+/// The number of temporary paths created so far, numbering the next one
+static TEMP_PATHS_CREATED: AtomicU64 = AtomicU64::new(0);
+
+/// An output being written to stdout, discarded, or written to a temporary file that is only renamed over
+/// the output path once it is finished, so the output path never holds a partly written file
+pub struct Output {
+    /// The writer of stdout or a sink, unused when writing a file
+    writer: Box<dyn Write>,
+    /// The temporary file being written, its path, and the output path it is renamed to once finished
+    file: Option<(File, PathBuf, PathBuf)>,
+}
+
+impl Output {
+    /// Create the temporary file for an output path
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let temp_path = temp_path(path);
+        log::debug!(
+            "Creating {} for writing through {}",
+            path.display(),
+            temp_path.display()
+        );
+        let file = File::create(&temp_path)?;
+        register_temp_path(&temp_path);
+        Ok(Output {
+            writer: Box::new(io::sink()),
+            file: Some((file, temp_path, path.into())),
+        })
+    }
+
+    /// Create an output that discards everything written to it
+    pub fn sink() -> Self {
+        Output {
+            writer: Box::new(io::sink()),
+            file: None,
+        }
+    }
+
+    /// Flush the output and, if it is a file, sync it to disk and rename it over the output path, so a crash never
+    /// leaves the output path holding a partly written file. If syncing or renaming fails, the temporary file is
+    /// removed.
+    pub fn finish(mut self) -> io::Result<()> {
+        let Some((mut file, temp_path, path)) = self.file.take() else {
+            return self.writer.flush();
+        };
+        let result = file.flush().and_then(|()| file.sync_all());
+        // close the file first, as an open file cannot be renamed on every platform
+        drop(file);
+        let result = result.and_then(|()| fs::rename(&temp_path, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        unregister_temp_path(&temp_path);
+        result
+    }
+}
+
+impl Write for Output {
+    /// Write to the output
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.file {
+            Some((file, ..)) => file.write(buf),
+            None => self.writer.write(buf),
+        }
+    }
+
+    /// Flush the output, without renaming a file over the output path
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some((file, ..)) => file.flush(),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl Drop for Output {
+    /// Remove the temporary file of an output that was never finished, such as after a write error
+    fn drop(&mut self) {
+        if let Some((file, temp_path, _)) = self.file.take() {
+            drop(file);
+            let _ = fs::remove_file(&temp_path);
+            unregister_temp_path(&temp_path);
+        }
+    }
+}
+
+/// Create a file for writing, or stdout if the path is `-`. The file must be finished to appear at the path.
+/// Rust never translates newlines on stdout, so binary data is written unchanged on all platforms.
+pub fn open_writer(path: &str) -> io::Result<Output> {
+    if path == STDIO_PATH {
+        log::debug!("Writing to stdout");
+        Ok(Output {
+            writer: Box::new(io::stdout().lock()),
+            file: None,
+        })
+    } else {
+        Output::create(Path::new(path))
+    }
+}
+
+/// Get a hidden temporary path next to a path, so renaming it over the path stays on the same file system.
+/// Each temporary path is numbered, so outputs written at the same time never share one.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let number = TEMP_PATHS_CREATED.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, process::id(), number))
+}
+
+/// Write a file through a temporary file renamed over the path once it is complete,
+/// so the path never holds a partly written file. The temporary file is removed if writing fails.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut output = Output::create(path)?;
+    output.write_all(contents)?;
+    output.finish()
+}
+
+/// Parse a size in bytes, with an optional `K`, `M`, `G`, or `T` suffix for powers of 1024
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+        Some((i, 'T' | 't')) => (&s[..i], 1 << 40),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| {
+            format!(
+                "Invalid size '{}', expected a number of bytes such as 500M or 2G",
+                s
+            )
+        })
+}
+
+/// Check an output path can be written to, refusing to overwrite an existing file unless `force` is set
+pub fn check_overwrite(path: &Path, force: bool) -> Result<(), CgnCliError> {
+    if !force && path != Path::new(STDIO_PATH) && path.exists() {
+        return Err(CgnCliError::OutputExists(path.into()));
+    }
+    Ok(())
+}
+
+/// Get the length in bytes of an input, if it is an uncompressed file rather than stdin or a URL
+pub fn input_len(path: &str) -> Option<u64> {
+    if path == STDIO_PATH || is_url(path) || is_compressed_input(path) {
+        None
+    } else {
+        fs::metadata(path).ok().map(|metadata| metadata.len())
+    }
+}
+
+/// Get the total length in bytes of several inputs read with `open_readers`, if every length is known
+pub fn inputs_len(paths: &[String]) -> Option<u64> {
+    let separators = paths.len() as u64 - 1;
+    paths
+        .iter()
+        .map(|path| input_len(path))
+        .sum::<Option<u64>>()
+        .map(|len| len + separators)
+}
+
+/// A reader or writer that counts the bytes passing through it
+pub struct Counting<T> {
+    inner: T,
+    pub count: u64,
+}
+
+impl<T> Counting<T> {
+    /// Wrap a reader or writer, starting the count at zero
+    pub fn new(inner: T) -> Self {
+        Counting { inner, count: 0 }
+    }
+
+    /// Get the wrapped reader or writer back
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    /// Read from the inner reader, counting the bytes read
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// A reader that counts the bytes read through it into a shared count, which can still be read once the reader is
+/// wrapped in another that rewrites what it reads
+pub struct SharedCounting<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R> SharedCounting<R> {
+    /// Wrap a reader, returning it with the count it adds to
+    pub fn new(inner: R) -> (Self, Rc<Cell<u64>>) {
+        let count = Rc::new(Cell::new(0));
+        let reader = SharedCounting {
+            inner,
+            count: Rc::clone(&count),
+        };
+        (reader, count)
+    }
+}
+
+impl<R: Read> Read for SharedCounting<R> {
+    /// Read from the inner reader, counting the bytes read
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    /// Write to the inner writer, counting the bytes written
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    /// Flush the inner writer
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
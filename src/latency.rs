@@ -0,0 +1,57 @@
+use super::algorithm::Algorithm;
+use super::archive::{compress_stream, decompress, decompress_records};
+use super::error::CgnCliError;
+use super::header::{read_header, Extensions};
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// The time from starting to decompress an archive of the benchmarked games to recovering its first game, in seconds,
+/// for an archive that must be decompressed as a whole and one with a record per game
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FirstGameLatency {
+    pub monolithic: f64,
+    pub framed: f64,
+}
+
+impl Display for FirstGameLatency {
+    /// Display the latency of each archive and how many times faster the framed archive is
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Time to first game: {} seconds monolithic, {} seconds framed ({:.1}x faster)",
+            self.monolithic,
+            self.framed,
+            self.monolithic / self.framed
+        )
+    }
+}
+
+/// Compress the games into a framed archive with the algorithm, and time how long it takes to recover its first game
+/// against how long it takes to decompress every game. A monolithic archive has to be decompressed in full before
+/// any of its games can be split off, so its latency is the time to decompress every game.
+pub fn first_game_latency(
+    games: &[String],
+    algorithm: Algorithm,
+) -> Result<FirstGameLatency, CgnCliError> {
+    let pgn_data = games.join("\n\n");
+    let mut file = Vec::new();
+    compress_stream(
+        pgn_data.as_bytes(),
+        &mut file,
+        algorithm,
+        &Extensions::default(),
+        false,
+    )?;
+    let (header, payload) = read_header(&file)?;
+
+    let start = Instant::now();
+    black_box(decompress(&header, payload)?);
+    let monolithic = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    black_box(decompress_records(&header, payload)?.next().transpose()?);
+    let framed = start.elapsed().as_secs_f64();
+    Ok(FirstGameLatency { monolithic, framed })
+}
@@ -0,0 +1,141 @@
+//! # CGN-CLI (Compressed Game Notation Command Line Interface)
+
+//! CGN-CLI is a simple command line interface for the CGN (Compressed Game Notation) library I created. It allows you to compress and decompress PGN files using the CGN library. It is designed to be fast, efficient, and flexible. It supports WASM compilation via wasm-pack, and contains 4 different compression algorithms to choose from.
+
+//! ## Algorithms (High to Low Compression Ratios --- Low to High Speed)
+//! 1) `opening-huffman` - A Huffman encoding algorithm that uses the huffman-encoding crate to compress the PGN data, but with an additional optimization for compressing common opening moves.
+//! 2) `dynamic-huffman` - A Huffman encoding algorithm that uses the huffman-encoding crate to compress the PGN data, but with a huffman tree that is updated dynamically as the data is compressed.
+//! 3) `huffman` - A Huffman encoding algorithm that uses a huffman-encoding crate to compress the PGN data.
+//! 4) `bincode` - A simple binary encoding algorithm that uses the bincode crate to serialize the PGN data into a binary format.
+
+//! ## Installation
+//! Ensure you have Rust installed on your system. Then run the following command:
+//! ```bash
+//! cargo install cgn-cli
+//! ```
+
+//! ## Getting Started
+//! ```bash
+//! cgn-cli --help
+//! ```
+
+//! ## Library
+//! The algorithms can also be used from Rust, with the same file header and algorithm detection as the command line.
+//! Games are decompressed in PGN reduced export format, so a game already in that format decompresses to itself:
+//! ```
+//! use cgn_cli::algorithm::Algorithm;
+//!
+//! let pgn_str = "[Event \"Casual game\"]\n[Site \"\"]\n[Date \"\"]\n[Round \"\"]\n[White \"\"]\n[Black \"\"]\n\
+//!     [Result \"1-0\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0";
+//! let compressed = cgn_cli::compress(pgn_str, Algorithm::OpeningHuffman).unwrap();
+//! assert_eq!(cgn_cli::decompress(&compressed).unwrap(), pgn_str);
+//! ```
+
+pub mod algorithm;
+pub mod archive;
+pub mod codec;
+pub mod error;
+pub mod header;
+
+// the modules behind the command line interface, public only so the binary can use them
+#[doc(hidden)]
+pub mod analyze;
+#[doc(hidden)]
+pub mod append;
+#[doc(hidden)]
+pub mod baseline;
+#[doc(hidden)]
+pub mod batch;
+#[doc(hidden)]
+pub mod benchmark;
+#[doc(hidden)]
+pub mod browse;
+#[doc(hidden)]
+pub mod cat;
+#[doc(hidden)]
+pub mod compare;
+#[doc(hidden)]
+pub mod compress;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod crypto;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod encoding;
+#[doc(hidden)]
+pub mod entropy;
+#[doc(hidden)]
+pub mod extract;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod framed;
+#[doc(hidden)]
+pub mod fsck;
+#[doc(hidden)]
+pub mod fuzz;
+#[doc(hidden)]
+pub mod genetic_algorithm;
+#[doc(hidden)]
+pub mod info;
+#[doc(hidden)]
+pub mod interrupt;
+#[doc(hidden)]
+pub mod io_util;
+#[doc(hidden)]
+pub mod latency;
+#[doc(hidden)]
+pub mod list;
+#[doc(hidden)]
+pub mod memory;
+#[doc(hidden)]
+pub mod merge;
+#[doc(hidden)]
+pub mod normalize;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod pgn_db;
+#[doc(hidden)]
+pub mod profile;
+#[doc(hidden)]
+pub mod progress;
+#[doc(hidden)]
+pub mod selftest;
+#[doc(hidden)]
+pub mod split;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod validate;
+#[doc(hidden)]
+pub mod verify;
+
+use algorithm::{Algorithm, AlgorithmChoice};
+use error::CgnCliError;
+use header::{resolve_header, write_extended_file, Extensions};
+
+/// Compress a PGN string with an algorithm into a complete CGN file, with a header recording the algorithm
+/// and a checksum of the payload
+pub fn compress(pgn_str: &str, algorithm: Algorithm) -> Result<Vec<u8>, CgnCliError> {
+    let compressed_pgn_data =
+        codec::compress(algorithm, pgn_str).map_err(CgnCliError::CompressionFailed)?;
+    let mut output = Vec::with_capacity(compressed_pgn_data.len() + 24);
+    write_extended_file(
+        &mut output,
+        algorithm,
+        0,
+        &Extensions::default(),
+        &compressed_pgn_data,
+    )?;
+    Ok(output)
+}
+
+/// Decompress a CGN file into a PGN string, detecting the algorithm and layout from its header.
+/// Files compressed with `--stream` or `--per-game` are decompressed into every game they hold.
+pub fn decompress(data: &[u8]) -> Result<String, CgnCliError> {
+    let (header, payload) = resolve_header(data, AlgorithmChoice::Auto)?;
+    archive::decompress(&header, payload)
+}
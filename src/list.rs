@@ -0,0 +1,52 @@
+use super::archive::{decompress, decompress_records, is_single_game};
+use super::error::CgnCliError;
+use super::filter::header_value;
+use super::header::read_header;
+use super::io_util::open_reader;
+use super::output;
+use super::pgn_db::PgnDbIter;
+use std::io::{self, BufReader, Read, Write};
+
+/// The headers printed for each game, in order
+const LISTED_HEADERS: [&str; 4] = ["White", "Black", "Date", "Result"];
+
+/// Describe a game by its index followed by its players, date, and result, using `?` for missing headers
+pub fn describe_game(index: usize, pgn_str: &str) -> String {
+    let [white, black, date, result] =
+        LISTED_HEADERS.map(|key| header_value(pgn_str, key).unwrap_or("?"));
+    format!("{}: {} vs {}, {}, {}", index, white, black, date, result)
+}
+
+/// Print the description of a game
+fn print_game(out: &mut impl Write, index: usize, pgn_str: &str) -> io::Result<()> {
+    writeln!(out, "{}", describe_game(index, pgn_str))
+}
+
+/// Print one line per game in a CGN file with its index, players, date, and result.
+/// Streamed and indexed files are decompressed one record at a time. Other files hold a single payload,
+/// which is only decompressed if `full` is set.
+pub fn list(input_path: &str, full: bool) -> Result<(), CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let (header, payload) = read_header(&compressed_data)?;
+
+    if !is_single_game(&header) {
+        return output::report(|out| {
+            for (i, pgn_str) in decompress_records(&header, payload)?.enumerate() {
+                print_game(out, i, &pgn_str?)?;
+            }
+            Ok(())
+        });
+    }
+
+    if !full {
+        return Err(CgnCliError::ListRequiresFull);
+    }
+    let pgn_data = decompress(&header, payload)?;
+    output::report(|out| {
+        for (i, pgn_str) in PgnDbIter::new(BufReader::new(pgn_data.as_bytes())).enumerate() {
+            print_game(out, i, &pgn_str)?;
+        }
+        Ok(())
+    })
+}
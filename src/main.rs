@@ -1,54 +1,108 @@
-//! # CGN-CLI (Compressed Game Notation Command Line Interface)
+//! The `cgn-cli` binary, a command line interface over the `cgn_cli` library
 
-//! CGN-CLI is a simple command line interface for the CGN (Compressed Game Notation) library I created. It allows you to compress and decompress PGN files using the CGN library. It is designed to be fast, efficient, and flexible. It supports WASM compilation via wasm-pack, and contains 4 different compression algorithms to choose from.
-
-//! ## Algorithms (High to Low Compression Ratios --- Low to High Speed)
-//! 1) `opening-huffman` - A Huffman encoding algorithm that uses the huffman-encoding crate to compress the PGN data, but with an additional optimization for compressing common opening moves.
-//! 2) `dynamic-huffman` - A Huffman encoding algorithm that uses the huffman-encoding crate to compress the PGN data, but with a huffman tree that is updated dynamically as the data is compressed.
-//! 3) `huffman` - A Huffman encoding algorithm that uses a huffman-encoding crate to compress the PGN data.
-//! 4) `bincode` - A simple binary encoding algorithm that uses the bincode crate to serialize the PGN data into a binary format.
-
-//! ## Installation
-//! Ensure you have Rust installed on your system. Then run the following command:
-//! ```bash
-//! cargo install cgn-cli
-//! ```
-
-//! ## Getting Started
-//! ```bash
-//! cgn-cli --help
-//! ```
-
-mod benchmark;
-use benchmark::{bench, ToTake};
-
-mod genetic_algorithm;
-use genetic_algorithm::{genetic_algorithm, GeneticAlgorithmConfig};
-
-use cgn::compression::bincode::{bincode_compress_pgn_str, bincode_decompress_pgn_str};
-use cgn::compression::dynamic_huffman::{
-    dynamic_huffman_compress_pgn_str, dynamic_huffman_decompress_pgn_str,
+use cgn_cli::algorithm::{Algorithm, AlgorithmChoice, AlgorithmSelection, Tuning};
+use cgn_cli::analyze::analyze;
+use cgn_cli::append::append;
+use cgn_cli::archive::{self, transcode};
+use cgn_cli::batch::{
+    compress_dir, compress_glob, compress_into_dir, is_glob, BatchOptions, OutputTemplate,
+    DEFAULT_OUTPUT_TEMPLATE,
 };
-use cgn::compression::huffman::{huffman_compress_pgn_str, huffman_decompress_pgn_str};
-use cgn::compression::opening_huffman::{
-    opening_huffman_compress_pgn_str, opening_huffman_decompress_pgn_str,
+use cgn_cli::benchmark::{
+    bench, compression_ratio, default_threads, resolve_threads, BenchConfig, BenchFormat,
+    BenchMetric, EcoGrouping, Priority, Repetitions, SampleMode, Sampling, ToTake,
 };
-use clap::{Parser, Subcommand};
-use std::fs::File;
+use cgn_cli::browse::browse;
+use cgn_cli::cat::cat;
+use cgn_cli::compare::compare;
+use cgn_cli::compress::{compress_input, warn_if_exists, CompressOptions, CompressSummary};
+use cgn_cli::config::{load_config, Config};
+use cgn_cli::crypto::{decrypt_payload, read_passphrase};
+use cgn_cli::diff::diff;
+use cgn_cli::encoding::Encoding;
+use cgn_cli::entropy::entropy;
+use cgn_cli::error::CgnCliError;
+use cgn_cli::extract::{count, extract};
+use cgn_cli::filter::{CommentFilter, GameFilter, TimeControlClass};
+use cgn_cli::framed::decompress_framed;
+use cgn_cli::fsck::fsck;
+use cgn_cli::genetic_algorithm::{genetic_algorithm, GeneticAlgorithmConfig, HistoryFormat};
+use cgn_cli::header::{parse_header, read_header, resolve_header, Header};
+use cgn_cli::info::info;
+use cgn_cli::io_util::{check_overwrite, open_reader, open_writer, parse_size, STDIO_PATH};
+use cgn_cli::list::list;
+use cgn_cli::merge::merge;
+use cgn_cli::normalize::StripComments;
+use cgn_cli::pgn_db::{count_db_games, GameWindow};
+use cgn_cli::split::split;
+use cgn_cli::stats::stats;
+use cgn_cli::validate::validate_pgn;
+use cgn_cli::verify::{verify, verify_checksum};
+use cgn_cli::{fuzz, memory, normalize, output, profile, selftest};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use env_logger::WriteStyle;
+use log::LevelFilter;
+use rayon::ThreadPoolBuilder;
+use std::ffi::OsStr;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// The exit codes listed at the end of the help text
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0    Success
+  1    Generic error
+  2    Invalid arguments
+  3    I/O error
+  4    Compression or decompression failure
+  5    Corrupt or unrecognized file
+  130  Interrupted by Ctrl-C";
 
 #[derive(Parser)]
-#[clap(name = "cgn-cli", version = "0.1.2", author = "Jaden S")]
+#[clap(name = "cgn-cli", version = "0.1.2", author = "Jaden S", after_help = EXIT_CODES_HELP)]
 /// A command line interface for the cgn library
 struct Args {
     #[clap(subcommand)]
     command: Commands,
+
+    /// Log more detail about what the tool is doing, repeated for more detail (-v info, -vv debug, -vvv trace)
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Config file to read default options from, instead of searching for cgn.toml in the current directory
+    /// and then $XDG_CONFIG_HOME/cgn/
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Hide status messages, summaries, and progress bars, printing only results, warnings, and errors
+    #[clap(short, long, global = true)]
+    quiet: bool,
+
+    /// Never color the output. Color is also turned off by setting the NO_COLOR environment variable
+    #[clap(long, global = true)]
+    no_color: bool,
+
+    /// Print how long the command spent reading, parsing, compressing, and writing to stderr once it finishes
+    #[clap(long, global = true)]
+    profile: bool,
+
+    /// Also write the timings of --profile as a folded stack file for flamegraph tools. Requires the profiling feature
+    #[clap(long, global = true, requires = "profile")]
+    profile_output: Option<PathBuf>,
+
+    /// Maximum number of threads for every command that works in parallel, such as bench, gen-algo, compressing
+    /// several files, and decompressing streamed or indexed files. Defaults to the number of logical CPUs, and 0 uses
+    /// all of them. Larger counts are capped at the number of logical CPUs
+    #[clap(long, global = true, default_value_t = default_threads())]
+    threads: usize,
 }
 
 #[derive(Subcommand)]
 /// The commands for the command line interface
 enum Commands {
-    /// Compress a single PGN file
+    /// Compress a PGN file, or several concatenated into one compressed file
     Compress {
         /// Optimization level (0-3)
         #[clap(short, default_value = "3", value_parser = |s: &str| match s.parse::<u8>() {
@@ -57,16 +111,285 @@ enum Commands {
         })]
         optimization_level: u8,
 
-        /// Input file path
+        /// Algorithm name, as an alternative to the optimization level, or 'best' to compress with every algorithm
+        /// and keep the smallest output. 'best' is the slowest option, as it runs all four algorithms
+        #[clap(long, conflicts_with = "optimization_level")]
+        algorithm: Option<AlgorithmSelection>,
+
+        /// Input file paths followed by the output path. Inputs may be '-' for stdin, an http:// or https:// URL to
+        /// download with the network feature, or a single glob pattern matching several files, in which case the
+        /// output is a directory. Several input files are concatenated in order,
+        /// with a newline between them, and compressed together. The output may be '-' for stdout.
+        /// With --output-dir, every path is an input compressed into its own file
+        #[clap(value_parser, required = true, num_args = 1..)]
+        paths: Vec<String>,
+
+        /// Print only the compressed size in bytes to stdout, without writing an output file.
+        /// Every path is treated as an input, so no output path may be given
+        #[clap(long, conflicts_with = "dry_run")]
+        stdout_size: bool,
+
+        /// Decompress the result in memory and check it matches the input before writing the output
+        #[clap(long)]
+        verify: bool,
+
+        /// Show the progress of reading the input
+        #[clap(long)]
+        progress: bool,
+
+        /// Compress a multi-game database one game at a time, holding only a single game in memory
+        #[clap(long)]
+        stream: bool,
+
+        /// Compress each game in a multi-game database separately behind an index, so games can be extracted individually
+        #[clap(long, conflicts_with = "stream")]
+        per_game: bool,
+
+        /// Largest input to read into memory, in bytes or with a K, M, G, or T suffix. Streamed inputs are not limited
+        #[clap(long, default_value = "4G", value_parser = parse_size)]
+        max_input_size: u64,
+
+        /// Height of the Gaussian function that adjusts the Huffman tree of dynamic-huffman, as found by gen-algo.
+        /// Stored in the header so decompression uses the same value
+        #[clap(long, requires = "dev", conflicts_with_all = ["stream", "per_game"])]
+        height: Option<f64>,
+
+        /// Deviation of the Gaussian function that adjusts the Huffman tree of dynamic-huffman, as found by gen-algo.
+        /// Stored in the header so decompression uses the same value
+        #[clap(long, requires = "height")]
+        dev: Option<f64>,
+
+        /// Compress the input in memory and report the compressed size without writing the output
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Overwrite the output if it already exists
+        #[clap(short, long)]
+        force: bool,
+
+        /// Keep compressing the remaining files after a failure, listing every failure at the end
+        #[clap(long)]
+        keep_going: bool,
+
+        /// Store the modification time of each input file in its compressed file and give the compressed file the same
+        /// modification time. Decompressing restores the stored time to the output
+        #[clap(long)]
+        preserve_times: bool,
+
+        /// Leave out the time of creation and the version of the tool that are otherwise stored in the header, so
+        /// compressing the same input always gives the same output
+        #[clap(long)]
+        no_metadata: bool,
+
+        /// Name of each compressed file when compressing several files, with {stem}, {ext}, {algo}, and {parent}
+        /// expanded from the input file
+        #[clap(long, default_value = DEFAULT_OUTPUT_TEMPLATE)]
+        output_template: OutputTemplate,
+
+        /// Encrypt the compressed payload with AES-256-GCM under a passphrase read from CGN_PASSPHRASE, or prompted for.
+        /// Requires the encryption feature
+        #[clap(long, conflicts_with = "stream")]
+        encrypt: bool,
+
+        /// Skip this many games at the start of the input before compressing, reading the input as a PGN database
+        #[clap(long, default_value = "0")]
+        skip: usize,
+
+        /// Compress at most this many games, after any skipped with --skip
+        #[clap(long)]
+        take: Option<usize>,
+
+        /// Compress every path as a separate input into this directory, in parallel. Glob patterns and plain files are
+        /// written inside it, and directories are mirrored under it
+        #[clap(long, conflicts_with = "stdout_size")]
+        output_dir: Option<PathBuf>,
+
+        /// Text encoding of the input. A UTF-8 byte order mark is dropped, and 'auto' reads the input as UTF-8,
+        /// falling back to Latin-1 from the first byte that is not valid UTF-8
+        #[clap(long, value_enum, default_value = "utf-8")]
+        encoding: Encoding,
+
+        /// Rewrite each game in a canonical form before compressing, with the standard tags first and the rest
+        /// sorted, whitespace normalized, and a consistent result. The output is marked as normalized, as it no
+        /// longer decompresses to the exact input
+        #[clap(long)]
+        normalize: bool,
+
+        /// Strip annotations from the comments of every game before compressing, such as the [%clk] and [%eval]
+        /// commands of Lichess games, rewriting the games in the normalized form. The size of the input is reported
+        /// both before and after stripping
+        #[clap(long, value_enum, default_value = "none")]
+        strip_comments: StripComments,
+
+        /// Close the streamed output and start the next numbered one, such as output-000.cgn, once this many games
+        /// are written to it, like log rotation. The output path is the prefix of the numbered files
+        #[clap(long, requires = "stream", value_parser = clap::value_parser!(u32).range(1..))]
+        max_games_per_archive: Option<u32>,
+
+        /// Write each game as soon as it is compressed as a bare frame, its length as a little-endian u32 followed by
+        /// its bytes, with no header or index, for piping into another process. The output is usually '-', and is read
+        /// back with decompress --framed and the same algorithm
+        #[clap(long, conflicts_with_all = ["stream", "encrypt", "height"])]
+        framed_stdout: bool,
+    },
+    /// Recursively compress every PGN file in a directory, mirroring the directory structure in the output
+    CompressDir {
+        /// Optimization level (0-3)
+        #[clap(short, default_value = "3", value_parser = |s: &str| match s.parse::<u8>() {
+            Ok(n) if n <= 3 => Ok(n),
+            _ => Err(String::from("Optimization level must be between 0 and 3")),
+        })]
+        optimization_level: u8,
+
+        /// Algorithm name, as an alternative to the optimization level
+        #[clap(long, value_enum, conflicts_with = "optimization_level")]
+        algorithm: Option<Algorithm>,
+
+        /// Input directory path
         #[clap(value_parser)]
-        input_path: String,
+        input_dir: PathBuf,
 
-        /// Output file path
+        /// Output directory path
         #[clap(value_parser)]
-        output_path: String,
+        output_dir: PathBuf,
+
+        /// Overwrite existing files in the output directory
+        #[clap(short, long)]
+        force: bool,
+
+        /// Keep compressing the remaining files after a failure, listing every failure at the end
+        #[clap(long)]
+        keep_going: bool,
+
+        /// Check that each compressed file decompresses back to its input before writing it
+        #[clap(long)]
+        verify: bool,
+
+        /// Largest input file to read into memory, in bytes or with a K, M, G, or T suffix
+        #[clap(long, default_value = "4G", value_parser = parse_size)]
+        max_input_size: u64,
+
+        /// Store the modification time of each input file in its compressed file and give the compressed file the same
+        /// modification time. Decompressing restores the stored time to the output
+        #[clap(long)]
+        preserve_times: bool,
+
+        /// Leave out the time of creation and the version of the tool that are otherwise stored in the header, so
+        /// compressing the same input always gives the same output
+        #[clap(long)]
+        no_metadata: bool,
+
+        /// Name of each compressed file when compressing several files, with {stem}, {ext}, {algo}, and {parent}
+        /// expanded from the input file
+        #[clap(long, default_value = DEFAULT_OUTPUT_TEMPLATE)]
+        output_template: OutputTemplate,
+
+        /// Text encoding of the input. A UTF-8 byte order mark is dropped, and 'auto' reads the input as UTF-8,
+        /// falling back to Latin-1 from the first byte that is not valid UTF-8
+        #[clap(long, value_enum, default_value = "utf-8")]
+        encoding: Encoding,
+
+        /// Rewrite each game in a canonical form before compressing, with the standard tags first and the rest
+        /// sorted, whitespace normalized, and a consistent result. The output is marked as normalized, as it no
+        /// longer decompresses to the exact input
+        #[clap(long)]
+        normalize: bool,
     },
     /// Decompress a single PGN file
     Decompress {
+        /// Optimization level (0-3), or 'auto' to read the algorithm from the file header
+        #[clap(short, default_value = "auto", value_parser)]
+        optimization_level: AlgorithmChoice,
+
+        /// Algorithm name, as an alternative to the optimization level
+        #[clap(long, value_enum, conflicts_with = "optimization_level")]
+        algorithm: Option<Algorithm>,
+
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Output file path, or '-' for stdout
+        #[clap(value_parser)]
+        output_path: String,
+
+        /// Parse the decompressed output and check every game is valid PGN before writing it
+        #[clap(long)]
+        validate: bool,
+
+        /// Overwrite the output if it already exists
+        #[clap(short, long)]
+        force: bool,
+
+        /// When the output path is a directory, write to the original file name stored in the header inside it
+        #[clap(long)]
+        restore_name: bool,
+
+        /// Decrypt an encrypted file with a passphrase read from CGN_PASSPHRASE, or prompted for.
+        /// Requires the encryption feature
+        #[clap(long)]
+        decrypt: bool,
+
+        /// Read the input as a framed stream written by compress --framed-stdout, writing each game as its frame
+        /// arrives. The stream does not record its algorithm, so it must be given with -o or --algorithm
+        #[clap(long, conflicts_with_all = ["validate", "restore_name", "decrypt"])]
+        framed: bool,
+
+        /// Reformat the games for reading: tags in a consistent order, one space between moves, movetext wrapped at 80
+        /// columns, and a blank line between games. Without it, the games are written exactly as they were stored
+        #[clap(long, conflicts_with = "framed")]
+        pretty: bool,
+    },
+    /// Decompress compressed files to stdout, reading the algorithm of each from its header
+    Cat {
+        /// Input file paths, or '-' for stdin, decompressed one after another
+        #[clap(value_parser, required = true)]
+        input_paths: Vec<String>,
+
+        /// Reformat the games for reading: tags in a consistent order, one space between moves, movetext wrapped at 80
+        /// columns, and a blank line between games
+        #[clap(long)]
+        pretty: bool,
+    },
+    /// Re-compress a compressed file with a different algorithm, without writing the intermediate PGN
+    Transcode {
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Output file path, or '-' for stdout
+        #[clap(value_parser)]
+        output_path: String,
+
+        /// Algorithm to re-compress the file with
+        #[clap(value_enum)]
+        to_algorithm: Algorithm,
+
+        /// Overwrite the output if it already exists, and transcode even if the file is already compressed with the target algorithm
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Compress the games in a PGN file and add them to a file compressed with --stream or --per-game
+    Append {
+        /// Input PGN file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Compressed file to add the games to
+        #[clap(value_parser)]
+        archive_path: String,
+
+        /// Algorithm the file is expected to be compressed with. Appending fails if the file uses a different algorithm,
+        /// in which case transcode it first
+        #[clap(long, value_enum)]
+        algorithm: Option<Algorithm>,
+
+        /// Check that each new game decompresses back to the input before adding it
+        #[clap(long)]
+        verify: bool,
+    },
+    /// Split the games of a PGN file into numbered streamed files, such as prefix-000.cgn, each decompressible on its own
+    Split {
         /// Optimization level (0-3)
         #[clap(short, default_value = "3", value_parser = |s: &str| match s.parse::<u8>() {
             Ok(n) if n <= 3 => Ok(n),
@@ -74,35 +397,347 @@ enum Commands {
         })]
         optimization_level: u8,
 
-        /// Input file path
+        /// Algorithm name, as an alternative to the optimization level
+        #[clap(long, value_enum, conflicts_with = "optimization_level")]
+        algorithm: Option<Algorithm>,
+
+        /// Input PGN file path, or '-' for stdin
         #[clap(value_parser)]
         input_path: String,
 
-        /// Output file path
+        /// Number of files to split the games into
+        #[clap(value_parser = clap::value_parser!(u32).range(1..))]
+        parts: u32,
+
+        /// Prefix of the output paths, which are numbered from prefix-000.cgn
+        #[clap(value_parser)]
+        output_prefix: String,
+
+        /// Give each file the next run of games in order, rather than dealing the games to the files in turn.
+        /// The input is read twice, once to count its games, so it cannot be stdin
+        #[clap(long)]
+        contiguous: bool,
+
+        /// Overwrite the output files if they already exist
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Merge the games of several files compressed with --stream or --per-game into one indexed file, without
+    /// re-compressing them
+    Merge {
+        /// Compressed files to merge, in order, all compressed with the same algorithm
+        #[clap(value_parser, required = true)]
+        input_paths: Vec<String>,
+
+        /// Output file path, or '-' for stdout
         #[clap(value_parser)]
         output_path: String,
+
+        /// Overwrite the output if it already exists
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Check every game of a file compressed with --stream or --per-game against the checksum of its record and that
+    /// it decompresses, reporting the index of each corrupt game. With --salvage, the intact games are written to a
+    /// new file and the lost games listed beside it
+    Fsck {
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Write the intact games to this file, compressed with --per-game, with the index and reason of each lost game
+        /// listed in the same path with '.lost' added
+        #[clap(long)]
+        salvage: Option<String>,
+
+        /// Overwrite the salvaged file and its list of lost games if they already exist
+        #[clap(short, long, requires = "salvage")]
+        force: bool,
+    },
+    /// Decompress a single game from a compressed file, using the index of files compressed with --per-game
+    Extract {
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Index of the game to extract, starting from 0
+        #[clap(value_parser, required_unless_present = "count")]
+        game_index: Option<usize>,
+
+        /// Output file path, or '-' for stdout
+        #[clap(value_parser, required_unless_present = "count")]
+        output_path: Option<String>,
+
+        /// Print the number of games in the file instead of extracting one
+        #[clap(long)]
+        count: bool,
+
+        /// Overwrite the output if it already exists
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Browse the games of a compressed file in an interactive terminal interface, showing the PGN of the selected game
+    Browse {
+        /// Input file path
+        #[clap(value_parser)]
+        input_path: String,
+    },
+    /// Print the index, players, date, and result of each game in a compressed file
+    List {
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Decompress the whole file to list its games if it was not compressed with --stream or --per-game
+        #[clap(long)]
+        full: bool,
+    },
+    /// Print the algorithm, format version, and sizes of a compressed file
+    Info {
+        /// Input file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+    },
+    /// Check that a compressed file decompresses to exactly the original PGN file
+    Verify {
+        /// Compressed file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// Original PGN file path
+        #[clap(value_parser, required_unless_present = "checksum_only")]
+        original_path: Option<String>,
+
+        /// Only check the payload against the checksum in the header, without decompressing it
+        #[clap(long)]
+        checksum_only: bool,
     },
     /// Benchmark the compression and decompression algorithms against a Lichess PGN database
     Bench {
-        /// Number of games to benchmark each algorithm on. If set to 'all', then all games in the database will be used
+        /// Number of games to benchmark each algorithm on. If set to 'all', then all games in the database will be used.
+        /// A percentage such as '10%' takes that fraction of the games, at the cost of a pass to count them first
         #[clap(value_parser)]
         number_of_games: ToTake,
 
-        /// Input database path (Lichess PGN database format required)
+        /// Input database path (Lichess PGN database format required), or a directory of PGN files read as one database
         #[clap(value_parser)]
         input_db_path: String,
 
-        /// Optional output file path for the benchmark results
+        /// Optional file to also write the benchmark results to, as CSV, JSON, or Markdown if it ends in '.csv',
+        /// '.json', or '.md', and as the metrics of every game appended to it otherwise
         #[clap(value_parser)]
         output_path: Option<String>,
+
+        /// Format to print the benchmark results in
+        #[clap(long, value_enum, default_value = "human")]
+        format: BenchFormat,
+
+        /// Only benchmark games where both players are rated at least this Elo
+        #[clap(long)]
+        min_elo: Option<u32>,
+
+        /// Only benchmark games where both players are rated at most this Elo
+        #[clap(long)]
+        max_elo: Option<u32>,
+
+        /// Only benchmark games played with this class of time control
+        #[clap(long, value_enum)]
+        time_control: Option<TimeControlClass>,
+
+        /// Only benchmark games with at least this many moves, counting a move by each player as one move
+        #[clap(long)]
+        min_moves: Option<usize>,
+
+        /// Only benchmark games with at most this many moves, counting a move by each player as one move
+        #[clap(long)]
+        max_moves: Option<usize>,
+
+        /// Only benchmark games with comments or variations, or only games without them
+        #[clap(long, value_enum, default_value = "any")]
+        comments: CommentFilter,
+
+        /// Report the peak heap usage of each algorithm, running them one at a time (requires the measure-memory feature)
+        #[clap(long)]
+        measure_memory: bool,
+
+        /// How to choose the games to benchmark. Sequential takes the first games and is fastest, while random
+        /// picks games uniformly from the whole database in a single pass
+        #[clap(long, value_enum, default_value = "sequential")]
+        sample: SampleMode,
+
+        /// Seed for random sampling, so the same games can be benchmarked again. Chosen at random if not given
+        #[clap(long)]
+        seed: Option<u64>,
+
+        /// Instead of collecting metrics, decompress randomly corrupted copies of each game and report how many
+        /// corruptions caused panics, clean errors, or silently wrong output
+        #[clap(long, conflicts_with_all = ["output_path", "format", "measure_memory"])]
+        fuzz_robustness: bool,
+
+        /// Recommend the algorithm that best suits --priority after printing the results
+        #[clap(long, conflicts_with = "fuzz_robustness")]
+        recommend: bool,
+
+        /// What to favor when recommending an algorithm: the smallest size, the fastest speed, or a balance of both
+        #[clap(long, value_enum, default_value = "balanced", requires = "recommend")]
+        priority: Priority,
+
+        /// Number of runs of each algorithm to discard before timing, to warm up caches
+        #[clap(long, default_value_t = 0, conflicts_with = "fuzz_robustness")]
+        warmup: usize,
+
+        /// Number of timed runs of each algorithm, reporting the mean and standard deviation of the timings
+        #[clap(long, default_value = "1", conflicts_with = "fuzz_robustness", value_parser = |s: &str| match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(n),
+            _ => Err(String::from("Iterations must be at least 1")),
+        })]
+        iterations: usize,
+
+        /// Stop the benchmark with an error at the first game an algorithm fails to compress or round-trip
+        #[clap(long, conflicts_with = "fuzz_robustness")]
+        strict: bool,
+
+        /// Certify every algorithm as lossless on the games: each game must decompress to exactly the bytes of the
+        /// original in PGN reduced export format, after stripping any --strip-comments annotations. The benchmark
+        /// stops at the first game that does not, printing what it should and did decompress to
+        #[clap(long, conflicts_with = "fuzz_robustness")]
+        strict_roundtrip: bool,
+
+        /// Also report each algorithm's compression ratio for games of 0-20, 21-40, 41-80, and 81+ plies
+        #[clap(long, conflicts_with = "fuzz_robustness")]
+        ratio_by_length: bool,
+
+        /// Also report each algorithm's compression ratio for the games of each opening, grouped by the ECO tag of the
+        /// games or by its family from A to E, listing the best and worst compressed openings
+        #[clap(long, value_enum, conflicts_with = "fuzz_robustness")]
+        by_eco: Option<EcoGrouping>,
+
+        /// Also draw a histogram of the compressed sizes of the games with this algorithm, scaled to the terminal width
+        #[clap(long, value_enum, conflicts_with = "fuzz_robustness")]
+        histogram: Option<Algorithm>,
+
+        /// Also measure an extra metric for each algorithm. 'first-game-latency' times how long an archive of the games
+        /// with a record per game takes to recover its first game, against an archive compressed as a whole, which
+        /// must decompress every game first
+        #[clap(long, value_enum, conflicts_with = "fuzz_robustness")]
+        metric: Option<BenchMetric>,
+
+        /// Strip annotations from the comments of every game before it is measured, such as the [%clk] and [%eval]
+        /// commands of Lichess games, and report each algorithm's compression ratio against the games both with and
+        /// without them
+        #[clap(
+            long,
+            value_enum,
+            default_value = "none",
+            conflicts_with = "fuzz_robustness"
+        )]
+        strip_comments: StripComments,
+
+        /// Compare each algorithm's compression ratio and timings against a previous JSON benchmark report, failing
+        /// if any of them got worse by more than --tolerance or --time-tolerance
+        #[clap(long, conflicts_with = "fuzz_robustness")]
+        baseline: Option<String>,
+
+        /// Percentage the compression ratio may get worse than the baseline by before it counts as a regression
+        #[clap(long, default_value_t = 5.0, requires = "baseline")]
+        tolerance: f64,
+
+        /// Percentage a timing may get worse than the baseline by before it counts as a regression. Timings also
+        /// have to get worse by more than 0.1ms per game, as smaller changes are noise
+        #[clap(long, default_value_t = 25.0, requires = "baseline")]
+        time_tolerance: f64,
+    },
+    /// Compare the games of two compressed files, reporting how many are only in one of them or differ.
+    /// Games are matched by their Event, Site, Date, Round, White, and Black tags, and compared by their moves and
+    /// result. With --verbose, the indices of those games are listed too
+    Diff {
+        /// First compressed file path, or '-' for stdin
+        #[clap(value_parser)]
+        archive_a: String,
+
+        /// Second compressed file path
+        #[clap(value_parser)]
+        archive_b: String,
+    },
+    /// Compress a PGN file with two algorithms and report which produces the smaller output
+    Compare {
+        /// Input PGN file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+
+        /// First algorithm to compare
+        #[clap(value_enum)]
+        algo_a: Algorithm,
+
+        /// Second algorithm to compare
+        #[clap(value_enum)]
+        algo_b: Algorithm,
+    },
+    /// Compute compression statistics for a Lichess PGN database using a single algorithm
+    Stats {
+        /// Optimization level (0-3)
+        #[clap(short, default_value = "3", value_parser = |s: &str| match s.parse::<u8>() {
+            Ok(n) if n <= 3 => Ok(n),
+            _ => Err(String::from("Optimization level must be between 0 and 3")),
+        })]
+        optimization_level: u8,
+
+        /// Algorithm name, as an alternative to the optimization level
+        #[clap(long, value_enum, conflicts_with = "optimization_level")]
+        algorithm: Option<Algorithm>,
+
+        /// Number of games to compress. If set to 'all', then all games in the database will be used.
+        /// A percentage such as '10%' takes that fraction of the games, at the cost of a pass to count them first
+        #[clap(value_parser)]
+        number_of_games: ToTake,
+
+        /// Input database path (Lichess PGN database format required)
+        #[clap(value_parser)]
+        input_db_path: String,
+    },
+    /// Print the number of games in a PGN database, counting the lines that start a game without parsing or holding
+    /// the games in memory
+    CountGames {
+        /// Input database path, either a PGN file or a directory of them. Files ending in .zst, .gz, or .bz2 are
+        /// decompressed as they are read
+        #[clap(value_parser)]
+        input_db_path: String,
+    },
+    /// Report the average compressed bits per move in the opening, middlegame, and endgame of the games in a PGN file
+    Analyze {
+        /// Optimization level (0-3)
+        #[clap(short, default_value = "3", value_parser = |s: &str| match s.parse::<u8>() {
+            Ok(n) if n <= 3 => Ok(n),
+            _ => Err(String::from("Optimization level must be between 0 and 3")),
+        })]
+        optimization_level: u8,
+
+        /// Algorithm name, as an alternative to the optimization level
+        #[clap(long, value_enum, conflicts_with = "optimization_level")]
+        algorithm: Option<Algorithm>,
+
+        /// Input PGN file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
+    },
+    /// Print the Shannon entropy of the moves in a PGN file, the size it implies for them, and the bits per move
+    /// each algorithm achieves on the same moves
+    Entropy {
+        /// Input PGN file path, or '-' for stdin
+        #[clap(value_parser)]
+        input_path: String,
     },
     /// Run a genetic algorithm to find the optimal height and dev values for the dynamic Huffman compression algorithm. Used during development.
     GenAlgo {
-        /// Initial population size
-        #[clap(value_parser)]
+        /// Initial population size, at least 2 so parents can be chosen for crossover
+        #[clap(value_parser = |s: &str| match s.parse::<usize>() {
+            Ok(n) if n >= 2 => Ok(n),
+            _ => Err(String::from("Initial population must be at least 2")),
+        })]
         init_population: usize,
 
-        /// Number of games to benchmark each individual on. If set to 'all', then all games in the database will be used
+        /// Number of games to benchmark each individual on. If set to 'all', then all games in the database will be used.
+        /// A percentage such as '10%' takes that fraction of the games, at the cost of a pass to count them first
         #[clap(value_parser)]
         number_of_games: ToTake,
 
@@ -114,8 +749,11 @@ enum Commands {
         #[clap(value_parser = |s: &str| s.parse::<f64>().map(|n| n.clamp(0.0, 1.0)))]
         mutation_rate: f64,
 
-        /// Tournament size
-        #[clap(value_parser)]
+        /// Tournament size, at least 1
+        #[clap(value_parser = |s: &str| match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(n),
+            _ => Err(String::from("Tournament size must be at least 1")),
+        })]
         tournament_size: usize,
 
         /// Minimum height value
@@ -141,79 +779,744 @@ enum Commands {
         /// Output file path for the genetic algorithm results
         #[clap(value_parser)]
         output_path: String,
+
+        /// Seed for the random number generator, chosen at random and recorded in the output file if not given
+        #[clap(long)]
+        seed: Option<u64>,
+
+        /// Path to save the population and RNG state to, so an interrupted run can be resumed
+        #[clap(long)]
+        checkpoint: Option<String>,
+
+        /// Number of generations between checkpoints
+        #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        checkpoint_every: u32,
+
+        /// Path of a checkpoint to resume the run from
+        #[clap(long)]
+        resume: Option<String>,
+
+        /// Path to write the best and mean fitness, best height and dev, and diversity of each generation to
+        #[clap(long)]
+        history: Option<String>,
+
+        /// Format to write the history in
+        #[clap(long, value_enum, default_value = "json", requires = "history")]
+        history_format: HistoryFormat,
+
+        /// Stop early if the best fitness has not improved by more than --min-delta in this many generations
+        #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+        patience: Option<u32>,
+
+        /// Smallest decrease in the best fitness counted as an improvement by --patience
+        #[clap(long, default_value_t = 0.0, requires = "patience")]
+        min_delta: f64,
+
+        /// Number of the fittest individuals carried unchanged into each new generation
+        #[clap(long, default_value_t = 1)]
+        elitism: usize,
+    },
+    /// Print a shell completion script to stdout
+    #[clap(hide = true)]
+    Completions {
+        /// Shell to generate the completion script for
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+    /// Compress embedded games with every algorithm and check the output matches the golden files, to confirm
+    /// the binary format has not changed
+    #[clap(hide = true)]
+    Selftest {
+        /// Write the current output as new golden files into this directory instead of checking it
+        #[clap(long)]
+        bless: Option<PathBuf>,
     },
 }
 
+/// Get the path to decompress to inside an output directory, using the original file name stored in the header.
+/// Output paths that are not directories are used as they are.
+fn restored_output_path(output_path: String, header: &Header) -> Result<String, CgnCliError> {
+    let dir = Path::new(&output_path);
+    if output_path == STDIO_PATH || !dir.is_dir() {
+        return Ok(output_path);
+    }
+
+    // only accept a plain file name, so a crafted header cannot write outside the directory
+    let name = header
+        .name
+        .as_deref()
+        .filter(|name| Path::new(name).file_name() == Some(OsStr::new(name)))
+        .ok_or_else(|| CgnCliError::NoStoredName(dir.to_path_buf()))?;
+    Ok(dir.join(name).to_string_lossy().into_owned())
+}
+
+/// Get the log level for the number of times `-v` was given, logging only warnings by default
+fn log_level(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
 /// The main function for the command line interface.
 fn main() {
-    let cli = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut cli = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    output::init(cli.quiet, cli.no_color);
+    env_logger::Builder::new()
+        .filter_level(log_level(cli.verbose))
+        .parse_default_env()
+        .write_style(match cli.no_color {
+            true => WriteStyle::Never,
+            false => WriteStyle::Auto,
+        })
+        .init();
+
+    if cli.profile_output.is_some() && !profile::is_available() {
+        output::error(CgnCliError::ProfilingUnavailable);
+        std::process::exit(CgnCliError::ProfilingUnavailable.exit_code() as i32);
+    }
+    profile::init(cli.profile);
+    let profile_output = cli.profile_output.clone();
+
+    let result = load_config(cli.config.as_deref())
+        .and_then(|config| apply_config(&mut cli, &matches, &config))
+        .and_then(|()| {
+            init_thread_pool(cli.threads);
+            run(cli)
+        });
+
+    // report the timings even if the command failed, as where it spent its time may explain why
+    if profile::is_enabled() {
+        eprint!("{}", profile::breakdown());
+        if let Some(profile_output) = &profile_output {
+            if let Err(e) = profile::write_folded(profile_output) {
+                output::error(format!(
+                    "failed to write {}: {}",
+                    profile_output.display(),
+                    e
+                ));
+            }
+        }
+    }
+    if let Err(e) = result {
+        log::debug!("{:?}", e);
+        output::error(&e);
+        std::process::exit(e.exit_code() as i32);
+    }
+}
+
+/// Build the global thread pool every parallel command runs on, with the number of threads given by --threads
+fn init_thread_pool(threads: usize) {
+    let threads = resolve_threads(threads);
+    log::debug!("Running parallel work on up to {} thread(s)", threads);
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .expect("Failed to build thread pool");
+}
+
+/// Fill in the options that were not given on the command line with the defaults from the config file
+fn apply_config(cli: &mut Args, matches: &ArgMatches, config: &Config) -> Result<(), CgnCliError> {
+    if let Some(threads) = config
+        .threads
+        .filter(|_| matches.value_source("threads") == Some(ValueSource::DefaultValue))
+    {
+        cli.threads = threads;
+    }
+
+    let command = &mut cli.command;
+    let Some((_, matches)) = matches.subcommand() else {
+        return Ok(());
+    };
+    let defaulted = |id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
+    let set_level = |optimization_level: &mut u8| {
+        if let Some(level) = config
+            .optimization_level
+            .filter(|_| defaulted("optimization_level"))
+        {
+            *optimization_level = level;
+        }
+    };
+    let set_template = |output_template: &mut OutputTemplate| -> Result<(), CgnCliError> {
+        if let Some(template) = config
+            .output_template()?
+            .filter(|_| defaulted("output_template"))
+        {
+            *output_template = template;
+        }
+        Ok(())
+    };
+    let set_verify = |verify: &mut bool| {
+        if let Some(v) = config.verify.filter(|_| defaulted("verify")) {
+            *verify = v;
+        }
+    };
+
+    match command {
+        Commands::Compress {
+            optimization_level,
+            output_template,
+            verify,
+            ..
+        } => {
+            set_level(optimization_level);
+            set_template(output_template)?;
+            set_verify(verify);
+        }
+        Commands::CompressDir {
+            optimization_level,
+            output_template,
+            ..
+        } => {
+            set_level(optimization_level);
+            set_template(output_template)?;
+        }
+        Commands::Stats {
+            optimization_level, ..
+        }
+        | Commands::Analyze {
+            optimization_level, ..
+        }
+        | Commands::Split {
+            optimization_level, ..
+        } => set_level(optimization_level),
+        Commands::Append { verify, .. } => set_verify(verify),
+        _ => {}
+    }
+    Ok(())
+}
 
+/// Report the size of the input once comments were stripped from it, if they were
+fn note_stripped(summary: &CompressSummary, strip: StripComments) {
+    if let Some(stripped_bytes) = summary.stripped_bytes {
+        output::note(format!(
+            "Stripping {} comments left {} bytes ({:.2}% of the input), compressed to {:.2}% of the stripped input",
+            strip,
+            stripped_bytes,
+            compression_ratio(stripped_bytes as usize, summary.original_bytes as usize) * 100.0,
+            compression_ratio(summary.compressed_bytes as usize, stripped_bytes as usize) * 100.0
+        ));
+    }
+}
+
+/// Run the command specified by the command line arguments.
+fn run(cli: Args) -> Result<(), CgnCliError> {
     match cli.command {
         Commands::Compress {
             optimization_level,
-            input_path,
-            output_path,
+            algorithm,
+            paths,
+            stdout_size,
+            verify,
+            progress,
+            stream,
+            per_game,
+            max_input_size,
+            height,
+            dev,
+            dry_run,
+            force,
+            keep_going,
+            preserve_times,
+            no_metadata,
+            output_template,
+            encrypt,
+            skip,
+            take,
+            encoding,
+            normalize,
+            strip_comments,
+            output_dir,
+            max_games_per_archive,
+            framed_stdout,
         } => {
-            // open and read the file into a string
-            let mut input_file = File::open(input_path).unwrap();
-            let mut pgn_str = String::new();
-            input_file.read_to_string(&mut pgn_str).unwrap();
-
-            // compress the PGN data using the specified optimization level
-            let compressed_pgn_data = match optimization_level {
-                0 => bincode_compress_pgn_str(&pgn_str),
-                1 => huffman_compress_pgn_str(&pgn_str),
-                2 => dynamic_huffman_compress_pgn_str(&pgn_str),
-                3 => opening_huffman_compress_pgn_str(&pgn_str),
-                _ => unreachable!(),
+            // compress the PGN data using the specified algorithm or optimization level
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                None => AlgorithmSelection::Explicit(Algorithm::from_level(optimization_level)?),
             };
 
-            // if the vector is empty, then the compression failed
-            if compressed_pgn_data.is_empty() {
-                println!("Compression failed");
-                return;
+            // tuned parameters only apply to dynamic huffman
+            let tuning = height.zip(dev).map(|(height, dev)| Tuning { height, dev });
+            if tuning.is_some()
+                && algorithm != AlgorithmSelection::Explicit(Algorithm::DynamicHuffman)
+            {
+                return Err(CgnCliError::TuningUnsupported(algorithm.to_string()));
             }
 
-            // write the compressed PGN data to the output file
-            let mut output_file = File::create(output_path).unwrap();
-            output_file.write_all(&compressed_pgn_data).unwrap();
+            // read the passphrase up front, so a wrong confirmation fails before anything is compressed
+            let passphrase = encrypt.then(|| read_passphrase(true)).transpose()?;
+
+            let options = CompressOptions {
+                algorithm,
+                verify,
+                progress,
+                stream,
+                per_game,
+                max_input_size,
+                tuning,
+                passphrase,
+                window: GameWindow::new(skip, take),
+                encoding,
+                normalize,
+                strip_comments,
+                framed: framed_stdout,
+                max_games_per_archive: max_games_per_archive.map(|n| n as usize),
+                force,
+                metadata: !no_metadata,
+            };
+
+            // print only the compressed size for scripts, compressing every path in memory
+            if stdout_size {
+                let summary = compress_input(&paths, None, &options)?;
+                println!("{}", summary.compressed_bytes);
+                return Ok(());
+            }
+
+            // every path is an input when compressing into an output directory, and otherwise the last path is the output
+            let (output_path, input_paths) = match (&output_dir, paths.split_last()) {
+                (Some(output_dir), _) => (output_dir.to_string_lossy().into_owned(), &paths[..]),
+                (None, Some((output_path, input_paths))) if !input_paths.is_empty() => {
+                    (output_path.clone(), input_paths)
+                }
+                _ => return Err(CgnCliError::MissingOutputPath),
+            };
+
+            // compress the inputs as a batch of files into the output directory, or each match of a glob pattern
+            let glob_input = matches!(input_paths, [input_path] if is_glob(input_path));
+            if output_dir.is_some() || glob_input {
+                let batch = if output_dir.is_some() {
+                    "--output-dir"
+                } else {
+                    "a glob pattern"
+                };
+                let AlgorithmSelection::Explicit(algorithm) = algorithm else {
+                    return Err(CgnCliError::BestUnsupported(batch));
+                };
+                if tuning.is_some() {
+                    return Err(CgnCliError::TuningUnsupported(String::from(batch)));
+                }
+                if options.window.is_some() {
+                    return Err(CgnCliError::SingleOnly("--skip and --take"));
+                }
+                if strip_comments != StripComments::None {
+                    return Err(CgnCliError::SingleOnly("--strip-comments"));
+                }
+                if max_games_per_archive.is_some() {
+                    return Err(CgnCliError::SingleOnly("--max-games-per-archive"));
+                }
+                if framed_stdout {
+                    return Err(CgnCliError::SingleOnly("--framed-stdout"));
+                }
+                if stream {
+                    return Err(CgnCliError::SingleOnly("--stream"));
+                }
+                if per_game {
+                    return Err(CgnCliError::SingleOnly("--per-game"));
+                }
+                if progress {
+                    return Err(CgnCliError::SingleOnly("--progress"));
+                }
+                let batch_options = BatchOptions {
+                    algorithm,
+                    dry_run,
+                    force,
+                    keep_going,
+                    preserve_times,
+                    output_template,
+                    passphrase: options.passphrase,
+                    encoding,
+                    normalize,
+                    verify,
+                    max_input_size,
+                    metadata: !no_metadata,
+                };
+                return match output_dir {
+                    Some(output_dir) => compress_into_dir(input_paths, &output_dir, &batch_options),
+                    None => compress_glob(&input_paths[0], Path::new(&output_path), &batch_options),
+                };
+            }
+
+            if preserve_times {
+                return Err(CgnCliError::BatchOnly("--preserve-times"));
+            }
+
+            // the numbered archives of a rotation are each checked before they are written
+            let rotating = max_games_per_archive.is_some();
+            if rotating && output_path == STDIO_PATH {
+                return Err(CgnCliError::StdoutUnsupported("--max-games-per-archive"));
+            }
+
+            // compress the input in memory and report the result without writing it on a dry run
+            let input_path = input_paths.join(", ");
+            if dry_run {
+                if !rotating {
+                    warn_if_exists(Path::new(&output_path));
+                }
+                let summary = compress_input(input_paths, None, &options)?;
+                output::status(format!(
+                    "Dry run: {} would be compressed from {} to {} bytes, a compression ratio of {}",
+                    input_path,
+                    summary.original_bytes,
+                    summary.compressed_bytes,
+                    compression_ratio(
+                        summary.compressed_bytes as usize,
+                        summary.original_bytes as usize
+                    )
+                ));
+                note_stripped(&summary, strip_comments);
+                return Ok(());
+            }
+            if !rotating {
+                check_overwrite(Path::new(&output_path), force)?;
+            }
+            let start = Instant::now();
+            let summary = compress_input(input_paths, Some(&output_path), &options)?;
+
+            // print the summary to stderr, so it never mixes with compressed data written to stdout
+            output::note(format!(
+                "Compressed {} from {} to {} bytes ({:.2}%) in {:.3} seconds",
+                input_path,
+                summary.original_bytes,
+                summary.compressed_bytes,
+                compression_ratio(
+                    summary.compressed_bytes as usize,
+                    summary.original_bytes as usize
+                ) * 100.0,
+                start.elapsed().as_secs_f64()
+            ));
+            note_stripped(&summary, strip_comments);
+        }
+        Commands::CompressDir {
+            optimization_level,
+            algorithm,
+            input_dir,
+            output_dir,
+            force,
+            keep_going,
+            verify,
+            max_input_size,
+            preserve_times,
+            no_metadata,
+            output_template,
+            encoding,
+            normalize,
+        } => {
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                None => Algorithm::from_level(optimization_level)?,
+            };
+            let options = BatchOptions {
+                algorithm,
+                dry_run: false,
+                force,
+                keep_going,
+                preserve_times,
+                output_template,
+                passphrase: None,
+                encoding,
+                normalize,
+                verify,
+                max_input_size,
+                metadata: !no_metadata,
+            };
+            compress_dir(&input_dir, &output_dir, &options)?;
         }
         Commands::Decompress {
             optimization_level,
+            algorithm,
             input_path,
             output_path,
+            validate,
+            force,
+            restore_name,
+            decrypt,
+            framed,
+            pretty,
         } => {
-            // open and read the file into a string
-            let mut input_file = File::open(input_path).unwrap();
+            // decompress a framed stream game by game as it is read, with the algorithm given for it
+            if framed {
+                let algorithm = match (algorithm, optimization_level) {
+                    (Some(algorithm), _) | (None, AlgorithmChoice::Explicit(algorithm)) => {
+                        algorithm
+                    }
+                    (None, AlgorithmChoice::Auto) => {
+                        return Err(CgnCliError::FramedAlgorithmRequired)
+                    }
+                };
+                check_overwrite(Path::new(&output_path), force)?;
+                let mut output = open_writer(&output_path)?;
+                let games = decompress_framed(open_reader(&input_path)?, &mut output, algorithm)?;
+                output.finish()?;
+                log::info!("Decompressed {} game(s) from {}", games, input_path);
+                return Ok(());
+            }
+
+            // open and read the input into a byte vector
             let mut compressed_pgn_data = Vec::new();
-            input_file.read_to_end(&mut compressed_pgn_data).unwrap();
-
-            // decompress the PGN data using the specified optimization level
-            let pgn_data = match optimization_level {
-                0 => bincode_decompress_pgn_str(&compressed_pgn_data),
-                1 => huffman_decompress_pgn_str(&compressed_pgn_data),
-                2 => dynamic_huffman_decompress_pgn_str(&compressed_pgn_data),
-                3 => opening_huffman_decompress_pgn_str(&compressed_pgn_data),
-                _ => unreachable!(),
+            profile::time("read", || {
+                open_reader(&input_path)?.read_to_end(&mut compressed_pgn_data)
+            })?;
+
+            // select the algorithm from the header, unless the user specified one
+            let choice = match algorithm {
+                Some(algorithm) => AlgorithmChoice::Explicit(algorithm),
+                None => optimization_level,
+            };
+            let decrypted;
+            let (header, payload) = match resolve_header(&compressed_pgn_data, choice) {
+                // decrypt the payload with the passphrase before decompressing it
+                Err(CgnCliError::Encrypted) if decrypt => {
+                    let (header, payload) = parse_header(&compressed_pgn_data)?;
+                    let (header, plaintext) =
+                        decrypt_payload(&header, payload, &read_passphrase(false)?)?;
+                    decrypted = plaintext;
+                    (header, &decrypted[..])
+                }
+                result => result?,
             };
+            let output_path = match restore_name {
+                true => restored_output_path(output_path, &header)?,
+                false => output_path,
+            };
+            check_overwrite(Path::new(&output_path), force)?;
+            log::info!(
+                "Decompressing {} with {} (format version {}, flags {:#010b})",
+                input_path,
+                header.algorithm,
+                header.version,
+                header.flags
+            );
+            let start = Instant::now();
+            let pgn_data = archive::decompress_parallel(&header, payload)?;
+            log::info!(
+                "Decompressed {} bytes to {} bytes in {:.3} seconds",
+                compressed_pgn_data.len(),
+                pgn_data.len(),
+                start.elapsed().as_secs_f64()
+            );
 
-            // if the string is empty, then the decompression failed
-            if pgn_data.is_empty() {
-                println!("Decompression failed");
-                return;
+            // check the decompressed games are valid PGN
+            if validate {
+                validate_pgn(&pgn_data)?;
             }
 
-            // write the decompressed PGN data to the output file
-            let mut output_file = File::create(output_path).unwrap();
-            output_file.write_all(pgn_data.as_bytes()).unwrap();
+            // reformat the games for reading if asked
+            let pgn_data = match pretty {
+                true => normalize::pretty(&pgn_data),
+                false => pgn_data,
+            };
+
+            // write the decompressed PGN data to the output
+            profile::time("write", || {
+                let mut output = open_writer(&output_path)?;
+                output.write_all(pgn_data.as_bytes())?;
+                output.finish()
+            })?;
+
+            // restore the modification time of the original file, if the header stores one
+            if let Some(mtime) = header.mtime.filter(|_| output_path != STDIO_PATH) {
+                filetime::set_file_mtime(&output_path, mtime)?;
+            }
         }
+        Commands::Cat {
+            input_paths,
+            pretty,
+        } => cat(&input_paths, pretty)?,
+        Commands::Merge {
+            input_paths,
+            output_path,
+            force,
+        } => merge(&input_paths, &output_path, force)?,
+        Commands::Fsck {
+            input_path,
+            salvage,
+            force,
+        } => fsck(&input_path, salvage.as_deref(), force)?,
+        Commands::Split {
+            optimization_level,
+            algorithm,
+            input_path,
+            parts,
+            output_prefix,
+            contiguous,
+            force,
+        } => {
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                None => Algorithm::from_level(optimization_level)?,
+            };
+            split(
+                &input_path,
+                parts as usize,
+                &output_prefix,
+                algorithm,
+                contiguous,
+                force,
+            )?;
+        }
+        Commands::Transcode {
+            input_path,
+            output_path,
+            to_algorithm,
+            force,
+        } => {
+            check_overwrite(Path::new(&output_path), force)?;
+
+            // open and read the input into a byte vector
+            let mut compressed_pgn_data = Vec::new();
+            open_reader(&input_path)?.read_to_end(&mut compressed_pgn_data)?;
+
+            // decompress with the algorithm from the header and re-compress with the target algorithm
+            let (header, payload) = read_header(&compressed_pgn_data)?;
+            if header.algorithm == to_algorithm && !force {
+                return Err(CgnCliError::SameAlgorithm(to_algorithm));
+            }
+            let transcoded = transcode(&header, payload, to_algorithm)?;
+
+            // write the transcoded file to the output
+            let mut output = open_writer(&output_path)?;
+            output.write_all(&transcoded)?;
+            output.finish()?;
+        }
+        Commands::Append {
+            input_path,
+            archive_path,
+            algorithm,
+            verify,
+        } => append(&input_path, &archive_path, algorithm, verify)?,
+        Commands::Extract {
+            input_path,
+            game_index,
+            output_path,
+            count: count_only,
+            force,
+        } => match (game_index, output_path) {
+            (Some(game_index), Some(output_path)) if !count_only => {
+                check_overwrite(Path::new(&output_path), force)?;
+                extract(&input_path, game_index, &output_path)?;
+            }
+            _ => count(&input_path)?,
+        },
+        Commands::Browse { input_path } => browse(&input_path)?,
+        Commands::List { input_path, full } => list(&input_path, full)?,
+        Commands::Info { input_path } => {
+            info(&input_path)?;
+        }
+        Commands::Verify {
+            input_path,
+            original_path,
+            checksum_only,
+        } => match original_path {
+            Some(original_path) if !checksum_only => verify(&input_path, &original_path)?,
+            _ => verify_checksum(&input_path)?,
+        },
         Commands::Bench {
             number_of_games,
             input_db_path,
             output_path,
+            format,
+            min_elo,
+            max_elo,
+            time_control,
+            min_moves,
+            max_moves,
+            comments,
+            measure_memory,
+            sample,
+            seed,
+            fuzz_robustness,
+            recommend,
+            priority,
+            warmup,
+            iterations,
+            strict,
+            strict_roundtrip,
+            ratio_by_length,
+            by_eco,
+            histogram,
+            metric,
+            strip_comments,
+            baseline,
+            tolerance,
+            time_tolerance,
         } => {
-            bench(number_of_games, &input_db_path, &output_path);
+            if measure_memory && !memory::is_available() {
+                return Err(CgnCliError::MemoryMeasurementUnavailable);
+            }
+            let number_of_games = number_of_games.resolve(&input_db_path)?;
+            let config = BenchConfig {
+                number_of_games,
+                input_db_path,
+                output_path,
+                format,
+                filter: GameFilter {
+                    min_elo,
+                    max_elo,
+                    time_control,
+                    min_moves,
+                    max_moves,
+                    comments,
+                },
+                sampling: Sampling::new(sample, seed),
+                measure_memory,
+                recommend: recommend.then_some(priority),
+                repetitions: Repetitions { warmup, iterations },
+                strict,
+                strict_roundtrip,
+                ratio_by_length,
+                by_eco,
+                histogram,
+                metric,
+                strip_comments,
+                baseline,
+                tolerance,
+                time_tolerance,
+            };
+            if fuzz_robustness {
+                fuzz::fuzz_robustness(config)?;
+            } else {
+                bench(config)?;
+            }
+        }
+        Commands::Diff {
+            archive_a,
+            archive_b,
+        } => diff(&archive_a, &archive_b, cli.verbose > 0)?,
+        Commands::Compare {
+            input_path,
+            algo_a,
+            algo_b,
+        } => compare(&input_path, algo_a, algo_b)?,
+        Commands::Stats {
+            optimization_level,
+            algorithm,
+            number_of_games,
+            input_db_path,
+        } => {
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                None => Algorithm::from_level(optimization_level)?,
+            };
+            let number_of_games = number_of_games.resolve(&input_db_path)?;
+            print!("{}", stats(&input_db_path, &number_of_games, algorithm)?);
+        }
+        Commands::CountGames { input_db_path } => println!("{}", count_db_games(&input_db_path)?),
+        Commands::Analyze {
+            optimization_level,
+            algorithm,
+            input_path,
+        } => {
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                None => Algorithm::from_level(optimization_level)?,
+            };
+            analyze(&input_path, algorithm)?;
         }
+        Commands::Entropy { input_path } => entropy(&input_path)?,
         Commands::GenAlgo {
             input_db_path,
             output_path,
@@ -226,7 +1529,17 @@ fn main() {
             dev_max,
             mutation_rate,
             tournament_size,
+            seed,
+            checkpoint,
+            checkpoint_every,
+            resume,
+            history,
+            history_format,
+            patience,
+            min_delta,
+            elitism,
         } => {
+            let number_of_games = number_of_games.resolve(&input_db_path)?;
             let config = GeneticAlgorithmConfig {
                 init_population,
                 number_of_games,
@@ -239,8 +1552,28 @@ fn main() {
                 dev_max,
                 input_db_path,
                 output_path,
+                seed: seed.unwrap_or_else(rand::random),
+                checkpoint_path: checkpoint,
+                checkpoint_every,
+                resume_path: resume,
+                history_path: history,
+                history_format,
+                patience,
+                min_delta,
+                elitism,
             };
-            genetic_algorithm(config);
+            genetic_algorithm(config)?;
         }
+        Commands::Completions { shell } => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        }
+        Commands::Selftest { bless } => match bless {
+            Some(output_dir) => selftest::bless(&output_dir)?,
+            None => selftest::selftest()?,
+        },
     }
+
+    Ok(())
 }
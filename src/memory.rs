@@ -0,0 +1,23 @@
+/// Tracks the peak heap usage of the process when built with the `measure-memory` feature
+#[cfg(feature = "measure-memory")]
+#[global_allocator]
+static PEAK_ALLOC: peak_alloc::PeakAlloc = peak_alloc::PeakAlloc;
+
+/// Check if memory usage can be measured in this build
+pub fn is_available() -> bool {
+    cfg!(feature = "measure-memory")
+}
+
+/// Reset the recorded peak heap usage to the current usage
+pub fn reset_peak() {
+    #[cfg(feature = "measure-memory")]
+    PEAK_ALLOC.reset_peak_usage();
+}
+
+/// Get the peak heap usage in bytes since the last reset, if memory usage can be measured
+pub fn peak_bytes() -> Option<usize> {
+    #[cfg(feature = "measure-memory")]
+    return Some(PEAK_ALLOC.peak_usage());
+    #[cfg(not(feature = "measure-memory"))]
+    None
+}
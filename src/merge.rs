@@ -0,0 +1,55 @@
+use super::archive::{is_single_game, merge_records};
+use super::error::CgnCliError;
+use super::header::{read_header, Header};
+use super::io_util::{check_overwrite, open_reader, open_writer};
+use super::output;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Merge the games of several streamed or indexed CGN files, such as the output of `split`, into one indexed file
+/// without re-compressing them. Every file must be compressed with the same algorithm, as records cannot be mixed,
+/// though files of earlier format versions can be merged as every record is written again in the current version.
+/// The output is only overwritten if `force` is set.
+pub fn merge(input_paths: &[String], output_path: &str, force: bool) -> Result<(), CgnCliError> {
+    check_overwrite(Path::new(output_path), force)?;
+    let compressed_data = input_paths
+        .iter()
+        .map(|input_path| {
+            let mut compressed_data = Vec::new();
+            open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+            Ok(compressed_data)
+        })
+        .collect::<Result<Vec<_>, CgnCliError>>()?;
+
+    // check every file can have its records merged with those of the first
+    let mut files: Vec<(Header, &[u8])> = Vec::with_capacity(input_paths.len());
+    for (input_path, compressed_data) in input_paths.iter().zip(&compressed_data) {
+        let (header, payload) = read_header(compressed_data)?;
+        if is_single_game(&header) || header.tuning.is_some() {
+            return Err(CgnCliError::NotMergeable(input_path.clone()));
+        }
+        if let Some((first, _)) = files.first() {
+            if header.algorithm != first.algorithm {
+                return Err(CgnCliError::MergeMismatch {
+                    path: input_path.clone(),
+                    algorithm: header.algorithm,
+                    first_path: input_paths[0].clone(),
+                    expected: first.algorithm,
+                });
+            }
+        }
+        files.push((header, payload));
+    }
+
+    let (merged, games) = merge_records(&files)?;
+    let mut output = open_writer(output_path)?;
+    output.write_all(&merged)?;
+    output.finish()?;
+    output::status(format!(
+        "Merged {} game(s) from {} file(s) into {}",
+        games,
+        files.len(),
+        output_path
+    ));
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use clap::ValueEnum;
+use pgn_reader::{BufferedReader, Nag, RawComment, RawHeader, SanPlus, Skip, Visitor};
+use shakmaty::Outcome;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Cursor, Read};
+
+/// The Seven Tag Roster, written first and in this order, before any other tags sorted by name
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// The result tokens a game can end with
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// The column movetext is wrapped at
+const LINE_LEN: usize = 80;
+
+/// Which annotations to remove from the comments of games, such as the `[%clk]` and `[%eval]` commands Lichess adds
+/// after every move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum StripComments {
+    /// Remove `[%clk]` clock commands
+    Clk,
+    /// Remove `[%eval]` engine evaluation commands
+    Eval,
+    /// Remove every comment
+    All,
+    /// Keep every comment
+    #[default]
+    None,
+}
+
+impl StripComments {
+    /// Strip the annotations from the text of a comment, returning None if nothing is left of it
+    fn apply(self, comment: &str) -> Option<String> {
+        let text = match self {
+            StripComments::Clk => remove_command(comment, "clk"),
+            StripComments::Eval => remove_command(comment, "eval"),
+            StripComments::All => return None,
+            StripComments::None => comment.to_string(),
+        };
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        (!words.is_empty()).then(|| words.join(" "))
+    }
+}
+
+impl Display for StripComments {
+    /// Display the annotations stripped as they are named on the command line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StripComments::Clk => "clk",
+            StripComments::Eval => "eval",
+            StripComments::All => "all",
+            StripComments::None => "none",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Remove every `[%command ...]` embedded in a comment
+fn remove_command(comment: &str, command: &str) -> String {
+    let opening = format!("[%{}", command);
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find(&opening) {
+        let after = &rest[start + opening.len()..];
+        // only match the whole command name, so `[%clk` does not remove a `[%clkx ...]` command
+        if !after.starts_with([' ', ']']) {
+            text.push_str(&rest[..start + opening.len()]);
+            rest = after;
+            continue;
+        }
+        text.push_str(&rest[..start]);
+        rest = after.find(']').map_or("", |end| &after[end + 1..]);
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Escape a tag value so it can be written between quotes
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Get the position in which a tag is written, with the Seven Tag Roster first
+fn tag_order(key: &str) -> (usize, &str) {
+    let roster_index = SEVEN_TAG_ROSTER.iter().position(|tag| *tag == key);
+    (roster_index.unwrap_or(SEVEN_TAG_ROSTER.len()), key)
+}
+
+/// Get the ply a game starts at from its FEN tag, counting from zero for white's first move, so games set up from a
+/// position are numbered from the move they start at
+fn starting_ply(fen: &str) -> usize {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    let fullmoves = fields
+        .get(5)
+        .and_then(|fullmoves| fullmoves.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    (fullmoves - 1) * 2 + usize::from(fields.get(1) == Some(&"b"))
+}
+
+/// Wrap tokens into lines no longer than the line length, unless a single token is longer
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > LINE_LEN {
+            text.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            text.push(' ');
+            line_len += 1;
+        }
+        text.push_str(token);
+        line_len += token.len();
+    }
+    text
+}
+
+/// A visitor that rewrites a game in a canonical form: the Seven Tag Roster in order followed by the other tags
+/// sorted by name, then the movetext with one space between tokens, comments trimmed, and lines wrapped, ending with
+/// the same result as the Result tag
+#[derive(Default)]
+struct Normalizer {
+    tags: Vec<(String, String)>,
+    tokens: Vec<String>,
+    /// The ply of the next move, counting from zero for white's first move
+    ply: usize,
+    /// The ply to return to at the end of each variation being read
+    variations: Vec<usize>,
+    /// Whether the next move needs a move number even if it is black's, such as after a comment
+    numbered: bool,
+    /// Whether a variation was opened and the next token starts it
+    opening: bool,
+    outcome: Option<Outcome>,
+    strip: StripComments,
+}
+
+impl Normalizer {
+    /// Create a normalizer that also strips annotations from comments
+    fn stripping(strip: StripComments) -> Self {
+        Self {
+            strip,
+            ..Self::default()
+        }
+    }
+
+    /// Add a token to the movetext, starting a variation with it if one was just opened
+    fn push_token(&mut self, token: String) {
+        if std::mem::take(&mut self.opening) {
+            self.tokens.push(format!("({}", token));
+        } else {
+            self.tokens.push(token);
+        }
+    }
+}
+
+impl Visitor for Normalizer {
+    /// The normalized PGN of the game
+    type Result = String;
+
+    /// Reset the game at the start of each game
+    fn begin_game(&mut self) {
+        *self = Self::stripping(self.strip);
+        self.numbered = true;
+    }
+
+    /// Keep the tag with its value unescaped
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        let key = String::from_utf8_lossy(key).trim().to_string();
+        self.tags
+            .push((key, value.decode_utf8_lossy().into_owned()));
+    }
+
+    /// Number the moves from the position the game starts at
+    fn end_headers(&mut self) -> Skip {
+        if let Some((_, fen)) = self.tags.iter().find(|(key, _)| key == "FEN") {
+            self.ply = starting_ply(fen);
+        }
+        Skip(false)
+    }
+
+    /// Add a move, preceded by its move number if it is white's or follows a comment or variation.
+    /// The number is kept on the same line as its move.
+    fn san(&mut self, san_plus: SanPlus) {
+        let number = self.ply / 2 + 1;
+        let token = if self.ply.is_multiple_of(2) {
+            format!("{}. {}", number, san_plus)
+        } else if self.numbered {
+            format!("{}... {}", number, san_plus)
+        } else {
+            san_plus.to_string()
+        };
+        self.push_token(token);
+        self.ply += 1;
+        self.numbered = false;
+    }
+
+    /// Add a numeric annotation glyph
+    fn nag(&mut self, nag: Nag) {
+        self.push_token(nag.to_string());
+    }
+
+    /// Add a comment with its whitespace collapsed and annotations stripped, skipping comments left empty
+    fn comment(&mut self, comment: RawComment<'_>) {
+        let text = String::from_utf8_lossy(comment.as_bytes());
+        if let Some(text) = self.strip.apply(&text) {
+            self.push_token(format!("{{ {} }}", text));
+            self.numbered = true;
+        }
+    }
+
+    /// Open a variation replacing the last move
+    fn begin_variation(&mut self) -> Skip {
+        self.variations.push(self.ply);
+        self.ply = self.ply.saturating_sub(1);
+        self.opening = true;
+        self.numbered = true;
+        Skip(false)
+    }
+
+    /// Close the variation, returning to the move after the one it replaced
+    fn end_variation(&mut self) {
+        if std::mem::take(&mut self.opening) {
+            self.tokens.push(String::from("()"));
+        } else if let Some(token) = self.tokens.last_mut() {
+            token.push(')');
+        }
+        self.ply = self.variations.pop().unwrap_or(self.ply);
+        self.numbered = true;
+    }
+
+    /// Keep the result of the movetext
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcome = outcome;
+    }
+
+    /// Write the game, taking its result from the movetext or else a valid Result tag, so both always agree
+    fn end_game(&mut self) -> Self::Result {
+        let tag_result = self
+            .tags
+            .iter()
+            .find(|(key, value)| key == "Result" && RESULTS.contains(&value.as_str()))
+            .map(|(_, value)| value.clone());
+        let result = match self.outcome {
+            Some(outcome) => outcome.to_string(),
+            None => tag_result.unwrap_or_else(|| String::from("*")),
+        };
+        match self.tags.iter_mut().find(|(key, _)| key == "Result") {
+            Some((_, value)) => value.clone_from(&result),
+            None => self.tags.push((String::from("Result"), result.clone())),
+        }
+        self.tags
+            .sort_by(|(a, _), (b, _)| tag_order(a).cmp(&tag_order(b)));
+
+        let mut pgn_str = String::new();
+        for (key, value) in &self.tags {
+            pgn_str.push_str(&format!("[{} \"{}\"]\n", key, escape_tag_value(value)));
+        }
+        pgn_str.push('\n');
+        self.tokens.push(result);
+        pgn_str.push_str(&wrap_tokens(&self.tokens));
+        pgn_str.push_str("\n\n");
+        pgn_str
+    }
+}
+
+/// Rewrite a single game in a canonical form with annotations stripped from its comments, or get it back unchanged
+/// if it cannot be read
+pub fn strip_comments(pgn_str: &str, strip: StripComments) -> String {
+    match BufferedReader::new_cursor(pgn_str.as_bytes())
+        .read_game(&mut Normalizer::stripping(strip))
+    {
+        Ok(Some(stripped)) => stripped,
+        _ => pgn_str.to_string(),
+    }
+}
+
+/// Reformat every game in PGN text for reading, in the same canonical form as normalizing, with each game ending in a
+/// newline and a blank line between games. The text is returned unchanged if any game in it cannot be read.
+pub fn pretty(pgn_str: &str) -> String {
+    let mut reader = BufferedReader::new_cursor(pgn_str.as_bytes());
+    let mut games = Vec::new();
+    loop {
+        match reader.read_game(&mut Normalizer::default()) {
+            Ok(Some(game)) => games.push(game.trim_end().to_string()),
+            Ok(None) => break,
+            Err(_) => return pgn_str.to_string(),
+        }
+    }
+    if games.is_empty() {
+        return pgn_str.to_string();
+    }
+    games.join("\n\n") + "\n"
+}
+
+/// A reader over a PGN database with every game rewritten in a canonical form, holding only the game being read
+/// in memory
+pub struct NormalizingReader<R> {
+    games: BufferedReader<R>,
+    game: Cursor<Vec<u8>>,
+    strip: StripComments,
+}
+
+impl<R: Read> NormalizingReader<R> {
+    /// Create a reader normalizing the games of a PGN database
+    pub fn new(inner: R) -> Self {
+        Self::stripping(inner, StripComments::None)
+    }
+
+    /// Create a reader normalizing the games of a PGN database and stripping annotations from their comments
+    pub fn stripping(inner: R, strip: StripComments) -> Self {
+        Self {
+            games: BufferedReader::new(inner),
+            game: Cursor::new(Vec::new()),
+            strip,
+        }
+    }
+}
+
+impl<R: Read> Read for NormalizingReader<R> {
+    /// Read from the current game, normalizing the next game once it is exhausted
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.game.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self
+                .games
+                .read_game(&mut Normalizer::stripping(self.strip))?
+            {
+                Some(game) => self.game = Cursor::new(game.into_bytes()),
+                None => return Ok(0),
+            }
+        }
+    }
+}
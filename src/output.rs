@@ -0,0 +1,72 @@
+use super::error::CgnCliError;
+use owo_colors::{OwoColorize, Stream, Style};
+use std::fmt::Display;
+use std::io::{self, ErrorKind, StdoutLock, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--quiet`, hiding everything but results and errors
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Set how human-facing messages are printed for the rest of the run. Messages are colored only when the stream they
+/// are printed to is a terminal, `--no-color` is not given, and the `NO_COLOR` environment variable is not set.
+pub fn init(quiet: bool, no_color: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+    if no_color {
+        owo_colors::set_override(false);
+    }
+}
+
+/// Check if status messages, summaries, and progress bars are hidden
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print a status message or summary to stdout, unless quiet. A closed stdout is ignored rather than panicking.
+pub fn status(message: impl Display) {
+    if !is_quiet() {
+        let _ = writeln!(io::stdout(), "{}", message);
+    }
+}
+
+/// Print a status message or summary to stderr, unless quiet, for commands that may write data to stdout
+pub fn note(message: impl Display) {
+    if !is_quiet() {
+        eprintln!("{}", message);
+    }
+}
+
+/// Print a check that passed to stdout, labelled OK in green, unless quiet. A closed stdout is ignored rather than
+/// panicking.
+pub fn ok(message: impl Display) {
+    if !is_quiet() {
+        let label = "OK:".if_supports_color(Stream::Stdout, |label| label.green());
+        let _ = writeln!(io::stdout(), "{} {}", label, message);
+    }
+}
+
+/// Print a warning to stderr, labelled in yellow
+pub fn warning(message: impl Display) {
+    let style = Style::new().yellow().bold();
+    let label = "warning:".if_supports_color(Stream::Stderr, |label| label.style(style));
+    eprintln!("{} {}", label, message);
+}
+
+/// Print an error to stderr, labelled in red. Errors are printed even when quiet.
+pub fn error(message: impl Display) {
+    let style = Style::new().red().bold();
+    let label = "error:".if_supports_color(Stream::Stderr, |label| label.style(style));
+    eprintln!("{} {}", label, message);
+}
+
+/// Write a report to a locked stdout. A reader such as `head` closing the pipe early stops the report without an
+/// error, rather than panicking as `println!` does.
+pub fn report<F>(write: F) -> Result<(), CgnCliError>
+where
+    F: FnOnce(&mut StdoutLock<'static>) -> Result<(), CgnCliError>,
+{
+    let mut stdout = io::stdout().lock();
+    match write(&mut stdout).and_then(|()| Ok(stdout.flush()?)) {
+        Err(CgnCliError::Io(e)) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,166 @@
+use super::io_util::open_file_reader;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::iter::{Skip, Take};
+use std::path::Path;
+
+/// The file names read when a PGN database is a directory of files
+const DB_FILE_SUFFIXES: [&str; 4] = [".pgn", ".pgn.zst", ".pgn.gz", ".pgn.bz2"];
+
+/// An iterator over the games in a PGN database file.
+pub struct PgnDbIter<R: BufRead> {
+    reader: R,
+    buffer: String,
+}
+
+impl<R: BufRead> PgnDbIter<R> {
+    /// Creates a new PgnDBIter from a BufRead reader
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnDbIter<R> {
+    // The type of the elements being iterated over.
+    type Item = String;
+
+    /// Get the next game in the database.
+    fn next(&mut self) -> Option<Self::Item> {
+        // start with the line that started this game, which was read while finishing the previous one
+        let mut game = std::mem::take(&mut self.buffer);
+
+        // read until the next game
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => break, // EOF
+                Ok(_) => {
+                    // skip empty lines at the start of a game
+                    if game.is_empty() && self.buffer.trim().is_empty() {
+                        continue;
+                    // stop reading if we reach the start of the next game
+                    } else if self.buffer.starts_with("[Event") && !game.is_empty() {
+                        break;
+                    }
+                    // otherwise, add the line to the game
+                    game.push_str(&self.buffer);
+                }
+                Err(_) => return None,
+            }
+        }
+
+        // return the game if it's not empty
+        if game.trim().is_empty() {
+            None
+        } else {
+            Some(game)
+        }
+    }
+}
+
+/// A range of games in a PGN database: the games left after skipping some, up to a number of games if given
+#[derive(Clone, Copy, Debug)]
+pub struct GameWindow {
+    pub skip: usize,
+    pub take: Option<usize>,
+}
+
+impl GameWindow {
+    /// Get the window, or None if it covers every game
+    pub fn new(skip: usize, take: Option<usize>) -> Option<Self> {
+        (skip > 0 || take.is_some()).then_some(Self { skip, take })
+    }
+
+    /// Read only the games in the window from a PGN database, one game at a time
+    pub fn reader<R: BufRead>(self, reader: R) -> GameWindowReader<R> {
+        GameWindowReader {
+            games: PgnDbIter::new(reader)
+                .skip(self.skip)
+                .take(self.take.unwrap_or(usize::MAX)),
+            game: Cursor::new(Vec::new()),
+        }
+    }
+}
+
+/// A reader over the games in a window of a PGN database, holding only the game being read in memory
+pub struct GameWindowReader<R: BufRead> {
+    games: Take<Skip<PgnDbIter<R>>>,
+    game: Cursor<Vec<u8>>,
+}
+
+impl<R: BufRead> Read for GameWindowReader<R> {
+    /// Read from the current game, moving on to the next game in the window once it is exhausted
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.game.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.games.next() {
+                Some(game) => self.game = Cursor::new(game.into_bytes()),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Find the PGN files directly inside a directory, sorted by name so files named by date are read in order
+fn find_db_files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.to_string_lossy();
+        if path.is_file() && DB_FILE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+            files.push(name.into_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Count the games in a PGN database by counting the lines that start a game, without parsing the games.
+/// This still reads and decompresses the whole database once, so it costs a full pass over a large database.
+pub fn count_db_games(path: &str) -> io::Result<usize> {
+    let files = if Path::new(path).is_dir() {
+        find_db_files(Path::new(path))?
+    } else {
+        vec![path.to_string()]
+    };
+
+    let mut games = 0;
+    for file in files {
+        for line in BufReader::new(open_file_reader(&file)?).split(b'\n') {
+            if line?.starts_with(b"[Event") {
+                games += 1;
+            }
+        }
+    }
+    Ok(games)
+}
+
+/// Opens a PGN database and returns an iterator over the games in the database.
+/// The database is either a single file or a directory of `.pgn`, `.pgn.zst`, `.pgn.gz`, and `.pgn.bz2` files read as one stream of games,
+/// opening each file only once the previous one is exhausted. Files ending in `.zst`, `.gz`, or `.bz2` are decompressed as they are read.
+pub fn pgn_db_into_iter(path: &str) -> io::Result<Box<dyn Iterator<Item = String> + Send>> {
+    if !Path::new(path).is_dir() {
+        let reader = BufReader::new(open_file_reader(path)?);
+        return Ok(Box::new(PgnDbIter::new(reader)));
+    }
+
+    let files = find_db_files(Path::new(path))?;
+    log::debug!("Reading {} file(s) from {}", files.len(), path);
+    Ok(Box::new(files.into_iter().flat_map(|file| {
+        match open_file_reader(&file) {
+            Ok(reader) => Some(PgnDbIter::new(BufReader::new(reader))),
+            Err(e) => {
+                log::warn!("Skipping {}: {}", file, e);
+                None
+            }
+        }
+        .into_iter()
+        .flatten()
+    })))
+}
@@ -0,0 +1,104 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Set by `--profile`, timing the phases of the run
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// When profiling started, so time spent outside every phase can be reported
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// The total time spent in each phase, in the order the phases were first entered
+static PHASES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Check if a folded stack file can be written in this build
+pub fn is_available() -> bool {
+    cfg!(feature = "profiling")
+}
+
+/// Start timing the phases of the run if `enabled` is set
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if enabled {
+        *STARTED.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// Check if the phases of the run are being timed
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Run a closure, adding the time it takes to the named phase if profiling. When not profiling, only a flag is checked.
+pub fn time<T>(phase: &'static str, f: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let mut phases = PHASES.lock().unwrap();
+    match phases.iter_mut().find(|(name, _)| *name == phase) {
+        Some((_, total)) => *total += elapsed,
+        None => phases.push((phase, elapsed)),
+    }
+    result
+}
+
+/// Get the time spent in each phase, followed by the time spent outside every phase since profiling started
+fn phases() -> Vec<(&'static str, Duration)> {
+    let mut phases = PHASES.lock().unwrap().clone();
+    let wall = STARTED
+        .lock()
+        .unwrap()
+        .map_or(Duration::ZERO, |started| started.elapsed());
+    let timed = phases.iter().map(|(_, time)| *time).sum::<Duration>();
+    phases.push(("other", wall.saturating_sub(timed)));
+    phases
+}
+
+/// Get a table of the time spent in each phase and its share of the total.
+/// Phases run on several threads at once are summed over the threads, so they can add up to more than the wall time.
+pub fn breakdown() -> String {
+    let phases = phases();
+    let total = phases.iter().map(|(_, time)| *time).sum::<Duration>();
+    let mut table = String::from("Profile:\n");
+    for (phase, time) in phases.iter().chain([&("total", total)]) {
+        let share = match total.as_secs_f64() {
+            0.0 => 0.0,
+            total => time.as_secs_f64() / total * 100.0,
+        };
+        let _ = writeln!(
+            table,
+            "  {:<12} {:>10.3} s {:>6.1}%",
+            phase,
+            time.as_secs_f64(),
+            share
+        );
+    }
+    table
+}
+
+/// Write the time spent in each phase as folded stacks, one `cgn-cli;phase microseconds` line per phase, which
+/// flamegraph tools such as inferno and speedscope can draw. Requires the `profiling` feature.
+pub fn write_folded(path: &Path) -> io::Result<()> {
+    #[cfg(feature = "profiling")]
+    {
+        let mut folded = String::new();
+        for (phase, time) in phases() {
+            let _ = writeln!(folded, "cgn-cli;{} {}", phase, time.as_micros());
+        }
+        super::io_util::write_atomically(path, folded.as_bytes())
+    }
+    #[cfg(not(feature = "profiling"))]
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} cannot be written without the profiling feature",
+            path.display()
+        ),
+    ))
+}
@@ -0,0 +1,47 @@
+use super::output;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal};
+
+/// The template for progress bars with a known length
+const BAR_TEMPLATE: &str = "{bar:40} {pos}/{len} {msg} ({per_sec}, ETA {eta})";
+
+/// The template for progress bars with an unknown length
+const SPINNER_TEMPLATE: &str = "{spinner} {pos} {msg} ({per_sec})";
+
+/// The template for progress bars counting bytes with a known length
+const BYTES_BAR_TEMPLATE: &str =
+    "{bar:40} {bytes}/{total_bytes} {percent}% ({bytes_per_sec}, ETA {eta})";
+
+/// The template for progress bars counting bytes with an unknown length
+const BYTES_SPINNER_TEMPLATE: &str = "{spinner} {bytes} ({bytes_per_sec})";
+
+/// Create a progress bar counting items, or a spinner if the total is unknown.
+/// The bar is hidden when quiet or if stdout is not a terminal.
+pub fn progress_bar(len: Option<u64>, msg: &'static str) -> ProgressBar {
+    if output::is_quiet() || !io::stdout().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let (bar, template) = match len {
+        Some(len) => (ProgressBar::new(len), BAR_TEMPLATE),
+        None => (ProgressBar::new_spinner(), SPINNER_TEMPLATE),
+    };
+    bar.set_style(ProgressStyle::with_template(template).expect("Invalid progress bar template"));
+    bar.set_message(msg);
+    bar
+}
+
+/// Create a progress bar counting bytes, or a spinner if the total is unknown.
+/// The bar is hidden unless `enabled` is set and stdout is a terminal, and always when quiet.
+pub fn bytes_progress_bar(len: Option<u64>, enabled: bool) -> ProgressBar {
+    if !enabled || output::is_quiet() || !io::stdout().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let (bar, template) = match len {
+        Some(len) => (ProgressBar::new(len), BYTES_BAR_TEMPLATE),
+        None => (ProgressBar::new_spinner(), BYTES_SPINNER_TEMPLATE),
+    };
+    bar.set_style(ProgressStyle::with_template(template).expect("Invalid progress bar template"));
+    bar
+}
@@ -0,0 +1,100 @@
+use super::algorithm::Algorithm;
+use super::codec;
+use super::error::CgnCliError;
+use super::header::read_header;
+use super::io_util::write_atomically;
+use super::output;
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+/// A representative game embedded in the binary, with the golden output of each algorithm for it
+struct Fixture {
+    name: &'static str,
+    pgn_str: &'static str,
+    golden: [&'static [u8]; 4],
+}
+
+/// Embed a fixture game and its golden output for every algorithm, in the order of `Algorithm::value_variants`
+macro_rules! fixture {
+    ($name:literal) => {
+        Fixture {
+            name: $name,
+            pgn_str: include_str!(concat!("../fixtures/", $name, ".pgn")),
+            golden: [
+                include_bytes!(concat!("../fixtures/golden/", $name, ".bincode.cgn")),
+                include_bytes!(concat!("../fixtures/golden/", $name, ".huffman.cgn")),
+                include_bytes!(concat!(
+                    "../fixtures/golden/",
+                    $name,
+                    ".dynamic-huffman.cgn"
+                )),
+                include_bytes!(concat!(
+                    "../fixtures/golden/",
+                    $name,
+                    ".opening-huffman.cgn"
+                )),
+            ],
+        }
+    };
+}
+
+/// The games checked by the self-test: a short game, a complete historical game, and a long modern game
+const FIXTURES: [Fixture; 3] = [
+    fixture!("scholars-mate"),
+    fixture!("immortal"),
+    fixture!("titled-tuesday"),
+];
+
+/// Check a golden file still decompresses to the fixture game
+fn golden_decompresses(golden: &[u8], pgn_str: &str) -> bool {
+    let Ok((header, payload)) = read_header(golden) else {
+        return false;
+    };
+    header.verify_checksum(payload).is_ok()
+        && codec::matches_original(pgn_str, header.decompress(payload))
+}
+
+/// Compress each embedded fixture game with every algorithm and compare the output to its golden file,
+/// checking the golden file also decompresses back to the game. Every mismatch is reported on stderr.
+pub fn selftest() -> Result<(), CgnCliError> {
+    let mut mismatches = 0;
+    for fixture in &FIXTURES {
+        for (&algorithm, golden) in Algorithm::value_variants().iter().zip(fixture.golden) {
+            let output = super::compress(fixture.pgn_str, algorithm)?;
+            let matches = output == golden && golden_decompresses(golden, fixture.pgn_str);
+            if matches {
+                output::ok(format_args!("{} with {}", fixture.name, algorithm));
+            } else {
+                output::error(format_args!(
+                    "mismatch in {} with {}",
+                    fixture.name, algorithm
+                ));
+                mismatches += 1;
+            }
+        }
+    }
+    if mismatches > 0 {
+        return Err(CgnCliError::SelftestFailed(mismatches));
+    }
+    output::status(format_args!(
+        "All {} golden outputs match",
+        FIXTURES.len() * Algorithm::value_variants().len()
+    ));
+    Ok(())
+}
+
+/// Write the current output of every algorithm for each fixture game into a directory as new golden files,
+/// for when the format is changed on purpose
+pub fn bless(output_dir: &Path) -> Result<(), CgnCliError> {
+    fs::create_dir_all(output_dir)?;
+    for fixture in &FIXTURES {
+        for &algorithm in Algorithm::value_variants() {
+            let output = super::compress(fixture.pgn_str, algorithm)?;
+            let output_path = output_dir.join(format!("{}.{}.cgn", fixture.name, algorithm));
+            write_atomically(&output_path, &output)?;
+            output::status(format_args!("Wrote {}", output_path.display()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use super::algorithm::Algorithm;
+use super::archive::StreamWriter;
+use super::error::CgnCliError;
+use super::header::{Extensions, Metadata};
+use super::io_util::{check_overwrite, open_reader, open_writer, Counting, Output, STDIO_PATH};
+use super::output;
+use super::pgn_db::{count_db_games, PgnDbIter};
+use super::profile;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Get the path of a numbered chunk, such as `prefix-000.cgn`
+pub fn chunk_path(output_prefix: &str, chunk: usize) -> String {
+    format!("{}-{:03}.cgn", output_prefix, chunk)
+}
+
+/// Split the games of a PGN file into a number of streamed CGN files, each decompressible on its own.
+/// Games are dealt to the chunks in turn, or if `contiguous` is set, each chunk takes the next run of games so the
+/// chunks hold the games in their original order, which needs a first pass over the input to count its games.
+/// Existing chunks are only overwritten if `force` is set.
+pub fn split(
+    input_path: &str,
+    parts: usize,
+    output_prefix: &str,
+    algorithm: Algorithm,
+    contiguous: bool,
+    force: bool,
+) -> Result<(), CgnCliError> {
+    let paths = (0..parts)
+        .map(|chunk| chunk_path(output_prefix, chunk))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        check_overwrite(Path::new(path), force)?;
+    }
+
+    // count the games to size each run of games, if the chunks are contiguous
+    let games_per_chunk = if contiguous {
+        if input_path == STDIO_PATH {
+            return Err(CgnCliError::StdinUnsupported("--contiguous"));
+        }
+        let games = count_db_games(input_path)?;
+        log::debug!(
+            "Splitting {} games of {} into {} runs",
+            games,
+            input_path,
+            parts
+        );
+        Some(games.div_ceil(parts).max(1))
+    } else {
+        None
+    };
+
+    let extensions = Extensions {
+        metadata: Some(Metadata::current()),
+        ..Extensions::default()
+    };
+    let mut chunks = paths
+        .iter()
+        .map(|path| StreamWriter::new(open_writer(path)?, algorithm, &extensions, false))
+        .collect::<Result<Vec<StreamWriter<Output>>, CgnCliError>>()?;
+    let reader = BufReader::new(open_reader(input_path)?);
+    for (i, pgn_str) in PgnDbIter::new(reader).enumerate() {
+        let chunk = match games_per_chunk {
+            Some(games_per_chunk) => (i / games_per_chunk).min(parts - 1),
+            None => i % parts,
+        };
+        chunks[chunk].write_game(&pgn_str)?;
+    }
+
+    // only move the chunks into place once every game is written
+    for (path, chunk) in paths.iter().zip(chunks) {
+        let games = chunk.games;
+        chunk.into_inner().finish()?;
+        output::status(format!("{}: {} game(s)", path, games));
+    }
+    Ok(())
+}
+
+/// An archive written by `rotate`, with the number of games and bytes written to it
+pub struct RotatedArchive {
+    pub path: String,
+    pub games: usize,
+    pub bytes: u64,
+}
+
+/// Open the next numbered streamed archive of a rotation
+fn open_archive(
+    output_prefix: &str,
+    archive: usize,
+    algorithm: Algorithm,
+    extensions: &Extensions,
+    verify: bool,
+    force: bool,
+) -> Result<(String, StreamWriter<Counting<Output>>), CgnCliError> {
+    let path = chunk_path(output_prefix, archive);
+    check_overwrite(Path::new(&path), force)?;
+    let writer = Counting::new(open_writer(&path)?);
+    let stream = StreamWriter::new(writer, algorithm, extensions, verify)?;
+    Ok((path, stream))
+}
+
+/// Close a streamed archive of a rotation, moving it into place and reporting its path and number of games
+fn close_archive(
+    path: String,
+    stream: StreamWriter<Counting<Output>>,
+) -> Result<RotatedArchive, CgnCliError> {
+    let games = stream.games;
+    let writer = stream.into_inner();
+    let bytes = writer.count;
+    writer.into_inner().finish()?;
+    output::status(format!("{}: {} game(s)", path, games));
+    Ok(RotatedArchive { path, games, bytes })
+}
+
+/// Compress the games read from a PGN database into numbered streamed archives, such as `prefix-000.cgn`, closing
+/// each archive and starting the next once it holds `max_games` games, so an unbounded input is written as bounded
+/// files. Only a single game is held in memory at once. An input with no games is written as a single empty archive.
+/// Existing archives are only overwritten if `force` is set.
+pub fn rotate<R: BufRead>(
+    reader: R,
+    output_prefix: &str,
+    max_games: usize,
+    algorithm: Algorithm,
+    extensions: &Extensions,
+    verify: bool,
+    force: bool,
+) -> Result<Vec<RotatedArchive>, CgnCliError> {
+    let mut archives = Vec::new();
+    let mut current = None;
+    let mut games = PgnDbIter::new(reader);
+    while let Some(pgn_str) = profile::time("read", || games.next()) {
+        let (_, stream) = match &mut current {
+            Some(current) => current,
+            None => current.insert(open_archive(
+                output_prefix,
+                archives.len(),
+                algorithm,
+                extensions,
+                verify,
+                force,
+            )?),
+        };
+        stream.write_game(&pgn_str)?;
+        if stream.games == max_games {
+            let (path, stream) = current.take().expect("an archive is open");
+            archives.push(close_archive(path, stream)?);
+        }
+    }
+
+    // close the last archive, or write an empty one if the input had no games
+    let current = match current {
+        Some(current) => Some(current),
+        None if archives.is_empty() => Some(open_archive(
+            output_prefix,
+            0,
+            algorithm,
+            extensions,
+            verify,
+            force,
+        )?),
+        None => None,
+    };
+    if let Some((path, stream)) = current {
+        archives.push(close_archive(path, stream)?);
+    }
+    Ok(archives)
+}
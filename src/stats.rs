@@ -0,0 +1,100 @@
+use super::algorithm::Algorithm;
+use super::benchmark::{map_games, median, Sampling, ToTake};
+use super::codec;
+use super::error::CgnCliError;
+use super::filter::GameFilter;
+use super::progress::progress_bar;
+use std::fmt::{self, Display, Formatter};
+
+/// Corpus-level compression statistics for a single algorithm
+pub struct Stats {
+    pub algorithm: Algorithm,
+    pub total_games: usize,
+    pub failed_games: usize,
+    pub total_original_bytes: usize,
+    pub total_compressed_bytes: usize,
+    pub min_compressed_bytes: usize,
+    pub max_compressed_bytes: usize,
+    pub mean_compressed_bytes: f64,
+    pub median_compressed_bytes: f64,
+    pub p95_compressed_bytes: usize,
+}
+
+impl Display for Stats {
+    /// Display the statistics
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Algorithm: {}", self.algorithm)?;
+        writeln!(f, "Total games: {}", self.total_games)?;
+        writeln!(f, "Failed games: {}", self.failed_games)?;
+        writeln!(
+            f,
+            "Total original size: {} bytes",
+            self.total_original_bytes
+        )?;
+        writeln!(
+            f,
+            "Total compressed size: {} bytes",
+            self.total_compressed_bytes
+        )?;
+        writeln!(
+            f,
+            "Overall compression ratio: {}",
+            self.total_compressed_bytes as f64 / self.total_original_bytes as f64
+        )?;
+        writeln!(
+            f,
+            "Compressed game size (bytes): min {}, max {}, mean {}, median {}, p95 {}",
+            self.min_compressed_bytes,
+            self.max_compressed_bytes,
+            self.mean_compressed_bytes,
+            self.median_compressed_bytes,
+            self.p95_compressed_bytes
+        )
+    }
+}
+
+/// Get the nearest-rank percentile of a sorted list of values, or 0 if the list is empty
+fn percentile(sorted: &[usize], p: f64) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Compress the games in a PGN database with a single algorithm and summarize the compressed sizes
+pub fn stats(db_path: &str, n: &ToTake, algorithm: Algorithm) -> Result<Stats, CgnCliError> {
+    let progress = progress_bar(n.count().map(|n| n as u64), "games");
+    let filter = GameFilter::default();
+    let sizes = map_games(
+        db_path,
+        n,
+        &filter,
+        Sampling::Sequential,
+        &progress,
+        |pgn_str| {
+            let compressed_bytes = codec::compress(algorithm, pgn_str).map_or(0, |c| c.len());
+            (pgn_str.len(), compressed_bytes)
+        },
+    )?;
+    progress.finish_and_clear();
+
+    // a compressed size of zero means the game failed to compress
+    let (ok, failed): (Vec<_>, Vec<_>) = sizes.into_iter().partition(|(_, size)| *size > 0);
+    let mut compressed_sizes = ok.iter().map(|(_, size)| *size).collect::<Vec<_>>();
+    compressed_sizes.sort_unstable();
+
+    let total_compressed_bytes = compressed_sizes.iter().sum::<usize>();
+    Ok(Stats {
+        algorithm,
+        total_games: ok.len(),
+        failed_games: failed.len(),
+        total_original_bytes: ok.iter().map(|(size, _)| *size).sum(),
+        total_compressed_bytes,
+        min_compressed_bytes: compressed_sizes.first().copied().unwrap_or(0),
+        max_compressed_bytes: compressed_sizes.last().copied().unwrap_or(0),
+        mean_compressed_bytes: total_compressed_bytes as f64 / compressed_sizes.len().max(1) as f64,
+        median_compressed_bytes: median(compressed_sizes.iter().map(|&x| x as f64).collect()),
+        p95_compressed_bytes: percentile(&compressed_sizes, 95.0),
+    })
+}
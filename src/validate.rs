@@ -0,0 +1,76 @@
+use super::error::CgnCliError;
+use pgn_reader::{BufferedReader, SanPlus, Skip, Visitor};
+use shakmaty::{Chess, Position};
+
+/// A visitor that replays the moves of a game, recording whether every move was legal
+struct GameValidator {
+    position: Chess,
+    valid: bool,
+}
+
+impl GameValidator {
+    /// Creates a new GameValidator
+    fn new() -> GameValidator {
+        GameValidator {
+            position: Chess::default(),
+            valid: true,
+        }
+    }
+}
+
+impl Visitor for GameValidator {
+    /// Whether the game was structurally valid
+    type Result = bool;
+
+    /// Reset the position at the start of each game
+    fn begin_game(&mut self) {
+        self.position = Chess::default();
+        self.valid = true;
+    }
+
+    /// Play the move, marking the game as invalid if it is illegal in the current position
+    fn san(&mut self, san_plus: SanPlus) {
+        if !self.valid {
+            return;
+        }
+        match san_plus.san.to_move(&self.position) {
+            Ok(m) => self.position.play_unchecked(&m),
+            Err(_) => self.valid = false,
+        }
+    }
+
+    /// Skip variations, as only the main line is stored by the cgn library
+    fn begin_variation(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    /// Report whether the game was valid
+    fn end_game(&mut self) -> Self::Result {
+        self.valid
+    }
+}
+
+/// Check that decompressed PGN data parses as a sequence of games made up of legal moves.
+/// Returns the index of the first invalid game as an error. Empty data is valid, as it holds no games.
+pub fn validate_pgn(pgn_str: &str) -> Result<(), CgnCliError> {
+    if pgn_str.trim().is_empty() {
+        return Ok(());
+    }
+
+    let mut reader = BufferedReader::new_cursor(pgn_str.as_bytes());
+    let mut validator = GameValidator::new();
+
+    let mut games = 0;
+    while let Some(valid) = reader.read_game(&mut validator)? {
+        if !valid {
+            return Err(CgnCliError::InvalidGame(games));
+        }
+        games += 1;
+    }
+
+    // data that is not empty must hold at least one game
+    if games == 0 {
+        return Err(CgnCliError::InvalidGame(0));
+    }
+    Ok(())
+}
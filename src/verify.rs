@@ -0,0 +1,71 @@
+use super::algorithm::AlgorithmChoice;
+use super::archive;
+use super::error::CgnCliError;
+use super::header::{read_header, resolve_header};
+use super::io_util::open_reader;
+use super::output;
+use std::io::Read;
+
+/// The number of bytes of context shown either side of the first difference
+const CONTEXT_LEN: usize = 20;
+
+/// Decompress a CGN file and check it is byte-for-byte identical to the original PGN file
+pub fn verify(input_path: &str, original_path: &str) -> Result<(), CgnCliError> {
+    // read the compressed and original files
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+    let mut original = Vec::new();
+    open_reader(original_path)?.read_to_end(&mut original)?;
+
+    // decompress the compressed file
+    let (header, payload) = resolve_header(&compressed_data, AlgorithmChoice::Auto)?;
+    let pgn_str = archive::decompress(&header, payload)?;
+
+    // find the first differing byte, if any
+    let decompressed = pgn_str.as_bytes();
+    let offset = match first_difference(decompressed, &original) {
+        Some(offset) => offset,
+        None => {
+            output::ok(format_args!("{} matches {}", input_path, original_path));
+            return Ok(());
+        }
+    };
+
+    Err(CgnCliError::VerificationFailed {
+        offset,
+        expected: context(&original, offset),
+        found: context(decompressed, offset),
+    })
+}
+
+/// Check a CGN file against the checksum in its header, without decompressing it
+pub fn verify_checksum(input_path: &str) -> Result<(), CgnCliError> {
+    let mut compressed_data = Vec::new();
+    open_reader(input_path)?.read_to_end(&mut compressed_data)?;
+
+    let (header, payload) = read_header(&compressed_data)?;
+    if header.checksum.is_none() {
+        return Err(CgnCliError::NoChecksum);
+    }
+    header.verify_checksum(payload)?;
+    output::ok(format_args!("{} matches its checksum", input_path));
+    Ok(())
+}
+
+/// Find the offset of the first byte that differs between two byte slices
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(offset) => Some(offset),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Get the bytes surrounding an offset as an escaped string
+fn context(data: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(CONTEXT_LEN).min(data.len());
+    let end = (offset + CONTEXT_LEN).min(data.len());
+    String::from_utf8_lossy(&data[start..end])
+        .escape_debug()
+        .to_string()
+}